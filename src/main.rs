@@ -1,72 +1,354 @@
+use std::io::Write;
+use std::time::Instant;
+
 use crate::sql_engine::sql_parser::SqlParser;
-use crate::sql_engine::sql_structs::SqlStmt;
+use crate::sql_engine::sql_structs::{SqlStmt, Value};
 use crate::storage_engine::common::*;
+use crate::utils::utils::{edit_distance, read_statement};
 
 mod sql_engine;
 mod storage_engine;
 mod utils;
 
-fn main() -> Result<(), String> {
-    let mut table_manager = TableManager::new();
-    loop {
-        let input= new_input_buffer();
+/*
+    page size used when the data folder does not exist yet, e.g. `rust_sqlite --page-size=8192`
+ */
+fn parse_page_size_arg() -> Result<Option<usize>, String> {
+    match std::env::args().find_map(|arg| arg.strip_prefix("--page-size=").map(String::from)) {
+        None => Ok(None),
+        Some(value) => match value.parse::<usize>() {
+            Ok(page_size) => Ok(Some(page_size)),
+            Err(_) => Err(format!("`{}` is not a valid page size.", value)),
+        },
+    }
+}
 
-        if input == "flush;" {
-            table_manager.flush_to_disk();
-            continue;
-        } else if input.starts_with("btree;") {
-            table_manager.print_btree(input.split_once(" ").unwrap().1);
-            continue;
-        } else if input == "exit;" {
-            break;
+/*
+    writes the rows of a SELECT in the format chosen by `.mode`
+ */
+fn write_result(result: &SelectResult, settings: &Settings, writer: &mut dyn Write) -> std::io::Result<()> {
+    match settings.output {
+        OutputMode::TABLE => result.print(settings.max_rows, writer),
+        OutputMode::CSV => result.to_csv(writer),
+        OutputMode::JSON => result.to_json(writer),
+        OutputMode::LIST => result.to_list(writer),
+    }
+}
+
+/*
+    parses and executes one statement, printing its result
+ */
+fn execute_sql(input: String, table_manager: &mut TableManager) -> Result<(), String> {
+    match SqlParser::parse_sql(input)? {
+        SqlStmt::SELECT(mut select) => {
+            let result = select.execute(table_manager)?;
+            if write_result(&result, table_manager.settings(), &mut std::io::stdout()).is_err() {
+                return Err(String::from("Can not write the result."));
+            }
+        }
+        SqlStmt::INSERT(mut insert) => {
+            insert.execute(table_manager)?;
+            println!("{:?}", "Data inserted.");
+        }
+        SqlStmt::CREATE(mut create) => {
+            if create.execute(table_manager)? {
+                println!("{:?}", "Table created.");
+            } else {
+                println!("{:?}", "Table already exists.");
+            }
+        }
+        SqlStmt::ALTER(alter) => {
+            alter.execute(table_manager)?;
+            println!("{:?}", "Table altered.");
         }
+        SqlStmt::DROP(drop) => {
+            if drop.execute(table_manager)? {
+                println!("{:?}", "Table dropped.");
+            } else {
+                println!("{:?}", "Table does not exist.");
+            }
+        }
+        SqlStmt::REINDEX(reindex) => {
+            let rows = reindex.execute(table_manager)?;
+            println!("Index of table `{}` rebuilt with {} rows.", reindex.table(), rows);
+        }
+        SqlStmt::PRAGMA(pragma) => {
+            let value = pragma.execute(table_manager)?;
+            println!("{} = {}", pragma.name(), value.to_sql_literal());
+        }
+    }
+    Ok(())
+}
 
-        let sql = match SqlParser::parse_sql(input) {
-            Ok(sql) => sql,
-            Err(e) => {
-                println!("{}", e);
-                continue;
+/*
+    runs every statement of a script, statements end with a `;` at the end of a line like in the REPL.
+    Stops at the first failing statement.
+ */
+fn read_script(path: &str, table_manager: &mut TableManager) -> Result<(), String> {
+    let script = match std::fs::read_to_string(path) {
+        Ok(script) => script,
+        Err(_) => return Err(format!("Can not read script `{}`.", path)),
+    };
+
+    let mut statement = String::new();
+    let mut first_line = 0;
+    for (index, line) in script.lines().enumerate() {
+        if line.trim().is_empty() && statement.is_empty() {
+            continue;
+        }
+        if statement.is_empty() {
+            first_line = index + 1;
+        }
+        statement.push_str(line);
+        statement.push('\n');
+        if line.trim().ends_with(";") {
+            if let Err(e) = execute_sql(statement.trim().to_string(), table_manager) {
+                return Err(format!("Error in `{}` at line {}: {}", path, first_line, e));
             }
-        };
+            statement.clear();
+        }
+    }
+
+    if !statement.trim().is_empty() {
+        return Err(format!("Statement at line {} of `{}` does not end with a `;`.", first_line, path));
+    }
+    Ok(())
+}
+
+/*
+    the line printed after a statement when the timer is on
+ */
+fn run_time(settings: &Settings, start: Instant) -> Option<String> {
+    if !settings.timer {
+        return None;
+    }
+    Some(format!("Run Time: {:.3} ms", start.elapsed().as_secs_f64() * 1000.0))
+}
+
+#[allow(clippy::upper_case_acronyms)]
+enum Command {
+    // the input is a SQL statement
+    SQL,
+    HANDLED,
+    EXIT,
+}
 
-        match sql {
-            SqlStmt::SELECT(mut select) => {
-                match select.execute(&mut table_manager) {
-                    Ok(result) => {
-                        result.print();
-                    }
-                    Err(e) => {
-                        println!("{}", e)
-                    }
-                };
+const META_COMMANDS: [&str; 3] = ["flush", "btree", "exit"];
+
+/*
+    `name arg;` -> ("name", "arg")
+ */
+fn split_command(input: &str) -> (&str, &str) {
+    let input = input.trim_end_matches(';').trim();
+    match input.split_once(|c: char| c.is_whitespace() || c == ';') {
+        Some((name, arg)) => (name, arg.trim_matches(|c: char| c.is_whitespace() || c == ';')),
+        None => (input, ""),
+    }
+}
+
+/*
+    The message for an unknown dot command or a word close to a meta-command keyword,
+    None when the input is left to the SQL parser.
+ */
+fn unknown_command(name: &str) -> Option<String> {
+    if name.starts_with('.') {
+        return Some(format!("Unknown command `{}`.", name));
+    }
+    META_COMMANDS
+        .iter()
+        .find(|c| edit_distance(name, c) <= 2)
+        .map(|command| format!("Unknown command `{}`, did you mean `{}`?", name, command))
+}
+
+/*
+    Runs a meta-command: the dot commands and the META_COMMANDS keywords.
+    A word close to a meta-command keyword is reported instead of being parsed as SQL.
+ */
+fn run_command(raw_input: &str, table_manager: &mut TableManager) -> Command {
+    let input = raw_input.to_lowercase();
+    let (name, arg) = split_command(&input);
+    match name {
+        "flush" => table_manager.flush_to_disk(),
+        "btree" => table_manager.print_btree(arg),
+        "exit" => return Command::EXIT,
+        ".dump" => match table_manager.dump_table(arg) {
+            Ok(statements) => statements.iter().for_each(|s| println!("{}", s)),
+            Err(e) => println!("{}", e),
+        },
+        ".repair" => match table_manager.repair(arg) {
+            Ok(report) => report.iter().for_each(|s| println!("{}", s)),
+            Err(e) => println!("{}", e),
+        },
+        ".verify" => match table_manager.verify(arg) {
+            Ok(_) => println!("Table `{}` is consistent.", arg),
+            Err(e) => println!("{}", e),
+        },
+        ".schema" => match table_manager.create_sql(arg) {
+            Ok(sql) => println!("{}", sql),
+            Err(e) => println!("{}", e),
+        },
+        ".layout" => match table_manager.get_table_metadata(arg) {
+            Ok(meta) => meta
+                .layout()
+                .iter()
+                .for_each(|(name, offset, size)| println!("{}: offset {}, {} bytes", name, offset, size)),
+            Err(e) => println!("{}", e),
+        },
+        // shortcuts for `pragma timer = on|off;` and `pragma autocommit = on|off;`
+        ".timer" | ".autocommit" => match arg {
+            "on" | "off" => {
+                if let Err(e) = table_manager.set_setting(&name[1..], &Value::BOOL(arg == "on")) {
+                    println!("{}", e);
+                }
             }
-            SqlStmt::INSERT(mut insert) => {
-                println!(
-                    "{:?}",
-                    match insert.execute(&mut table_manager) {
-                        Ok(_) => {
-                            String::from("Data inserted.")
-                        }
-                        Err(e) => {
-                            e
-                        }
-                    }
-                );
+            _ => println!("Usage: {} on|off;", name),
+        },
+        // `.output` is the former name of `.mode`
+        ".mode" | ".output" => match arg {
+            "table" => table_manager.set_output(OutputMode::TABLE),
+            "csv" => table_manager.set_output(OutputMode::CSV),
+            "json" => table_manager.set_output(OutputMode::JSON),
+            "list" => table_manager.set_output(OutputMode::LIST),
+            _ => println!("Usage: {} table|csv|json|list;", name),
+        },
+        ".backup" => {
+            // the paths keep their case
+            let (_, paths) = split_command(raw_input);
+            match paths.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [source, destination] => match table_manager.backup(source, destination) {
+                    Ok(files) => println!("{} files copied to `{}`.", files, destination),
+                    Err(e) => println!("{}", e),
+                },
+                _ => println!("Usage: .backup <source> <destination>;"),
             }
-            SqlStmt::CREATE(create) => {
-                println!(
-                    "{:?}",
-                    match create.execute(&mut table_manager) {
-                        Ok(_) => {
-                            String::from("Table created.")
-                        }
-                        Err(e) => {
-                            e
-                        }
-                    }
-                );
+        }
+        ".read" => {
+            // the path keeps its case
+            let (_, path) = split_command(raw_input);
+            if let Err(e) = read_script(path, table_manager) {
+                println!("{}", e);
             }
         }
+        _ => match unknown_command(name) {
+            Some(message) => println!("{}", message),
+            None => return Command::SQL,
+        },
+    }
+    Command::HANDLED
+}
+
+fn main() -> Result<(), String> {
+    let mut table_manager = TableManager::new(parse_page_size_arg()?)?;
+    loop {
+        let raw_input = match read_statement(&mut std::io::stdin().lock(), &mut std::io::stdout(), "sql>", ">") {
+            None => break,
+            Some(input) if input.is_empty() => continue,
+            Some(input) => input,
+        };
+        match run_command(&raw_input, &mut table_manager) {
+            Command::SQL => {}
+            Command::HANDLED => continue,
+            Command::EXIT => break,
+        }
+
+        let start = Instant::now();
+        if let Err(e) = execute_sql(raw_input, &mut table_manager) {
+            println!("{}", e);
+        }
+
+        if let Some(run_time) = run_time(table_manager.settings(), start) {
+            println!("{}", run_time);
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::sql_engine::sql_structs::Value;
+    use crate::utils::test_utils::TestDb;
+    use crate::{read_script, run_command, run_time, unknown_command, write_result, Command};
+
+    #[test]
+    fn timer_prints_the_run_time_once_enabled() {
+        let mut db = TestDb::open(&[]);
+        assert_eq!(run_time(db.table_manager.settings(), Instant::now()), None);
+
+        run_command(".timer on;", &mut db.table_manager);
+        let line = run_time(db.table_manager.settings(), Instant::now()).unwrap();
+        assert!(line.starts_with("Run Time: ") && line.ends_with(" ms"), "{}", line);
+
+        run_command(".timer off;", &mut db.table_manager);
+        assert_eq!(run_time(db.table_manager.settings(), Instant::now()), None);
+    }
+
+    #[test]
+    fn script_runs_until_its_first_error() {
+        let mut db = TestDb::open(&["main_script"]);
+        let path = std::env::temp_dir().join("main_script.sql");
+        let script = "create table main_script (id int primary key,\n  name text(8));\n\n\
+                      insert into main_script (id, name) values (1, 'a');\n\
+                      insert into main_script (id, name) values (2, 'b');\n";
+        std::fs::write(&path, script).unwrap();
+        read_script(path.to_str().unwrap(), &mut db.table_manager).unwrap();
+        assert_eq!(
+            db.query("select * from main_script;"),
+            vec![
+                vec![Value::INT(1), Value::TEXT(String::from("a"))],
+                vec![Value::INT(2), Value::TEXT(String::from("b"))],
+            ]
+        );
+
+        // the error names the first line of the failing statement, the following ones are not run
+        std::fs::write(&path, "insert into main_script (id, name) values (3, 'c');\nselect nothing\nfrom main_script;\ninsert into main_script (id, name) values (4, 'd');\n").unwrap();
+        let error = read_script(path.to_str().unwrap(), &mut db.table_manager).err().unwrap();
+        assert!(error.contains("at line 2"), "{}", error);
+        assert_eq!(db.query("select id from main_script where id > 2;"), vec![vec![Value::INT(3)]]);
+        assert!(read_script("main_script_missing.sql", &mut db.table_manager).is_err());
+    }
+
+    #[test]
+    fn misspelled_command_is_not_parsed_as_sql() {
+        let mut db = TestDb::open(&[]);
+        // `flsuh` is reported as a close-but-wrong `flush` instead of reaching the SQL parser
+        for input in ["flsuh;", "exti;", ".unknown;", "flush;"] {
+            assert!(matches!(run_command(input, &mut db.table_manager), Command::HANDLED), "{}", input);
+        }
+        assert!(matches!(run_command("exit;", &mut db.table_manager), Command::EXIT));
+        assert_eq!(unknown_command("flsuh").unwrap(), "Unknown command `flsuh`, did you mean `flush`?");
+        assert_eq!(unknown_command(".unknown").unwrap(), "Unknown command `.unknown`.");
+        assert_eq!(unknown_command("select"), None);
+        for input in ["select 1;", "insert into t values (1);", "drop table t;"] {
+            assert!(matches!(run_command(input, &mut db.table_manager), Command::SQL), "{}", input);
+        }
+    }
+
+    #[test]
+    fn mode_changes_how_results_are_written() {
+        let mut db = TestDb::open(&["main_mode"]);
+        db.run("create table main_mode (id int primary key, name text(8));").unwrap();
+        db.run("insert into main_mode (id, name) values (1, 'a,b');").unwrap();
+        db.run("insert into main_mode (id, name) values (2, 'c');").unwrap();
+        let output = |db: &mut TestDb| {
+            let result = db.run("select * from main_mode;").unwrap().unwrap();
+            let mut written = vec![];
+            write_result(&result, db.table_manager.settings(), &mut written).unwrap();
+            String::from_utf8(written).unwrap()
+        };
+
+        let table = output(&mut db);
+        assert!(table.starts_with("+----+------+\n| id | name |"), "{}", table);
+        run_command(".mode csv;", &mut db.table_manager);
+        assert_eq!(output(&mut db), "id,name\n1,\"a,b\"\n2,c\n");
+        run_command(".mode json;", &mut db.table_manager);
+        assert_eq!(output(&mut db), "[\n  {\"id\": 1, \"name\": \"a,b\"},\n  {\"id\": 2, \"name\": \"c\"}\n]\n");
+        run_command(".mode list;", &mut db.table_manager);
+        assert_eq!(output(&mut db), "1|a,b\n2|c\n");
+        run_command(".mode html;", &mut db.table_manager);
+        assert_eq!(output(&mut db), "1|a,b\n2|c\n");
+        run_command(".mode table;", &mut db.table_manager);
+        assert_eq!(output(&mut db), table);
+    }
+}
@@ -1,6 +1,6 @@
-use crate::sql_engine::sql_structs::{Condition, ConditionCluster, ConditionExpr, CreateStmt, DataType, FieldDefinition, InsertStmt, LogicalOperator, Operator, Order, OrderByCluster, OrderByExpr, SelectStmt, SqlStmt, Value, WhereExpr};
+use crate::sql_engine::sql_structs::{AlterAction, AlterStmt, ArithmeticOperator, Collation, Condition, ConditionCluster, ConditionExpr, CreateStmt, DataType, DropStmt, Expr, FieldDefinition, InsertStmt, LogicalOperator, Operator, Order, OrderByCluster, OrderByExpr, PragmaStmt, ReindexStmt, ScalarFunction, SelectStmt, SqlStmt, Value, WhereExpr};
 use crate::sql_engine::tokenizer::*;
-use crate::storage_engine::config::FIELD_NAME_SIZE;
+use crate::storage_engine::config::{BLOB_DEFAULT_SIZE, FIELD_NAME_SIZE, MAX_NESTING_DEPTH, TEXT_DEFAULT_SIZE};
 
 #[derive(Clone)]
 pub struct SqlParser {}
@@ -29,6 +29,22 @@ impl SqlParser {
             let mut create_stmt_parser = CreateStmtParser { tokenizer };
             let create_stmt = create_stmt_parser.parse()?;
             Ok(SqlStmt::CREATE(create_stmt))
+        } else if first_token.value() == ALTER {
+            let mut alter_stmt_parser = AlterStmtParser { tokenizer };
+            let alter_stmt = alter_stmt_parser.parse()?;
+            Ok(SqlStmt::ALTER(alter_stmt))
+        } else if first_token.value() == PRAGMA {
+            let mut pragma_stmt_parser = PragmaStmtParser { tokenizer };
+            let pragma_stmt = pragma_stmt_parser.parse()?;
+            Ok(SqlStmt::PRAGMA(pragma_stmt))
+        } else if first_token.value() == DROP {
+            let mut drop_stmt_parser = DropStmtParser { tokenizer };
+            let drop_stmt = drop_stmt_parser.parse()?;
+            Ok(SqlStmt::DROP(drop_stmt))
+        } else if first_token.value() == REINDEX {
+            let mut reindex_stmt_parser = ReindexStmtParser { tokenizer };
+            let reindex_stmt = reindex_stmt_parser.parse()?;
+            Ok(SqlStmt::REINDEX(reindex_stmt))
         } else {
             Err(String::from("Unknown sql statement."))
         }
@@ -41,67 +57,156 @@ struct SelectStmtParser {
 
 impl SelectStmtParser {
     fn parse(&mut self) -> Result<SelectStmt, String> {
-        let from = self.tokenizer.next_token()?;
+        self.tokenizer.next_token()?;
+        let distinct_on = if self.tokenizer.current_token().value() == DISTINCT {
+            let distinct_on = self.parse_distinct_on()?;
+            self.tokenizer.next_token()?; // skip ')'
+            distinct_on
+        } else {
+            vec![]
+        };
 
+        let from = self.tokenizer.current_token();
         if from.token_type() == TokenType::Keyword && from.value() == FROM {
             return Err(String::from("Syntax error, no selected columns found."));
         }
 
         let selected_fields = if self.tokenizer.current_token().token_type() == TokenType::AllColumn {
             self.tokenizer.next_token()?; // skip FROM
-            vec![String::from("*")]
+            vec![Expr::Field(String::from("*"))]
         } else {
             self.parse_selected_fields()?
         };
 
-        let table = self.tokenizer.next_token()?.value().into();
-        self.tokenizer.next_token()?;
+        // without FROM the statement already ended on the selected fields, or on LIMIT
+        let table = if self.tokenizer.current_token().value() == FROM {
+            let table = self.tokenizer.next_token()?.value().into();
+            self.tokenizer.next_token()?;
+            Some(table)
+        } else {
+            None
+        };
         let where_stmt: Option<WhereExpr> =
-            if !self.tokenizer.has_more() || self.tokenizer.current_token().value() == ORDER {
+            if !self.tokenizer.has_more() || [ORDER, LIMIT].contains(&self.tokenizer.current_token().value()) {
                 None
             } else {
                 Some(
                     WhereStmtParser {
-                        tokenizer: &mut self.tokenizer
+                        tokenizer: &mut self.tokenizer,
+                        depth: 0,
                     }.parse()?,
                 )
             };
 
-        let order_by_stmt: Option<OrderByCluster> = if !self.tokenizer.has_more() {
-            None
-        } else {
-            Some(
-                OrderByExprParser {
-                    tokenizer: &mut self.tokenizer,
-                }.parse()?,
-            )
-        };
+        let order_by_stmt: Option<OrderByCluster> =
+            if !self.tokenizer.has_more() || self.tokenizer.current_token().value() == LIMIT {
+                None
+            } else {
+                Some(
+                    OrderByExprParser {
+                        tokenizer: &mut self.tokenizer,
+                    }.parse()?,
+                )
+            };
 
-        Ok(SelectStmt::new(
+        let mut select_stmt = SelectStmt::new(
             selected_fields,
             table,
             where_stmt,
             order_by_stmt,
-        ))
+        );
+        if self.tokenizer.has_more() {
+            select_stmt.set_limit(self.parse_limit()?);
+        }
+        select_stmt.set_distinct_on(distinct_on);
+        Ok(select_stmt)
     }
 
-    fn parse_selected_fields(&mut self) -> Result<Vec<String>, String> {
-        let mut fields = Vec::<String>::new();
-        while self.tokenizer.has_more() && self.tokenizer.current_token().value() != FROM {
-            let field_token = self.tokenizer.current_token();
-            let field_name = field_token.value().to_string();
+    /*
+        `DISTINCT ON (expr, ...)`, the tokenizer is left on the closing `)`
+     */
+    fn parse_distinct_on(&mut self) -> Result<Vec<Expr>, String> {
+        if self.tokenizer.next_token()?.value() != ON {
+            return Err(String::from("Only DISTINCT ON (...) is supported, `ON` is expected after DISTINCT."));
+        }
+        if self.tokenizer.next_token()?.token_type() != TokenType::Lparen {
+            return Err(String::from("Syntax error, `(` expected after DISTINCT ON."));
+        }
+
+        let mut exprs = vec![];
+        loop {
+            self.tokenizer.next_token()?; // skip '(' or ','
+            exprs.push(ExprParser {
+                tokenizer: &mut self.tokenizer,
+                depth: 0,
+            }.parse()?);
+            match self.tokenizer.current_token().token_type() {
+                TokenType::COMMA => {}
+                TokenType::Rparen => return Ok(exprs),
+                _ => {
+                    return Err(format!(
+                        "Syntax error in DISTINCT ON, ',' or ')' is expected but found `{}`.",
+                        self.tokenizer.current_token().value()
+                    ))
+                }
+            }
+        }
+    }
+
+    /*
+        `LIMIT n` ending the statement, n >= 0
+     */
+    fn parse_limit(&mut self) -> Result<usize, String> {
+        if self.tokenizer.current_token().value() != LIMIT {
+            return Err(format!(
+                "Syntax error, expected LIMIT but found `{}`.",
+                self.tokenizer.current_token().value()
+            ));
+        }
+        let token = self.tokenizer.next_token()?;
+        let limit = match token.token_type() {
+            TokenType::Number => token.value().parse::<usize>().ok(),
+            _ => None,
+        };
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return Err(format!("LIMIT expects a non-negative integer but found `{}`.", token.value())),
+        };
+        if self.tokenizer.next_token()?.token_type() != TokenType::EOF {
+            return Err(format!(
+                "Syntax error, unexpected `{}` after LIMIT.",
+                self.tokenizer.current_token().value()
+            ));
+        }
+        Ok(limit)
+    }
+
+    fn parse_selected_fields(&mut self) -> Result<Vec<Expr>, String> {
+        let mut fields = Vec::<Expr>::new();
+        while self.tokenizer.has_more() && ![FROM, LIMIT].contains(&self.tokenizer.current_token().value()) {
+            let field = ExprParser {
+                tokenizer: &mut self.tokenizer,
+                depth: 0,
+            }.parse()?;
 
-            if fields.contains(&field_name) {
-                return Err(format!("Column `{field_name}` has already be selected."));
+            // a column can be selected again, only a repeated `*` is a mistake
+            if field.is_field("*") && fields.iter().any(|f| f.is_field("*")) {
+                return Err(format!("Column `{field}` has already be selected."));
             }
 
-            fields.push(field_name);
+            fields.push(field);
 
-            let next = self.tokenizer.next_token()?;
+            let next = self.tokenizer.current_token();
 
             if next.token_type() == TokenType::COMMA {
-                self.tokenizer.next_token()?; // skip ','
-            } else if next.value() != FROM {
+                let next = self.tokenizer.next_token()?; // skip ','
+                if [FROM, LIMIT].contains(&next.value()) || next.token_type() == TokenType::EOF {
+                    return Err(format!(
+                        "Syntax error, dangling `,` before `{}` in the selected fields.",
+                        next.value()
+                    ));
+                }
+            } else if next.value() != FROM && next.value() != LIMIT && next.token_type() != TokenType::EOF {
                 return Err(String::from(
                     "Syntax error, there must be a ',' between two selected fields.",
                 ));
@@ -114,6 +219,8 @@ impl SelectStmtParser {
 
 struct WhereStmtParser<'a> {
     tokenizer: &'a mut Tokenizer,
+    // parentheses opened around the current condition
+    depth: usize,
 }
 
 impl<'a> WhereStmtParser<'a> {
@@ -126,101 +233,146 @@ impl<'a> WhereStmtParser<'a> {
         }
         self.tokenizer.next_token()?;
 
-        let mut condition_exprs = Vec::<ConditionCluster>::new();
-        let mut logical_op = Some(LogicalOperator::AND);
-
-        while self.tokenizer.has_more() {
-            let condition_expr = self.parse_condition_cluster(logical_op.unwrap())?;
-            condition_exprs.push(condition_expr);
-            logical_op = None;
-
-            if self.tokenizer.current_token().value() == ORDER {
-                break;
-            } else if self.tokenizer.current_token().token_type() == TokenType::EOF {
-                break;
-            }
-
-            logical_op = Some(LogicalOperator::try_from(
-                self.tokenizer.current_token().value(),
-            )?);
-        }
-
-        if logical_op.is_some() {
+        if !self.tokenizer.has_more() || [ORDER, LIMIT].contains(&self.tokenizer.current_token().value()) {
             return Err(String::from(
-                "Syntax error, Where statement is not complete.",
+                "Syntax error, empty Where statement detected.",
             ));
         }
 
-        if condition_exprs.is_empty() {
-            return Err(String::from(
-                "Syntax error, empty Where statement detected.",
-            ));
+        let condition = self.parse_or()?;
+
+        if self.tokenizer.has_more() && ![ORDER, LIMIT].contains(&self.tokenizer.current_token().value()) {
+            return Err(match self.tokenizer.current_token().token_type() {
+                TokenType::Rparen => String::from("Syntax error, Where statement has an unmatched ')'."),
+                _ => String::from("Do you mean ORDER BY?"),
+            });
         }
 
-        Ok(WhereExpr::new(condition_exprs))
+        Ok(WhereExpr::new(condition))
     }
 
-    fn parse_condition_cluster(&mut self, cluster_operator: LogicalOperator) -> Result<ConditionCluster, String> {
-        let mut conditions = Vec::<Condition>::new();
-        let mut more_than_one = false;
-        let mut logical_op = LogicalOperator::AND;
-        let mut par = false;
-
-        if self.tokenizer.current_token().token_type() == TokenType::Lparen {
-            self.tokenizer.next_token()?; //skip '('
-            par = true;
+    /*
+        or_condition  := and_condition (OR and_condition)*
+        and_condition := primary (AND primary)*
+        primary       := [NOT] '(' or_condition ')' | comparison
+        AND binds tighter than OR: `a=1 or b=2 and c=3` is `a=1 or (b=2 and c=3)`.
+     */
+    fn parse_or(&mut self) -> Result<Condition, String> {
+        let mut conditions = vec![self.parse_and()?];
+        while self.tokenizer.current_token().value() == OR {
+            self.tokenizer.next_token()?; // skip OR
+            conditions.push(self.parse_and()?);
         }
+        Ok(Self::group(LogicalOperator::OR, conditions))
+    }
 
-        while self.tokenizer.has_more() && self.tokenizer.current_token().value() != ORDER {
-            if more_than_one {
-                logical_op = LogicalOperator::try_from(self.tokenizer.current_token().value())?;
-                self.tokenizer.next_token()?;
-            }
-            conditions.push(Condition::Expr(self.parse_expr(logical_op)?));
+    fn parse_and(&mut self) -> Result<Condition, String> {
+        let mut conditions = vec![self.parse_primary()?];
+        while self.tokenizer.current_token().value() == AND {
+            self.tokenizer.next_token()?; // skip AND
+            conditions.push(self.parse_primary()?);
+        }
+        Ok(Self::group(LogicalOperator::AND, conditions))
+    }
 
-            if par && self.tokenizer.current_token().value() == OR {
-                conditions.push(Condition::Cluster(self.parse_condition_cluster(LogicalOperator::OR)?));
-            } else if self.tokenizer.current_token().value() == OR  {
-                break;
+    fn parse_primary(&mut self) -> Result<Condition, String> {
+        if self.tokenizer.current_token().value() == NOT {
+            if self.tokenizer.next_token()?.token_type() != TokenType::Lparen {
+                return Err(format!(
+                    "Syntax error, `(` expected after NOT but `{}` was found.",
+                    self.tokenizer.current_token().value()
+                ));
             }
+            let condition = self.parse_primary()?;
+            return Ok(Condition::Cluster(ConditionCluster::negate(condition)));
+        }
+        if self.tokenizer.current_token().token_type() != TokenType::Lparen {
+            return Ok(Condition::Expr(self.parse_expr(LogicalOperator::AND)?));
+        }
 
-            if self.tokenizer.current_token().token_type() == TokenType::Rparen {
-                break;
+        // `(` opens a group of conditions or the left-hand side of a comparison, e.g. `(price + 1) * 2 > 3`
+        let (tokenizer, depth) = (self.tokenizer.clone(), self.depth);
+        match self.parse_group() {
+            Ok(condition) => Ok(condition),
+            Err(group_error) => {
+                *self.tokenizer = tokenizer;
+                self.depth = depth;
+                // a statement that is neither reports the error of the group
+                self.parse_expr(LogicalOperator::AND).map(Condition::Expr).map_err(|_| group_error)
             }
-
-            more_than_one = true;
         }
+    }
 
-        if  self.tokenizer.has_more() && self.tokenizer.current_token().value() != ORDER {
-            return Err(String::from("Do you mean ORDER BY?"))
+    fn parse_group(&mut self) -> Result<Condition, String> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(format!("Where statement is nested deeper than {} parentheses.", MAX_NESTING_DEPTH));
+        }
+        self.tokenizer.next_token()?; // skip '('
+        let condition = self.parse_or()?;
+        if self.tokenizer.current_token().token_type() != TokenType::Rparen {
+            return Err(format!(
+                "Syntax error, Where statement is incorrectly formatted, expected a ')' but found `{}`.",
+                self.tokenizer.current_token().value()
+            ));
         }
+        self.tokenizer.next_token()?; // skip ')'
+        self.depth -= 1;
+        Ok(condition)
+    }
 
-        if par && self.tokenizer.current_token().token_type() != TokenType::Rparen {
-            return Err(format!("Syntax error, Where statement is incorrectly formatted, expected a ')' but found {}", self.tokenizer.current_token().value()));
-        } else if par {
-            self.tokenizer.next_token()?; //skip ')'
+    /*
+        A single condition is kept as is, several ones become a cluster joined by `logical_operator`.
+        Nested clusters of the same operator are flattened, `a and (b and c)` is `a and b and c`.
+     */
+    fn group(logical_operator: LogicalOperator, conditions: Vec<Condition>) -> Condition {
+        if conditions.len() == 1 {
+            return conditions.into_iter().next().unwrap();
         }
 
-        Ok(ConditionCluster::new(cluster_operator, conditions))
+        let mut flattened = Vec::<Condition>::new();
+        for condition in conditions {
+            match condition {
+                Condition::Cluster(cluster) if cluster.logical_operator == logical_operator && !cluster.negated => {
+                    flattened.extend(cluster.conditions)
+                }
+                condition => flattened.push(condition),
+            }
+        }
+        for condition in flattened.iter_mut() {
+            condition.set_logical_operator(logical_operator);
+        }
+        Condition::Cluster(ConditionCluster::new(logical_operator, flattened))
     }
 
     fn parse_expr(
         &mut self,
         logical_operator: LogicalOperator,
     ) -> Result<ConditionExpr, String> {
-        let field = self.tokenizer.current_token().value().to_string();
-        self.tokenizer.next_token()?;
+        let left = ExprParser {
+            tokenizer: self.tokenizer,
+            depth: 0,
+        }.parse()?;
         let op = {
             OperatorParser {
-                tokenizer: &mut self.tokenizer,
+                tokenizer: self.tokenizer,
             }.parse()?
         };
         self.tokenizer.next_token()?;
-        let v = ValueParser {
-            tokenizer: &mut self.tokenizer,
-        }.parse()?;
-        self.tokenizer.next_token()?;
-        Ok(ConditionExpr::new(logical_operator, field, op, v))
+        // a field on the right-hand side is compared per row, e.g. `a > b`
+        let right = if self.tokenizer.current_token().token_type() == TokenType::Ident {
+            ExprParser {
+                tokenizer: self.tokenizer,
+                depth: 0,
+            }.parse()?
+        } else {
+            let v = ValueParser {
+                tokenizer: self.tokenizer,
+            }.parse()?;
+            self.tokenizer.next_token()?;
+            Expr::Literal(v)
+        };
+        Ok(ConditionExpr::new(logical_operator, left, op, right))
     }
 }
 
@@ -260,15 +412,25 @@ impl InsertStmtParser {
         let mut fields = Vec::<String>::new();
 
         if self.tokenizer.next_token()?.token_type() == TokenType::Lparen {
-            self.tokenizer.next_token()?;
+            if self.tokenizer.next_token()?.token_type() == TokenType::Rparen {
+                return Err(String::from("Syntax error, the list of inserted fields is empty."));
+            }
             while self.tokenizer.has_more() {
                 let field = self.tokenizer.current_token().value();
                 fields.push(field.to_string());
-                self.tokenizer.next_token()?;
-                if self.tokenizer.current_token().token_type() == TokenType::COMMA {
-                    self.tokenizer.next_token()?;
-                } else if self.tokenizer.current_token().token_type() == TokenType::Rparen {
-                    break;
+                match self.tokenizer.next_token()?.token_type() {
+                    TokenType::COMMA => {
+                        if self.tokenizer.next_token()?.token_type() == TokenType::Rparen {
+                            return Err(String::from("Syntax error, dangling `,` before `)` in the inserted fields."));
+                        }
+                    }
+                    TokenType::Rparen | TokenType::EOF => break,
+                    _ => {
+                        return Err(format!(
+                            "Syntax error, `,` or `)` expected in the inserted fields but `{}` was found.",
+                            self.tokenizer.current_token().value()
+                        ))
+                    }
                 }
             }
             if self.tokenizer.current_token().token_type() != TokenType::Rparen {
@@ -288,7 +450,10 @@ impl InsertStmtParser {
             return Err(String::from("Syntax error, `values` is missing."));
         }
         if self.tokenizer.next_token()?.token_type() == TokenType::Lparen {
-            self.tokenizer.next_token()?; // skip '('
+            // skip '('
+            if self.tokenizer.next_token()?.token_type() == TokenType::Rparen {
+                return Err(String::from("Syntax error, `values` is empty, at least one value is expected."));
+            }
             let mut values = Vec::<Value>::new();
             while self.tokenizer.has_more() {
                 let value = ValueParser {
@@ -296,11 +461,19 @@ impl InsertStmtParser {
                 }
                     .parse()?;
                 values.push(value);
-                self.tokenizer.next_token()?;
-                if self.tokenizer.current_token().token_type() == TokenType::COMMA {
-                    self.tokenizer.next_token()?;
-                } else if self.tokenizer.current_token().token_type() == TokenType::Rparen {
-                    break;
+                match self.tokenizer.next_token()?.token_type() {
+                    TokenType::COMMA => {
+                        if self.tokenizer.next_token()?.token_type() == TokenType::Rparen {
+                            return Err(String::from("Syntax error, dangling `,` before `)` in `values`."));
+                        }
+                    }
+                    TokenType::Rparen | TokenType::EOF => break,
+                    _ => {
+                        return Err(format!(
+                            "Syntax error, `,` or `)` expected in `values` but `{}` was found.",
+                            self.tokenizer.current_token().value()
+                        ))
+                    }
                 }
             }
             if self.tokenizer.current_token().token_type() != TokenType::Rparen {
@@ -325,18 +498,79 @@ impl CreateStmtParser {
         if self.tokenizer.next_token()?.value() != TABLE {
             return Err(String::from("Do you mean Create Table?"))
         };
-        let table_name = self.tokenizer.next_token()?.value().to_string();
-        let field_definitions = self.parse_field_definitions()?;
+        let mut if_not_exists = false;
+        if self.tokenizer.next_token()?.value() == IF {
+            if self.tokenizer.next_token()?.value() != NOT || self.tokenizer.next_token()?.value() != EXISTS {
+                return Err(String::from("Do you mean Create Table If Not Exists?"));
+            }
+            if_not_exists = true;
+            self.tokenizer.next_token()?;
+        }
+        let table_name = self.tokenizer.current_token().value().to_string();
 
-        Ok(CreateStmt::new(table_name, field_definitions))
+        let mut create_stmt = if self.tokenizer.next_token()?.value() == AS {
+            if self.tokenizer.next_token()?.value() != SELECT {
+                return Err(String::from("Syntax error, a SELECT is expected after AS."));
+            }
+            let tokenizer = std::mem::replace(&mut self.tokenizer, Tokenizer::new(String::new()));
+            let select_stmt = SelectStmtParser { tokenizer }.parse()?;
+            CreateStmt::from_select(table_name, select_stmt)
+        } else {
+            let field_definitions = self.parse_field_definitions()?;
+            let mut create_stmt = CreateStmt::new(table_name, field_definitions, None);
+            self.parse_table_option(&mut create_stmt)?;
+            create_stmt
+        };
+        create_stmt.set_if_not_exists(if_not_exists);
+        Ok(create_stmt)
+    }
+
+    /*
+        Optional `WITH (FILLFACTOR = n)` or `WITH (ROW_FORMAT = FIXED|VARIABLE)` after the field definitions
+     */
+    fn parse_table_option(&mut self, create_stmt: &mut CreateStmt) -> Result<(), String> {
+        if self.tokenizer.current_token().token_type() != TokenType::Rparen
+            || self.tokenizer.next_token()?.value() != WITH
+        {
+            return Ok(());
+        }
+
+        let error = || String::from("Syntax error, expected `WITH (FILLFACTOR = n)` or `WITH (ROW_FORMAT = FIXED|VARIABLE)`.");
+        if self.tokenizer.next_token()?.token_type() != TokenType::Lparen {
+            return Err(error());
+        }
+        let option = self.tokenizer.next_token()?.value().to_string();
+        if self.tokenizer.next_token()?.value() != "=" {
+            return Err(error());
+        }
+        let token = self.tokenizer.next_token()?;
+        match (option.as_str(), token.token_type(), token.value()) {
+            ("fillfactor", TokenType::Number, value) => {
+                create_stmt.set_fill_factor(value.parse::<usize>().map_err(|_| error())?);
+            }
+            ("row_format", TokenType::Ident, "fixed") => create_stmt.set_variable_rows(false),
+            ("row_format", TokenType::Ident, "variable") => create_stmt.set_variable_rows(true),
+            _ => return Err(error()),
+        }
+        if self.tokenizer.next_token()?.token_type() != TokenType::Rparen {
+            return Err(error());
+        }
+        Ok(())
     }
 
     fn parse_field_definitions(&mut self) -> Result<Vec<FieldDefinition>, String> {
-        if self.tokenizer.next_token()?.token_type() == TokenType::Lparen {
+        if self.tokenizer.current_token().token_type() == TokenType::Lparen {
             let mut field_definitions = Vec::<FieldDefinition>::new();
 
             while self.tokenizer.has_more() {
                 let field = self.tokenizer.next_token()?.value().to_string();
+                if self.tokenizer.current_token().token_type() == TokenType::Rparen {
+                    // `create table t ();`
+                    if field_definitions.is_empty() {
+                        break;
+                    }
+                    return Err(String::from("Syntax error, dangling `,` before `)` in the field definitions."));
+                }
 
                 if field.len() > FIELD_NAME_SIZE {
                     return Err(format!("Field name can not exceed {FIELD_NAME_SIZE}"));
@@ -346,16 +580,30 @@ impl CreateStmtParser {
                     tokenizer: &mut self.tokenizer,
                 }.parse()?;
 
-                let primary = self.tokenizer.current_token().value() == PRIMARY;
-                let key= self.tokenizer.next_token()?.value() == KEY;
+                let mut collation = Collation::BINARY;
+                if self.tokenizer.current_token().value() == COLLATE {
+                    collation = Collation::try_from(self.tokenizer.next_token()?.value())?;
+                    if !matches!(data_type, DataType::TEXT(_)) {
+                        return Err(format!("COLLATE only applies to TEXT fields, `{}` is {}.", field, data_type.to_sql()));
+                    }
+                    self.tokenizer.next_token()?;
+                }
 
-                if primary && key {
+                let primary = self.tokenizer.current_token().value() == PRIMARY;
+                if primary {
+                    if self.tokenizer.next_token()?.value() != KEY {
+                        return Err(String::from("Do you mean PRIMARY KEY?"))
+                    }
                     self.tokenizer.next_token()?;
-                } else if primary && !key {
-                    return Err(String::from("Do you mean PRIMARY KEY?"))
+                }
+                // the index orders its keys as BINARY
+                if primary && collation == Collation::NOCASE {
+                    return Err(format!("Primary key `{}` can not be COLLATE NOCASE.", field));
                 }
 
-                field_definitions.push(FieldDefinition::new(field, data_type, primary));
+                let mut definition = FieldDefinition::new(field, data_type, primary);
+                definition.set_collation(collation);
+                field_definitions.push(definition);
 
                 if self.tokenizer.current_token().token_type() == TokenType::COMMA {
                     continue
@@ -370,7 +618,7 @@ impl CreateStmtParser {
 
             if field_definitions.is_empty() {
                 return Err(String::from(
-                    "Syntax error, Create statement has no defined values",
+                    "Syntax error, Create statement has no defined values, a table needs at least one column.",
                 ));
             }
 
@@ -384,6 +632,145 @@ impl CreateStmtParser {
     }
 }
 
+struct AlterStmtParser {
+    tokenizer: Tokenizer,
+}
+
+impl AlterStmtParser {
+    fn parse(&mut self) -> Result<AlterStmt, String> {
+        if self.tokenizer.next_token()?.value() != TABLE {
+            return Err(String::from("Do you mean Alter Table?"))
+        };
+        let table_name = self.tokenizer.next_token()?.value().to_string();
+
+        if self.tokenizer.next_token()?.value() != RENAME {
+            return Err(String::from("Syntax error, Alter Table only supports RENAME."))
+        }
+
+        let action = if self.tokenizer.next_token()?.value() == COLUMN {
+            let old_name = self.parse_name()?;
+            if self.tokenizer.next_token()?.value() != TO {
+                return Err(String::from("Do you mean RENAME COLUMN ... TO ...?"))
+            }
+            AlterAction::RenameColumn(old_name, self.parse_name()?)
+        } else if self.tokenizer.current_token().value() == TO {
+            AlterAction::RenameTable(self.parse_name()?)
+        } else {
+            return Err(String::from("Do you mean RENAME TO or RENAME COLUMN?"))
+        };
+
+        if self.tokenizer.next_token()?.token_type() != TokenType::EOF {
+            return Err(format!(
+                "Syntax error, `;` expected but `{}` was found.",
+                self.tokenizer.current_token().value()
+            ));
+        }
+
+        Ok(AlterStmt::new(table_name, action))
+    }
+
+    fn parse_name(&mut self) -> Result<String, String> {
+        let name = self.tokenizer.next_token()?;
+        if name.token_type() != TokenType::Ident {
+            return Err(format!("Syntax error, `{}` is not a valid name.", name.value()));
+        }
+
+        if name.value().len() > FIELD_NAME_SIZE {
+            return Err(format!("Name can not exceed {FIELD_NAME_SIZE}"));
+        }
+        Ok(name.value().to_string())
+    }
+}
+
+struct DropStmtParser {
+    tokenizer: Tokenizer,
+}
+
+impl DropStmtParser {
+    fn parse(&mut self) -> Result<DropStmt, String> {
+        if self.tokenizer.next_token()?.value() != TABLE {
+            return Err(String::from("Do you mean Drop Table?"));
+        }
+        let mut if_exists = false;
+        if self.tokenizer.next_token()?.value() == IF {
+            if self.tokenizer.next_token()?.value() != EXISTS {
+                return Err(String::from("Do you mean Drop Table If Exists?"));
+            }
+            if_exists = true;
+            self.tokenizer.next_token()?;
+        }
+
+        let name = self.tokenizer.current_token();
+        if name.token_type() != TokenType::Ident {
+            return Err(format!("Syntax error, `{}` is not a valid name.", name.value()));
+        }
+        let table_name = name.value().to_string();
+
+        if self.tokenizer.next_token()?.token_type() != TokenType::EOF {
+            return Err(format!(
+                "Syntax error, `;` expected but `{}` was found.",
+                self.tokenizer.current_token().value()
+            ));
+        }
+        Ok(DropStmt::new(table_name, if_exists))
+    }
+}
+
+struct PragmaStmtParser {
+    tokenizer: Tokenizer,
+}
+
+impl PragmaStmtParser {
+    fn parse(&mut self) -> Result<PragmaStmt, String> {
+        let name = self.tokenizer.next_token()?;
+        if name.token_type() != TokenType::Ident {
+            return Err(format!("Syntax error, `{}` is not a pragma name.", name.value()));
+        }
+        let name = name.value().to_string();
+
+        let value = match self.tokenizer.next_token()? {
+            t if t.token_type() == TokenType::EOF => return Ok(PragmaStmt::new(name, None)),
+            t if t.value() == "=" => match self.tokenizer.next_token()?.value() {
+                // sqlite style switches, `pragma timer = on;`, ON is read as a keyword
+                ON => Value::BOOL(true),
+                "off" => Value::BOOL(false),
+                _ => ValueParser { tokenizer: &mut self.tokenizer }.parse()?,
+            },
+            t => return Err(format!("Syntax error, `=` expected but `{}` was found.", t.value())),
+        };
+
+        if self.tokenizer.next_token()?.token_type() != TokenType::EOF {
+            return Err(format!(
+                "Syntax error, `;` expected but `{}` was found.",
+                self.tokenizer.current_token().value()
+            ));
+        }
+        Ok(PragmaStmt::new(name, Some(value)))
+    }
+}
+
+struct ReindexStmtParser {
+    tokenizer: Tokenizer,
+}
+
+impl ReindexStmtParser {
+    fn parse(&mut self) -> Result<ReindexStmt, String> {
+        let name = self.tokenizer.next_token()?;
+        if name.token_type() != TokenType::Ident {
+            return Err(format!("Syntax error, `{}` is not a valid name.", name.value()));
+        }
+        let table_name = name.value().to_string();
+
+        if self.tokenizer.next_token()?.token_type() != TokenType::EOF {
+            return Err(format!(
+                "Syntax error, `;` expected but `{}` was found.",
+                self.tokenizer.current_token().value()
+            ));
+        }
+        Ok(ReindexStmt::new(table_name))
+    }
+}
+
 struct OrderByExprParser<'a> {
     tokenizer: &'a mut Tokenizer,
 }
@@ -399,17 +786,23 @@ impl<'a> OrderByExprParser<'a> {
 
         let mut order_bys = Vec::<OrderByExpr>::new();
 
-        while self.tokenizer.has_more() {
-            let field = self.tokenizer.next_token()?.value().to_string();
-            self.tokenizer.next_token()?;
+        while self.tokenizer.has_more() && self.tokenizer.current_token().value() != LIMIT {
+            self.tokenizer.next_token()?; // skip `by` or ','
+            let expr = ExprParser {
+                tokenizer: self.tokenizer,
+                depth: 0,
+            }.parse()?;
             let order: Order;
-            if !self.tokenizer.has_more() || self.tokenizer.current_token().token_type() == TokenType::COMMA {
+            if !self.tokenizer.has_more()
+                || self.tokenizer.current_token().token_type() == TokenType::COMMA
+                || self.tokenizer.current_token().value() == LIMIT
+            {
                 order = Order::ASC;
             } else {
                 order = Order::try_from(self.tokenizer.current_token().value())?;
                 self.tokenizer.next_token()?;
             }
-            order_bys.push(OrderByExpr::new(field, order));
+            order_bys.push(OrderByExpr::new(expr, order));
         }
 
         Ok(OrderByCluster {
@@ -427,14 +820,28 @@ impl<'a> ValueParser<'a> {
         let v = self.tokenizer.current_token().value();
         match self.tokenizer.current_token().token_type() {
             TokenType::LeftBracket => self.parse_array(),
-            TokenType::StringLiteral => Ok(Value::TEXT(v[1..v.len() - 1].to_string())),
-            TokenType::Number => {
-                let number_str = v;
-                if number_str.contains('.') {
-                    return Ok(Value::FLOAT(number_str.parse().unwrap()))
+            TokenType::StringLiteral => Ok(Value::TEXT(v[1..v.len() - 1].replace("''", "'"))),
+            TokenType::BlobLiteral => {
+                let hex = &v[2..v.len() - 1];
+                if !hex.len().is_multiple_of(2) {
+                    return Err(format!("Blob literal `{}` has an odd number of hex digits.", v));
                 }
-                Ok(Value::INT(number_str.parse().unwrap()))
-            },
+                Ok(Value::BLOB(
+                    (0..hex.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                        .collect(),
+                ))
+            }
+            TokenType::Number => Self::parse_number(v),
+            // a negative number, the sign is tokenized apart from the digits
+            TokenType::ArithmeticOperator if v == "-" => {
+                let token = self.tokenizer.next_token()?;
+                if token.token_type() != TokenType::Number {
+                    return Err(format!("Syntax error, a number is expected after `-` but found `{}`.", token.value()));
+                }
+                Self::parse_number(&format!("-{}", token.value()))
+            }
             TokenType::Boolean => Ok(Value::BOOL(v.to_lowercase() == "true")),
             _ => {
                 return Err(format!(
@@ -445,6 +852,19 @@ impl<'a> ValueParser<'a> {
         }
     }
 
+    fn parse_number(number_str: &str) -> Result<Value, String> {
+        if number_str.contains('.') {
+            return match number_str.parse::<f32>() {
+                Ok(f) if f.is_finite() => Ok(Value::FLOAT(f)),
+                _ => Err(format!("Float literal `{}` is out of range for FLOAT.", number_str)),
+            };
+        }
+        match number_str.parse::<i32>() {
+            Ok(i) => Ok(Value::INT(i)),
+            Err(_) => Err(format!("Integer literal `{}` is out of range for INTEGER.", number_str)),
+        }
+    }
+
     fn parse_array(&mut self) -> Result<Value, String> {
         let mut array = Vec::<Value>::new();
         self.tokenizer.next_token()?; // skip '['
@@ -468,8 +888,20 @@ impl<'a> ValueParser<'a> {
 
             array.push(value);
 
-            if self.tokenizer.current_token().token_type() != TokenType::COMMA {
-                self.tokenizer.next_token()?;
+            self.tokenizer.next_token()?;
+            match self.tokenizer.current_token().token_type() {
+                TokenType::COMMA => {
+                    if self.tokenizer.next_token()?.token_type() == TokenType::RightBracket {
+                        return Err(String::from("Syntax error, dangling `,` before `]` in array."));
+                    }
+                }
+                TokenType::RightBracket | TokenType::EOF => {}
+                _ => {
+                    return Err(format!(
+                        "Syntax error in array, ',' or ']' is expected but found `{}`.",
+                        self.tokenizer.current_token().value()
+                    ));
+                }
             }
         }
 
@@ -479,11 +911,150 @@ impl<'a> ValueParser<'a> {
             ));
         }
 
-        self.tokenizer.next_token()?;
+        // like the other values, leave the tokenizer on the last token of the array
         Ok(Value::ARRAY(array))
     }
 }
 
+/*
+    Parses an arithmetic expression over fields and literals.
+    `*` and `/` bind tighter than `+` and `-`, parentheses group.
+    Leaves the tokenizer on the first token after the expression.
+ */
+struct ExprParser<'a> {
+    tokenizer: &'a mut Tokenizer,
+    // parentheses, function calls and unary minuses around the current operand
+    depth: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn parse(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_term()?;
+        while let Some(operator) = self.current_operator(&[ArithmeticOperator::ADD, ArithmeticOperator::SUBTRACT]) {
+            self.tokenizer.next_token()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.parse_term()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_primary()?;
+        while let Some(operator) = self.current_operator(&[ArithmeticOperator::MULTIPLY, ArithmeticOperator::DIVIDE]) {
+            self.tokenizer.next_token()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.parse_primary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(format!("Expression is nested deeper than {} levels.", MAX_NESTING_DEPTH));
+        }
+        let expr = self.parse_operand()?;
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn parse_operand(&mut self) -> Result<Expr, String> {
+        let token = self.tokenizer.current_token();
+        let expr = match token.token_type() {
+            TokenType::Ident => {
+                let name = token.value().to_string();
+                if self.tokenizer.next_token()?.token_type() != TokenType::Lparen {
+                    return Ok(Expr::Field(name));
+                }
+                self.parse_function(&name)?
+            }
+            TokenType::Lparen => {
+                self.tokenizer.next_token()?; // skip '('
+                let expr = self.parse()?;
+                if self.tokenizer.current_token().token_type() != TokenType::Rparen {
+                    return Err(format!(
+                        "Syntax error, expected a ')' but found {}",
+                        self.tokenizer.current_token().value()
+                    ));
+                }
+                expr
+            }
+            TokenType::ArithmeticOperator if token.value() == "-" => {
+                // a negated number is one literal, so that i32::MIN is in range
+                if self.tokenizer.next_token()?.token_type() == TokenType::Number {
+                    let value = ValueParser::parse_number(&format!("-{}", self.tokenizer.current_token().value()))?;
+                    self.tokenizer.next_token()?;
+                    return Ok(Expr::Literal(value));
+                }
+                return match self.parse_primary()? {
+                    Expr::Literal(Value::INT(i)) => Ok(Expr::Literal(Value::INT(-i))),
+                    Expr::Literal(Value::FLOAT(f)) => Ok(Expr::Literal(Value::FLOAT(-f))),
+                    expr => Ok(Expr::Binary(
+                        Box::new(Expr::Literal(Value::INT(0))),
+                        ArithmeticOperator::SUBTRACT,
+                        Box::new(expr),
+                    )),
+                };
+            }
+            TokenType::Number | TokenType::StringLiteral | TokenType::BlobLiteral | TokenType::Boolean => Expr::Literal(
+                ValueParser {
+                    tokenizer: self.tokenizer,
+                }.parse()?,
+            ),
+            _ => {
+                return Err(format!(
+                    "Syntax error, expected a field or a value but found `{}`.",
+                    token.value()
+                ));
+            }
+        };
+        self.tokenizer.next_token()?;
+        Ok(expr)
+    }
+
+    /*
+        Parses the arguments of `name(arg, ...)`, the tokenizer is on the '('
+        and is left on the closing ')'.
+     */
+    fn parse_function(&mut self, name: &str) -> Result<Expr, String> {
+        if name == "count" {
+            if self.tokenizer.next_token()?.token_type() != TokenType::AllColumn
+                || self.tokenizer.next_token()?.token_type() != TokenType::Rparen
+            {
+                return Err(String::from("Syntax error, only COUNT(*) is supported."));
+            }
+            return Ok(Expr::CountAll);
+        }
+        let function = ScalarFunction::try_from(name)?;
+        let mut args = vec![];
+        self.tokenizer.next_token()?; // skip '('
+        while self.tokenizer.has_more() && self.tokenizer.current_token().token_type() != TokenType::Rparen {
+            args.push(self.parse()?);
+            if self.tokenizer.current_token().token_type() == TokenType::COMMA {
+                self.tokenizer.next_token()?; // skip ','
+            } else if self.tokenizer.current_token().token_type() != TokenType::Rparen {
+                return Err(format!(
+                    "Syntax error, expected a ',' or a ')' in `{}` but found `{}`.",
+                    name,
+                    self.tokenizer.current_token().value()
+                ));
+            }
+        }
+        if self.tokenizer.current_token().token_type() != TokenType::Rparen {
+            return Err(format!("Syntax error, function `{}` is not closed.", name));
+        }
+        Ok(Expr::Function(function, args))
+    }
+
+    fn current_operator(&self, accepted: &[ArithmeticOperator]) -> Option<ArithmeticOperator> {
+        let token = self.tokenizer.current_token();
+        if ![TokenType::ArithmeticOperator, TokenType::AllColumn].contains(&token.token_type()) {
+            return None;
+        }
+        ArithmeticOperator::try_from(token.value())
+            .ok()
+            .filter(|operator| accepted.contains(operator))
+    }
+}
+
 struct OperatorParser<'a> {
     tokenizer: &'a mut Tokenizer,
 }
@@ -495,11 +1066,18 @@ impl<'a> OperatorParser<'a> {
         if self.tokenizer.has_more() && self.tokenizer.current_token().value() == NOT {
             operator.push_str("not ");
             self.tokenizer.next_token()?;
+            // only IN can be negated, `not =` would otherwise reach Operator::try_from as "not ="
+            if !self.tokenizer.has_more() || self.tokenizer.current_token().value() != IN {
+                return Err(format!(
+                    "Syntax error, NOT must be followed by IN but found `{}`.",
+                    self.tokenizer.current_token().value()
+                ));
+            }
         }
 
+        // the tokenizer is left on the operator's last token, the caller skips it
         if self.tokenizer.has_more() && self.tokenizer.current_token().value() == IN {
             operator.push_str("in");
-            self.tokenizer.next_token()?;
         } else {
             operator.push_str(self.tokenizer.current_token().value());
         }
@@ -515,17 +1093,27 @@ impl<'a> DataTypeParser<'a> {
     fn parse(&mut self) -> Result<DataType, String> {
         let data_type = self.tokenizer.next_token()?.value().to_lowercase();
 
-        if data_type == "text" {
-            let mut size: usize = 255;
+        if data_type == "text" || data_type == "blob" {
+            let mut size = if data_type == "text" { TEXT_DEFAULT_SIZE } else { BLOB_DEFAULT_SIZE };
             if self.tokenizer.next_token()?.token_type() == TokenType::Lparen {
-                size = self.tokenizer.next_token()?.value().parse().unwrap_or(255);
+                let value = self.tokenizer.next_token()?.value();
+                // the declared size is persisted in TEXT_CHARS_NUM_SIZE bytes
+                size = match value.parse::<u32>() {
+                    Ok(0) => return Err(format!("The size of {} must be at least 1.", data_type.to_uppercase())),
+                    Ok(size) => size as usize,
+                    Err(_) => return Err(format!("`{}` is not a valid size for {}.", value, data_type)),
+                };
                 if self.tokenizer.next_token()?.token_type() != TokenType::Rparen {
                     return Err(String::from("Syntax error, expected a ')'."));
                 }
+                self.tokenizer.next_token()?; // skip ")"
             }
 
-            self.tokenizer.next_token()?; // skip ")"
-            Ok(DataType::TEXT(size))
+            if data_type == "text" {
+                Ok(DataType::TEXT(size))
+            } else {
+                Ok(DataType::BLOB(size))
+            }
         } else {
             self.tokenizer.next_token()?;
             match data_type.as_str() {
@@ -536,4 +1124,143 @@ impl<'a> DataTypeParser<'a> {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql_engine::sql_parser::SqlParser;
+    use crate::sql_engine::sql_structs::Value;
+    use crate::utils::test_utils::TestDb;
+
+    #[test]
+    fn blob_literal_operand() {
+        let mut db = TestDb::open(&["parser_blobs"]);
+        assert_eq!(db.query("select x'0a';"), vec![vec![Value::BLOB(vec![0x0a])]]);
+
+        db.run("create table parser_blobs (id int primary key, b blob(4));").unwrap();
+        db.run("insert into parser_blobs (id, b) values (1, x'0a');").unwrap();
+        db.run("insert into parser_blobs (id, b) values (2, x'0bff');").unwrap();
+        assert_eq!(db.query("select id from parser_blobs where b = x'0a';"), vec![vec![Value::INT(1)]]);
+    }
+
+    #[test]
+    fn zero_size_is_rejected() {
+        for sql in ["create table t (a text(0));", "create table t (a blob(0));"] {
+            assert!(SqlParser::parse_sql(sql.to_string()).err().unwrap().contains("must be at least 1"));
+        }
+        assert!(SqlParser::parse_sql(String::from("create table t (a text(1), b blob(1));")).is_ok());
+    }
+
+    #[test]
+    fn collate_applies_to_text_fields_only() {
+        assert!(SqlParser::parse_sql(String::from("create table t (a text(4) collate nocase, b text collate binary);")).is_ok());
+        let errors = [
+            ("create table t (a int collate nocase);", "only applies to TEXT"),
+            ("create table t (a text(4) collate upper);", "Unknown collation"),
+            ("create table t (a text(4) collate nocase primary key);", "can not be COLLATE NOCASE"),
+        ];
+        for (sql, error) in errors {
+            assert!(SqlParser::parse_sql(sql.to_string()).err().unwrap().contains(error), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn parenthesis_opens_a_group_or_an_expression() {
+        let mut db = TestDb::open(&["parser_groups"]);
+        db.run("create table parser_groups (id int primary key, price int, qty int);").unwrap();
+        for (id, price, qty) in [(1, 1, 5), (2, 2, 0), (3, 3, 1), (4, 4, 2)] {
+            db.run(&format!("insert into parser_groups (id, price, qty) values ({}, {}, {});", id, price, qty)).unwrap();
+        }
+        let ids = |db: &mut TestDb, condition: &str| -> Vec<Value> {
+            db.query(&format!("select id from parser_groups where {};", condition)).into_iter().map(|row| row[0].clone()).collect()
+        };
+
+        assert_eq!(ids(&mut db, "(price + 1) * 2 > 7"), vec![Value::INT(3), Value::INT(4)]);
+        assert_eq!(ids(&mut db, "((price)) = 2"), vec![Value::INT(2)]);
+        assert_eq!(ids(&mut db, "not (price - qty) < 1"), vec![Value::INT(2), Value::INT(3), Value::INT(4)]);
+        assert_eq!(ids(&mut db, "(price + qty) = 6 and (qty = 5 or (qty = 2 or id = 9))"), vec![Value::INT(1), Value::INT(4)]);
+        assert_eq!(ids(&mut db, "((qty = 0 or (qty = 1 or (qty = 2))) and price > 2) or id = 1"), vec![Value::INT(1), Value::INT(3), Value::INT(4)]);
+
+        assert!(SqlParser::parse_sql(String::from("select * from t where (a = 1 or b = 2;")).err().unwrap().contains("expected a ')'"));
+    }
+
+    #[test]
+    fn negated_number_is_one_literal() {
+        let mut db = TestDb::open(&["parser_negatives"]);
+        assert_eq!(db.query("select -2147483648;"), vec![vec![Value::INT(i32::MIN)]]);
+        assert_eq!(db.query("select -2147483648 + 1, - 2.5;"), vec![vec![Value::INT(i32::MIN + 1), Value::FLOAT(-2.5)]]);
+        assert!(db.run("select 2147483648;").err().unwrap().contains("out of range"));
+        assert!(db.run("select -2147483649;").err().unwrap().contains("out of range"));
+        let huge_float = format!("{}.0", "9".repeat(40));
+        assert_eq!(
+            db.run(&format!("select {};", huge_float)).err().unwrap(),
+            format!("Float literal `{}` is out of range for FLOAT.", huge_float)
+        );
+
+        db.run("create table parser_negatives (id int primary key, qty int);").unwrap();
+        db.run("insert into parser_negatives (id, qty) values (1, -2147483648);").unwrap();
+        db.run("insert into parser_negatives (id, qty) values (2, 0);").unwrap();
+        assert_eq!(
+            db.run("insert into parser_negatives (id, qty) values (3, 99999999999);").err().unwrap(),
+            "Integer literal `99999999999` is out of range for INTEGER."
+        );
+        assert_eq!(db.query("select id from parser_negatives where qty = -2147483648;"), vec![vec![Value::INT(1)]]);
+        assert_eq!(db.query("select id from parser_negatives where qty > -2147483648;"), vec![vec![Value::INT(2)]]);
+    }
+
+    #[test]
+    fn not_only_negates_in() {
+        let mut db = TestDb::open(&["parser_not_in"]);
+        db.run("create table parser_not_in (id int primary key, qty int);").unwrap();
+        for (id, qty) in [(1, 10), (2, 20), (3, 30), (4, 40)] {
+            db.run(&format!("insert into parser_not_in (id, qty) values ({}, {});", id, qty)).unwrap();
+        }
+        let ids = |db: &mut TestDb, condition: &str| -> Vec<Value> {
+            db.query(&format!("select id from parser_not_in where {};", condition)).into_iter().map(|row| row[0].clone()).collect()
+        };
+
+        assert_eq!(ids(&mut db, "id not in [1, 3]"), vec![Value::INT(2), Value::INT(4)]);
+        assert_eq!(ids(&mut db, "qty not in [20, 30, 50]"), vec![Value::INT(1), Value::INT(4)]);
+        assert_eq!(ids(&mut db, "qty in [20, 30, 50]"), vec![Value::INT(2), Value::INT(3)]);
+        assert_eq!(ids(&mut db, "id <> 2"), vec![Value::INT(1), Value::INT(3), Value::INT(4)]);
+
+        assert_eq!(
+            SqlParser::parse_sql(String::from("select id from parser_not_in where qty not = 10;")).err().unwrap(),
+            "Syntax error, NOT must be followed by IN but found `=`."
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_a_parse_error() {
+        let nested = |depth: usize, inner: &str| format!("{}{}{}", "(".repeat(depth), inner, ")".repeat(depth));
+
+        let condition = format!("select * from t where {};", nested(10_000, "a = 1"));
+        assert!(SqlParser::parse_sql(condition).err().unwrap().contains("nested deeper than"));
+        let expression = format!("select {};", nested(10_000, "1"));
+        assert!(SqlParser::parse_sql(expression).err().unwrap().contains("nested deeper than"));
+        let operand = format!("select * from t where a + {} = 3;", nested(10_000, "1 + 2"));
+        assert!(SqlParser::parse_sql(operand).err().unwrap().contains("nested deeper than"));
+
+        assert!(SqlParser::parse_sql(format!("select * from t where {};", nested(32, "a = 1"))).is_ok());
+        assert!(SqlParser::parse_sql(format!("select {};", nested(32, "1"))).is_ok());
+    }
+
+    #[test]
+    fn empty_values_tuple_is_rejected() {
+        let error = |sql: &str| SqlParser::parse_sql(sql.to_string()).err().unwrap();
+        assert_eq!(error("insert into t values ();"), "Syntax error, `values` is empty, at least one value is expected.");
+        assert_eq!(error("insert into t (a) values ();"), "Syntax error, `values` is empty, at least one value is expected.");
+        assert_eq!(error("insert into t () values (1);"), "Syntax error, the list of inserted fields is empty.");
+        assert!(SqlParser::parse_sql(String::from("insert into t values (1);")).is_ok());
+    }
+
+    #[test]
+    fn dangling_comma_is_reported_in_every_list() {
+        let error = |sql: &str| SqlParser::parse_sql(sql.to_string()).err().unwrap();
+        assert_eq!(error("select a, b, from t;"), "Syntax error, dangling `,` before `FROM` in the selected fields.");
+        assert_eq!(error("insert into t (a, b,) values (1, 2);"), "Syntax error, dangling `,` before `)` in the inserted fields.");
+        assert_eq!(error("insert into t (a, b) values (1, 2,);"), "Syntax error, dangling `,` before `)` in `values`.");
+        assert_eq!(error("create table t (a int, b int,);"), "Syntax error, dangling `,` before `)` in the field definitions.");
+        assert_eq!(error("select * from t where a in [1, 2,];"), "Syntax error, dangling `,` before `]` in array.");
+    }
+}
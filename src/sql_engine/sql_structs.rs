@@ -1,39 +1,53 @@
 use std::{fs, ptr};
-use std::cmp::{max, Ordering, PartialEq, PartialOrd};
+use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::Write;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::slice::Iter;
 
-use crate::build_path;
+use crate::{build_path, to_u8_array};
 use crate::sql_engine::sql_structs::Operator::{EQUALS, GT, GTE, IN, LT, LTE};
 use crate::storage_engine::common::{
     RowBytes, RowToInsert, RowValues, SelectResult, TableManager, TableStructureMetadata,
 };
 use crate::storage_engine::config::*;
-use crate::storage_engine::tables::Table;
-use crate::utils::utils::{copy_nonoverlapping, ToU8, u8_array_to_string};
+use crate::storage_engine::tables::BtreeTable;
+use crate::utils::utils::{json_string, list_files_of_folder, read_uint, ToU8, u8_array_to_string, write_u32, write_uint};
 
+#[allow(clippy::upper_case_acronyms)]
 pub(crate) enum SqlStmt {
     SELECT(SelectStmt),
     INSERT(InsertStmt),
     CREATE(CreateStmt),
+    ALTER(AlterStmt),
+    PRAGMA(PragmaStmt),
+    DROP(DropStmt),
+    REINDEX(ReindexStmt),
 }
 
 #[derive(PartialEq, Debug, PartialOrd)]
 pub(crate) struct SelectStmt {
-    pub(crate) selected_fields: Vec<String>,
-    pub(crate) table: String,
+    pub(crate) selected_fields: Vec<Expr>,
+    // None for a select without FROM, which evaluates its expressions once
+    pub(crate) table: Option<String>,
     pub(crate) where_expr: Option<WhereExpr>,
     pub(crate) order_by_expr: Option<OrderByCluster>,
+    // `LIMIT n`, applied after the sort
+    pub(crate) limit: Option<usize>,
+    // `DISTINCT ON (a, b)` keeps the first sorted row of each distinct (a, b)
+    pub(crate) distinct_on: Vec<Expr>,
 }
 
+// values of a row computed for a select, in the order of their expressions
+type ProjectedValues = Vec<Rc<Value>>;
+
 impl SelectStmt {
     pub(crate) fn new(
-        selected_fields: Vec<String>,
-        table: String,
+        selected_fields: Vec<Expr>,
+        table: Option<String>,
         where_stmt: Option<WhereExpr>,
         order_by_stmt: Option<OrderByCluster>,
     ) -> SelectStmt {
@@ -42,113 +56,200 @@ impl SelectStmt {
             table,
             where_expr: where_stmt,
             order_by_expr: order_by_stmt,
+            limit: None,
+            distinct_on: vec![],
         }
     }
 
+    pub(crate) fn set_distinct_on(&mut self, distinct_on: Vec<Expr>) {
+        self.distinct_on = distinct_on;
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = Some(limit);
+    }
+
     pub(crate) fn execute<'a>(
         &'a mut self,
         table_manager: &'a mut TableManager,
     ) -> Result<SelectResult, String> {
-        let table = table_manager.get_tables(&self.table);
-        if table.is_err() {
-            return Err(format!("Table `{}` does not exist.", self.table));
+        let table = match &self.table {
+            Some(table) => table.clone(),
+            None => return self.execute_without_table(),
+        };
+        table_manager.get_tables(&table)?;
+        self.check_fields(table_manager.get_table_metadata(&table)?)?;
+
+        if let Some(result) = self.execute_count(&table, table_manager)? {
+            return Ok(result);
+        }
+        if let Some(result) = self.execute_covering(&table, table_manager)? {
+            return Ok(result);
         }
 
-        let result = self.execute_where(table_manager);
+        let mut order_by_exprs = self.order_by_expr.take()
+                                     .unwrap_or_else(|| OrderByCluster::new(vec![]))
+                                     .order_by_exprs;
+
+        // rows of a table with a primary key are read in key order, they are not sorted again by the key
+        if let [order_by_expr] = order_by_exprs.as_slice() {
+            let table_meta = table_manager.get_table_metadata(&table)?;
+            if order_by_expr.order.is_asc()
+                && table_meta.fields
+                             .iter()
+                             .any(|f| f.data_def.is_primary_key && order_by_expr.expr.is_field(&f.data_def.field_name))
+            {
+                order_by_exprs.clear();
+            }
+        }
+
+        // rows come in their final order when nothing is sorted or dropped, the scan can stop at the limit
+        let scan_limit = if order_by_exprs.is_empty() && self.distinct_on.is_empty() { self.limit } else { None };
+        let mut result = self.execute_where(&table, table_manager, scan_limit)?;
+        if let Some(limit) = scan_limit {
+            result.truncate(limit);
+        }
 
-        let table_meta = table_manager.get_table_metadata(&self.table)?;
+        let table_meta = table_manager.get_table_metadata(&table)?;
 
-        let selected_fields: Vec<&str> =
-            if self.selected_fields.len() == 1 && self.selected_fields.first().unwrap() == "*" {
+        let selected_fields: Vec<Expr> =
+            if self.selected_fields.len() == 1 && self.selected_fields.first().unwrap().is_field("*") {
                 table_meta.fields
                           .iter()
-                          .map(|v| v.data_def.field_name.as_str())
+                          .map(|v| Expr::Field(v.data_def.field_name.to_string()))
                           .collect()
             } else {
-                self.selected_fields.iter().map(|x| x.as_str()).collect()
+                self.selected_fields.clone()
             };
 
-        let order_by_exprs = self.order_by_expr.take()
-                                 .unwrap_or_else(|| OrderByCluster::new(vec![]))
-                                 .order_by_exprs;
+        /*
+            Without ORDER BY and computed columns nothing has to be decoded now,
+            the values are read from the rows when the result is printed.
+         */
+        if order_by_exprs.is_empty()
+            && self.distinct_on.is_empty()
+            && selected_fields.iter().all(|f| matches!(f, Expr::Field(_)))
+        {
+            let mut columns = Vec::with_capacity(selected_fields.len());
+            for field in selected_fields.iter() {
+                columns.push(table_meta.get_shared_field_metadata(&field.to_string())?);
+            }
+            return Ok(SelectResult::lazy(
+                Self::headers(&selected_fields),
+                result,
+                columns,
+            ));
+        }
+
+        let mut human_readable_results = self.order_by(order_by_exprs, &result, table_meta, &selected_fields)?;
+        if let Some(limit) = self.limit {
+            human_readable_results.truncate(limit);
+        }
 
-        let projected_results = self.order_by(order_by_exprs, &result, table_meta, &selected_fields)?;
+        Ok(SelectResult::new(
+            Self::headers(&selected_fields),
+            human_readable_results,
+        ))
+    }
 
-        let human_readable_results = projected_results.into_iter().map(|(v, _)| v).collect();
+    /*
+        Column names of the result, a column selected again is numbered like in SQLite: `id`, `id:1`, `id:2`
+     */
+    fn headers(selected_fields: &[Expr]) -> Vec<String> {
+        selected_fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let name = field.to_string();
+                match selected_fields[..index].iter().filter(|f| f.to_string() == name).count() {
+                    0 => name,
+                    repeated => format!("{}:{}", name, repeated),
+                }
+            })
+            .collect()
+    }
 
-        Ok(SelectResult::new(selected_fields, human_readable_results))
+    /*
+        Fails on the first selected, ORDER BY or DISTINCT ON field that is not a field of the table,
+        before any row is read
+     */
+    fn check_fields(&self, table_meta: &TableStructureMetadata) -> Result<(), String> {
+        let order_by_exprs = self.order_by_expr.iter().flat_map(|o| o.order_by_exprs.iter().map(|e| &e.expr));
+        self.selected_fields
+            .iter()
+            .filter(|expr| !expr.is_field("*"))
+            .chain(order_by_exprs)
+            .chain(self.distinct_on.iter())
+            .try_for_each(|expr| expr.check_fields(table_meta))
     }
 
+    /*
+        Evaluates the selected fields of every row and sorts the rows,
+        then keeps the first row of each DISTINCT ON group.
+     */
     fn order_by(
         &self,
         order_by_exprs: Vec<OrderByExpr>,
         result: &Vec<RowBytes>,
         table_meta: &TableStructureMetadata,
-        selected_fields: &Vec<&str>,
-    ) -> Result<Vec<(RowValues, Vec<Rc<Value>>)>, String> {
-        let order_by_fields: Vec<&str> = order_by_exprs.iter().map(|o| o.field.as_str()).collect();
-
-        let max_row_size = table_meta.fields.iter().map(|x| x.size).max().unwrap();
-
-        let mut row_buf = vec![0; max_row_size];
-        let row_buf_ptr = row_buf.as_mut_ptr();
-
-        let mut projected_results: Vec<(RowValues, Vec<Rc<Value>>)> =
+        selected_fields: &Vec<Expr>,
+    ) -> Result<Vec<RowValues>, String> {
+        // (selected values, order by values, values compared by DISTINCT) of every row
+        let mut projected_results: Vec<(RowValues, ProjectedValues, ProjectedValues)> =
             Vec::with_capacity(result.len());
 
+        /*
+            Rows with equal sort keys are ordered by the primary key, so the output does not depend
+            on how the WHERE clause combined the scanned rows. Tables without a primary key keep
+            their insertion order, because the sort is stable.
+         */
+        let tie_breaker = if order_by_exprs.is_empty() {
+            None
+        } else {
+            table_meta.fields.iter().find(|f| f.data_def.is_primary_key)
+        };
+
         for row in result {
             let mut selected_values: Vec<Rc<Value>> = Vec::with_capacity(selected_fields.len());
-            let mut order_values: Vec<Rc<Value>> = Vec::with_capacity(order_by_fields.len());
-            let mut value_index: Vec<(usize, usize)> = Vec::new();
+            let mut order_values: Vec<Rc<Value>> = Vec::with_capacity(order_by_exprs.len());
 
-            for (index, field_name) in selected_fields.iter().enumerate() {
-                let field_meta = table_meta.get_field_metadata(field_name)?;
-                copy_nonoverlapping(
-                    row[field_meta.offset..field_meta.offset + field_meta.size].as_ptr(),
-                    row_buf_ptr,
-                    field_meta.size,
-                );
-                let value = Rc::new(Value::from_ptr(
-                    &field_meta.data_def.data_type,
-                    row_buf[..field_meta.size].as_ptr(),
-                ));
+            for expr in selected_fields {
+                selected_values.push(Rc::new(expr.evaluate(row, table_meta)?));
+            }
 
-                let mut i = 0;
-                if order_by_fields.iter().any(|v| {
-                    i += 1;
-                    v == field_name
-                }) {
-                    value_index.push((index, i - 1));
+            for order_by_expr in order_by_exprs.iter() {
+                // an expression that is also selected is evaluated only once
+                let value = match selected_fields.iter().position(|e| *e == order_by_expr.expr) {
+                    Some(index) => Rc::clone(&selected_values[index]),
+                    None => Rc::new(order_by_expr.expr.evaluate(row, table_meta)?),
+                };
+                // a NOCASE field is sorted by its folded value, the selected value keeps its case
+                match order_by_expr.expr.collation(table_meta) {
+                    Collation::BINARY => order_values.push(value),
+                    Collation::NOCASE => order_values.push(Rc::new(value.fold_case())),
                 }
-                selected_values.push(Rc::clone(&value));
             }
 
-            value_index.sort_by(|a, b| a.1.cmp(&b.1));
-
-            for (index, field_name) in order_by_fields.iter().enumerate() {
-                let (value_index_in_selected, index_in_order_fields) = value_index.first().unwrap();
-                if *index_in_order_fields == index {
-                    order_values.push(Rc::clone(&selected_values[*value_index_in_selected]));
-                    value_index.remove(0);
-                } else {
-                    let field_meta = table_meta.get_field_metadata(field_name)?;
-                    copy_nonoverlapping(
-                        row[field_meta.offset..field_meta.offset + field_meta.size].as_ptr(),
-                        row_buf_ptr,
-                        field_meta.size,
-                    );
-                    let value = Rc::new(Value::from_ptr(
-                        &field_meta.data_def.data_type,
-                        row_buf[..field_meta.size].as_ptr(),
-                    ));
-                    order_values.push(Rc::clone(&value));
+            let mut distinct_values: Vec<Rc<Value>> = Vec::with_capacity(self.distinct_on.len());
+            for expr in self.distinct_on.iter() {
+                match selected_fields.iter().position(|e| e == expr) {
+                    Some(index) => distinct_values.push(Rc::clone(&selected_values[index])),
+                    None => distinct_values.push(Rc::new(expr.evaluate(row, table_meta)?)),
                 }
             }
 
-            projected_results.push((RowValues::new(selected_values), order_values));
+            if let Some(primary_meta) = tie_breaker {
+                order_values.push(Rc::new(row.read_key(
+                    &primary_meta.data_def.data_type,
+                    primary_meta.offset,
+                    primary_meta.size,
+                )));
+            }
+
+            projected_results.push((RowValues::new(selected_values), order_values, distinct_values));
         }
 
-        projected_results.sort_by(|(_, order_values1), (_, order_values2)| {
+        projected_results.sort_by(|(_, order_values1, _), (_, order_values2, _)| {
             let mut index: usize = 0;
             for expr in &order_by_exprs {
                 let mut ordering = order_values1[index]
@@ -163,23 +264,119 @@ impl SelectStmt {
                 }
                 index += 1;
             }
-            Ordering::Equal
+
+            match (order_values1.get(index), order_values2.get(index)) {
+                (Some(primary1), Some(primary2)) => primary1.partial_cmp(primary2).unwrap(),
+                _ => Ordering::Equal,
+            }
         });
 
-        Ok(projected_results)
+        // values do not hash, their literals are compared instead
+        let mut seen: HashSet<Vec<String>> = HashSet::new();
+        Ok(projected_results
+            .into_iter()
+            .filter(|(_, _, distinct_values)| {
+                distinct_values.is_empty() || seen.insert(distinct_values.iter().map(|v| v.to_sql_literal()).collect())
+            })
+            .map(|(row, _, _)| row)
+            .collect())
     }
 
-    fn execute_where(&mut self, table_manager: &mut TableManager) -> Vec<RowBytes> {
+    /*
+        `limit` stops an index scan once enough rows matched, a full scan reads the whole table
+     */
+    fn execute_where(
+        &mut self,
+        table: &str,
+        table_manager: &mut TableManager,
+        limit: Option<usize>,
+    ) -> Result<Vec<RowBytes>, String> {
         match &mut self.where_expr {
-            None => table_manager.get_tables(&self.table)
-                                 .unwrap()
-                                 .first()
-                                 .unwrap()
-                                 .get_all(),
+            None => Ok(table_manager.get_base_table(table)?.get_all()),
             Some(ref mut w) => {
-                w.execute(&self.table, table_manager)
+                w.execute(table, table_manager, limit)
+            }
+        }
+    }
+
+    /*
+        `select id from t where id > 5;` only needs the keys of the index on `id`,
+        they are read from the B-tree cells without copying the rows.
+        None when the statement reads other fields, is sorted or has a DISTINCT ON.
+     */
+    fn execute_covering(&self, table: &str, table_manager: &mut TableManager) -> Result<Option<SelectResult>, String> {
+        let field = match self.selected_fields.as_slice() {
+            [Expr::Field(field)] if field != "*" && self.distinct_on.is_empty() => field,
+            _ => return Ok(None),
+        };
+        // the keys are read in ascending order
+        if let Some(order_by) = &self.order_by_expr {
+            match order_by.order_by_exprs.as_slice() {
+                [order_by_expr] if order_by_expr.expr.is_field(field) && order_by_expr.order.is_asc() => {}
+                _ => return Ok(None),
+            }
+        }
+        let condition = match &self.where_expr {
+            None => None,
+            Some(where_expr) => match where_expr.single_expr() {
+                Some(condition) if condition.is_on_field(field) => Some(condition),
+                _ => return Ok(None),
+            },
+        };
+
+        for t in table_manager.get_tables(table)?.iter() {
+            if let Some(btree) = t.as_any().downcast_ref::<BtreeTable>() {
+                if btree.key_field_name == *field {
+                    let rows = btree
+                        .scan_keys(condition, self.limit)?
+                        .into_iter()
+                        .map(|key| RowValues::new(vec![Rc::new(key)]))
+                        .collect();
+                    return Ok(Some(SelectResult::new(vec![field.to_string()], rows)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /*
+        `select count(*) from t;` reads the cell counts of the pages instead of the rows,
+        with a WHERE clause the matching rows are counted.
+        None when COUNT(*) is not selected.
+     */
+    fn execute_count(&mut self, table: &str, table_manager: &mut TableManager) -> Result<Option<SelectResult>, String> {
+        match self.selected_fields.as_slice() {
+            [Expr::CountAll] if self.distinct_on.is_empty() => {}
+            fields if fields.contains(&Expr::CountAll) => {
+                return Err(String::from("COUNT(*) can only be selected alone, rows are not grouped."));
             }
+            _ => return Ok(None),
         }
+        let count = match self.where_expr {
+            None => table_manager.get_base_table(table)?.row_count(),
+            Some(_) => self.execute_where(table, table_manager, None)?.len(),
+        };
+
+        let mut rows = vec![RowValues::new(vec![Rc::new(Value::INT(count as i32))])];
+        rows.truncate(self.limit.unwrap_or(1));
+        Ok(Some(SelectResult::new(vec![Expr::CountAll.to_string()], rows)))
+    }
+
+    /*
+        `select 2 * 3;` returns a single row holding the evaluated expressions
+     */
+    fn execute_without_table(&self) -> Result<SelectResult, String> {
+        let mut values = Vec::with_capacity(self.selected_fields.len());
+        for expr in self.selected_fields.iter() {
+            values.push(Rc::new(expr.evaluate_constant()?));
+        }
+
+        let mut rows = vec![RowValues::new(values)];
+        rows.truncate(self.limit.unwrap_or(1));
+        Ok(SelectResult::new(
+            Self::headers(&self.selected_fields),
+            rows,
+        ))
     }
 }
 
@@ -200,12 +397,22 @@ impl InsertStmt {
     }
 
     pub fn execute(&mut self, table_manager: &mut TableManager) -> Result<(), String> {
+        let coerce = table_manager.settings().coerce;
         let meta = table_manager.get_table_metadata(&self.table)?;
         if self.fields.len() == 1 && self.fields.first().unwrap() == "*" {
+            // `fields` keeps the declaration order, unlike `fields_meta_map`
             self.fields = meta.fields
                               .iter()
                               .map(|f| f.data_def.field_name.to_string())
                               .collect();
+            if self.values.len() != self.fields.len() {
+                return Err(format!(
+                    "Table `{}` has {} columns but {} values were supplied.",
+                    self.table,
+                    self.fields.len(),
+                    self.values.len()
+                ));
+            }
         } else {
             match self.fields
                       .iter()
@@ -220,6 +427,73 @@ impl InsertStmt {
                     ));
                 }
             };
+
+            if let Some((index, field)) = self.fields
+                                              .iter()
+                                              .enumerate()
+                                              .find(|(i, f)| self.fields[..*i].contains(f))
+            {
+                return Err(format!(
+                    "Field `{}` is listed more than once, at position {}.",
+                    field,
+                    index + 1
+                ));
+            }
+
+            // there are no defaults nor NULLs, every column needs a value
+            if let Some(missing) = meta.fields
+                                       .iter()
+                                       .find(|f| !self.fields.contains(&f.data_def.field_name))
+            {
+                return Err(format!(
+                    "Field `{}` of table `{}` is required but no value was supplied.",
+                    missing.data_def.field_name, self.table
+                ));
+            }
+        }
+
+        for (field, value) in self.fields.iter().zip(self.values.iter_mut()) {
+            let data_type = &meta.get_field_metadata(field)?.data_def.data_type;
+            *value = match data_type.convert(value, coerce) {
+                Some(converted) => converted,
+                None if matches!((data_type, &*value), (DataType::BOOLEAN, Value::INT(_))) => {
+                    return Err(format!(
+                        "Value {} of field `{}` is not a BOOL, only 0 and 1 are read as false and true.",
+                        value.to_sql_literal(),
+                        field
+                    ));
+                }
+                None if coerce && matches!(value, Value::TEXT(_)) => {
+                    return Err(format!(
+                        "Value {} of field `{}` can not be converted to {}.",
+                        value.to_sql_literal(),
+                        field,
+                        data_type.to_sql()
+                    ));
+                }
+                None => {
+                    return Err(format!(
+                        "Value {} of field `{}` is not of type {}.",
+                        value.to_sql_literal(),
+                        field,
+                        data_type.to_sql()
+                    ));
+                }
+            };
+
+            let len = match (data_type, &*value) {
+                (DataType::BLOB(size), Value::BLOB(bytes)) => Some((*size, bytes.len())),
+                (DataType::TEXT(size), Value::TEXT(text)) => Some((*size, text.len())),
+                _ => None,
+            };
+            if let Some((size, len)) = len {
+                if len > size {
+                    return Err(format!(
+                        "Value of field `{}` is longer than {} bytes.",
+                        field, size
+                    ));
+                }
+            }
         }
 
         let row = RowToInsert::new(
@@ -232,71 +506,51 @@ impl InsertStmt {
         for table in tables.iter_mut() {
             table.insert(&row)?;
         }
+        table_manager.commit(&self.table);
         Ok(())
     }
 }
 
 #[derive(PartialEq, PartialOrd, Debug)]
 pub(crate) struct WhereExpr {
-    condition_cluster: Vec<ConditionCluster>,
+    condition: Condition,
 }
 
 impl WhereExpr {
-    pub(crate) fn new(condition_exprs: Vec<ConditionCluster>) -> WhereExpr {
-        WhereExpr {
-            condition_cluster: condition_exprs,
-        }
-    }
-
-    fn execute(&mut self, table_name: &str, table_manager: &mut TableManager) -> Vec<RowBytes> {
-        let mut index_scan = false;
-        self.condition_cluster.sort_by(|c1, c2|{
-            let indexed1 = c1.iter()
-                             .any(|c| c.find_index(table_name, table_manager).is_some()).to_u8();
-
-            let indexed2 = c2.iter()
-                             .any(|c| c.find_index(table_name, table_manager).is_some()).to_u8();
-            index_scan = (indexed1 | indexed2) == 1;
-            indexed2.cmp(&indexed1)
-        } );
-
-        if index_scan {
-            let mut global_result = vec![];
-            let mut last_op: Option<LogicalOperator> = None;
-            for cluster in self.condition_cluster.iter() {
-                let table = match cluster.iter().filter_map(|c| c.find_index(table_name, table_manager)).next() {
-                    None => {
-                        table_manager.get_tables(table_name).unwrap().first_mut().unwrap()
-                    }
-                    Some(index) => {
-                        index
-                    }
-                };
+    pub(crate) fn new(condition: Condition) -> WhereExpr {
+        WhereExpr { condition }
+    }
 
-                let local_result = table.find_by_condition_cluster(cluster);
-                if last_op.is_none() || last_op.clone().unwrap().combine(cluster.logical_operator) == LogicalOperator::OR {
-                    global_result.extend(local_result.into_iter());
-                    if last_op.is_none() {
-                        last_op = Some(cluster.logical_operator);
-                    } else {
-                        last_op = Some(last_op.unwrap().combine(cluster.logical_operator))
-                    }
-                } else {
-                    let set: HashSet<RowBytes> = HashSet::from_iter(global_result.into_iter());
-                    global_result = vec![];
-                    for r in local_result {
-                        if set.contains(&r) {
-                            global_result.push(r);
-                        }
-                    }
-                }
-            };
+    /*
+        The only condition of a WHERE made of a single comparison
+     */
+    pub(crate) fn single_expr(&self) -> Option<&ConditionExpr> {
+        self.condition.unwrap_as_expr().ok()
+    }
 
-            global_result
-        } else {
-            // full scan
-            table_manager.get_tables(table_name).unwrap().first_mut().unwrap().find_by_condition_clusters(&self.condition_cluster)
+    fn execute(
+        &mut self,
+        table_name: &str,
+        table_manager: &mut TableManager,
+        limit: Option<usize>,
+    ) -> Result<Vec<RowBytes>, String> {
+        // a row whose condition can not be evaluated does not match, an unknown field would match no row
+        self.condition.check_fields(table_manager.get_table_metadata(table_name)?)?;
+
+        // only a conjunction of comparisons can be narrowed by the index of one of its fields
+        if let Some(exprs) = self.condition.conjunction() {
+            let index = exprs
+                .iter()
+                .filter_map(|e| e.field())
+                .find_map(|field| table_manager.find_index_for_field(table_name, field));
+            if let Some(index) = index {
+                let btree = index.as_any().downcast_ref::<BtreeTable>().unwrap();
+                return unsafe { btree.find_by_condition_exprs(exprs, limit) };
+            }
         }
+
+        // full scan
+        Ok(table_manager.get_base_table(table_name)?.find_by_condition(&self.condition))
     }
 }
 
@@ -304,85 +558,284 @@ impl WhereExpr {
 pub(crate) struct CreateStmt {
     table: String,
     definitions: Vec<FieldDefinition>,
+    // `WITH (FILLFACTOR = n)`, only for tables with a primary key
+    fill_factor: Option<usize>,
+    // `WITH (ROW_FORMAT = VARIABLE)`, only for tables without a primary key
+    variable_rows: bool,
+    // `create table t2 as select ...`, the definitions are read from the selected fields
+    select: Option<SelectStmt>,
+    // `create table if not exists`, an existing table is left as is
+    if_not_exists: bool,
 }
 
 impl CreateStmt {
-    pub(crate) fn new(table: String, definitions: Vec<FieldDefinition>) -> CreateStmt {
-        CreateStmt { table, definitions }
+    pub(crate) fn new(table: String, definitions: Vec<FieldDefinition>, fill_factor: Option<usize>) -> CreateStmt {
+        CreateStmt { table, definitions, fill_factor, variable_rows: false, select: None, if_not_exists: false }
     }
 
-    pub fn execute(&self, table_manager: &mut TableManager) -> Result<(), String> {
+    pub(crate) fn from_select(table: String, select: SelectStmt) -> CreateStmt {
+        CreateStmt {
+            table,
+            definitions: vec![],
+            fill_factor: None,
+            variable_rows: false,
+            select: Some(select),
+            if_not_exists: false,
+        }
+    }
+
+    pub(crate) fn set_fill_factor(&mut self, fill_factor: usize) {
+        self.fill_factor = Some(fill_factor);
+    }
+
+    pub(crate) fn set_variable_rows(&mut self, variable_rows: bool) {
+        self.variable_rows = variable_rows;
+    }
+
+    pub(crate) fn set_if_not_exists(&mut self, if_not_exists: bool) {
+        self.if_not_exists = if_not_exists;
+    }
+
+    fn exists(&self) -> bool {
         let table_name = self.table.as_str();
-        let frm_path = build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".frm");
+        Path::new(&build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".frm")).exists()
+    }
 
-        if Path::new(&frm_path).exists() {
-            return Err(format!("Table {} already exists.", table_name));
-        } else {
-            let dir = build_path!(DATA_FOLDER, table_name);
-            match fs::create_dir_all(dir) {
-                Ok(_) => {}
-                Err(_) => {
-                    return Err(String::from("Can not create dir."));
-                }
-            };
+    /*
+        Every check of `execute`, without writing anything to disk
+     */
+    pub fn validate(&self, table_manager: &TableManager) -> Result<(), String> {
+        if self.exists() {
+            return Err(format!("Table {} already exists.", self.table));
         }
 
-        let row_size = self
-            .definitions
+        if self.definitions.iter().filter(|d| d.is_primary_key).count() > 1 {
+            return Err(String::from("Each table can only have ONE primary key."));
+        }
+
+        if self.definitions.iter().any(|d| d.field_name.len() > FIELD_NAME_SIZE) {
+            return Err(format!("Field name can not exceed {FIELD_NAME_SIZE}"));
+        }
+
+        if let Some(fill_factor) = self.fill_factor {
+            if !(MIN_FILL_FACTOR..=MAX_FILL_FACTOR).contains(&fill_factor) {
+                return Err(format!(
+                    "FILLFACTOR must be between {} and {}.",
+                    MIN_FILL_FACTOR, MAX_FILL_FACTOR
+                ));
+            }
+        }
+
+        /* a TEXT longer than TEXT_INLINE_MAX_SIZE only takes the size of its position in the overflow pages */
+        let row_size = self.stored_row_size();
+        if row_size == 0 {
+            return Err(format!(
+                "Rows of table `{}` would take 0 bytes, a table needs at least one column with a size.",
+                self.table
+            ));
+        }
+        let format = table_manager.get_format();
+        match self.definitions.iter().find(|d| d.is_primary_key) {
+            Some(_) if self.variable_rows => {
+                return Err(String::from("ROW_FORMAT = VARIABLE only applies to a table without a primary key."));
+            }
+            Some(key) => {
+                BtreeLeafNodeBodyLayout::new(key.data_type.get_size(), row_size, format, self.get_fill_factor())?;
+            }
+            None if self.fill_factor.is_some() => {
+                return Err(String::from("FILLFACTOR only applies to a table with a primary key."));
+            }
+            None if self.variable_rows && self.definitions.iter().any(|d| d.is_overflow()) => {
+                return Err(format!(
+                    "ROW_FORMAT = VARIABLE can not store a TEXT longer than {}.",
+                    TEXT_INLINE_MAX_SIZE
+                ));
+            }
+            None if self.variable_rows => {
+                sequential_cells_num_by_page(format, self.max_variable_row_size())?;
+            }
+            None => {
+                sequential_cells_num_by_page(format, row_size)?;
+            }
+        };
+        Ok(())
+    }
+
+    fn get_fill_factor(&self) -> usize {
+        self.fill_factor.unwrap_or(DEFAULT_FILL_FACTOR)
+    }
+
+    fn row_size(&self) -> usize {
+        self.definitions
             .iter()
             .map(|d| d.data_type.get_size())
-            .sum();
+            .sum()
+    }
+
+    fn stored_row_size(&self) -> usize {
+        self.definitions
+            .iter()
+            .map(|d| d.stored_size())
+            .sum()
+    }
+
+    /*
+        Size of the cell of a row whose TEXT fields are all full, with `WITH (ROW_FORMAT = VARIABLE)`
+     */
+    fn max_variable_row_size(&self) -> usize {
+        let text_fields = self.definitions.iter().filter(|d| matches!(d.data_type, DataType::TEXT(_))).count();
+        VARIABLE_ROW_LENGTH_SIZE + self.row_size() + text_fields * VARIABLE_TEXT_LENGTH_SIZE
+    }
+
+    /*
+        false when the table already exists and the statement is `create table if not exists`
+     */
+    pub fn execute(&mut self, table_manager: &mut TableManager) -> Result<bool, String> {
+        if self.if_not_exists && self.exists() {
+            return Ok(false);
+        }
+        if let Some(select) = self.select.take() {
+            self.execute_select(select, table_manager)?;
+            return Ok(true);
+        }
+        /* without `WITH (FILLFACTOR = n)` a table with a primary key takes the `fill_factor` pragma */
+        if self.fill_factor.is_none() && self.definitions.iter().any(|d| d.is_primary_key) {
+            self.fill_factor = Some(table_manager.settings().fill_factor);
+        }
+        self.validate(table_manager)?;
+
+        let table_name = self.table.as_str();
+        let frm_path = build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".frm");
+
+        let dir = build_path!(DATA_FOLDER, table_name);
+        match fs::create_dir_all(dir) {
+            Ok(_) => {}
+            Err(_) => {
+                return Err(String::from("Can not create dir."));
+            }
+        };
 
         unsafe {
             match File::create(frm_path) {
-                Ok(file) => self.write_structure_metadata(file)?,
+                Ok(file) => self.write_structure_metadata(file, table_manager.get_format())?,
                 Err(_) => {
                     return Err(String::from("Can not create table."));
                 }
             }
+        }
+
+        let storage_path = self.create_storage_file(table_manager.get_format())?;
+        table_manager.register_new_table(&self.table, &storage_path)?;
+        table_manager.get_table_metadata(&self.table)?.check_layout(&self.definitions)?;
+        Ok(true)
+    }
+
+    /*
+        Writes the empty storage file of the table: `.seq` without primary key, `.idx` otherwise,
+        and the empty `.ovf` file of its long TEXT values if it has some.
+        `.repair` uses it too when only the `.frm` file of a table is left.
+     */
+    pub(crate) fn create_storage_file(&self, format: DbFormat) -> Result<PathBuf, String> {
+        let table_name = self.table.as_str();
+        let primary_key = self.definitions.iter().find(|d| d.is_primary_key);
+        let extension = if primary_key.is_some() { ".idx" } else { ".seq" };
+        let path = build_path!(DATA_FOLDER, table_name, table_name.to_owned() + extension);
+        let file = match File::create(&path) {
+            Ok(file) => file,
+            Err(_) => return Err(String::from("Can not create table.")),
+        };
+        // the overflow pages of a table whose index is rebuilt are kept, its cells still point to them
+        if self.definitions.iter().any(|d| d.is_overflow())
+            && OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(build_path!(DATA_FOLDER, table_name, table_name.to_owned() + OVERFLOW_EXTENSION))
+                .is_err()
+        {
+            return Err(String::from("Can not create table."));
+        }
 
-            let primary_key = self.definitions.iter().filter(|d| d.is_primary_key).next();
+        unsafe {
             match primary_key {
-                None => {
-                    let sequential_path =
-                        build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".seq");
-                    let sequential_file = File::create(&sequential_path).unwrap();
-                    self.write_seq_metadata(sequential_file, row_size)?;
-                    table_manager.register_new_table(&self.table, &sequential_path)
-                }
-                Some(f) => {
-                    let primary_path =
-                        build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".idx");
-                    let primary_file = File::create(&primary_path).unwrap();
-                    self.write_index_metadata(primary_file, f)?;
-                    table_manager.register_new_table(&self.table, &primary_path)
+                None => self.write_seq_metadata(file, self.stored_row_size(), format)?,
+                Some(f) => self.write_index_metadata(file, f)?,
+            }
+        }
+        Ok(path)
+    }
+
+    /*
+        Creates the table with the selected fields of the source table, then inserts the selected rows.
+        A field keeps its type and size, the primary key is kept only when it is selected,
+        otherwise the new table is sequential. Computed columns have no type to copy and are rejected.
+     */
+    fn execute_select(&mut self, mut select: SelectStmt, table_manager: &mut TableManager) -> Result<(), String> {
+        let source = match &select.table {
+            Some(source) => source.clone(),
+            None => return Err(String::from("Create table as select needs a FROM table to copy the fields from.")),
+        };
+        let result = select.execute(table_manager)?;
+
+        let source_meta = table_manager.get_table_metadata(&source)?;
+        self.definitions = Vec::with_capacity(result.fields.len());
+        for field in result.fields.iter() {
+            let data_def = match source_meta.get_field_metadata(field) {
+                Ok(field_meta) => &field_meta.data_def,
+                Err(_) => {
+                    return Err(format!(
+                        "Column `{}` is not a field of table `{}`, its type can not be copied.",
+                        field, source
+                    ))
                 }
+            };
+            let mut definition = FieldDefinition::new(
+                data_def.field_name.to_string(),
+                data_def.data_type,
+                data_def.is_primary_key,
+            );
+            definition.set_collation(data_def.collation);
+            self.definitions.push(definition);
+        }
+
+        self.execute(table_manager)?;
+
+        for row in 0..result.len() {
+            let pairs: Vec<(&str, Value)> = result
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(column, field)| (field.as_str(), result.get(row, column).as_ref().clone()))
+                .collect();
+            // a row that can not be inserted drops the new table, it is not left half filled
+            if let Err(e) = table_manager.insert_row(&self.table, &pairs) {
+                DropStmt::new(self.table.clone(), false).execute(table_manager)?;
+                return Err(e);
             }
         }
+        Ok(())
     }
 
-    unsafe fn write_structure_metadata(&self, mut file: File) -> Result<(), String> {
+    unsafe fn write_structure_metadata(&self, mut file: File, format: DbFormat) -> Result<(), String> {
+        let text_chars_num_size = format.text_chars_num_size();
         let mut total_size = 0;
         total_size += FIELD_NUMBER_SIZE;
         total_size += self.definitions.len() * FIELD_NAME_SIZE;
         total_size += self.definitions.len() * FIELD_TYPE_PRIMARY_SIZE;
 
-        let text_fields = self
+        let sized_fields = self
             .definitions
             .iter()
-            .filter(|d| d.data_type.is_text())
+            .filter(|d| d.data_type.is_sized())
             .count();
-        total_size += text_fields * TEXT_CHARS_NUM_SIZE;
+        total_size += sized_fields * text_chars_num_size;
+        total_size += FRM_FILL_FACTOR_SIZE;
 
         let mut vec = vec![0; total_size];
         let buf = vec.as_mut_ptr();
         let mut buf_pointer = 0; // pointer that points to the position where we should start reading
 
-        ptr::copy_nonoverlapping(
-            &self.definitions.len() as *const usize as *const u8,
-            buf,
-            FIELD_NUMBER_SIZE,
-        );
+        write_uint(buf, self.definitions.len(), FIELD_NUMBER_SIZE);
         buf_pointer += FIELD_NUMBER_SIZE;
 
         self.definitions.iter().for_each(|field_definition| {
@@ -392,8 +845,11 @@ impl CreateStmt {
                 field_definition.field_name.len(),
             );
             buf_pointer += FIELD_NAME_SIZE;
-            let data_type_primary: u8 = (field_definition.data_type.to_bit_code() << 1)
+            let mut data_type_primary: u8 = (field_definition.data_type.to_bit_code() << 1)
                 | field_definition.is_primary_key.to_u8();
+            if field_definition.collation == Collation::NOCASE {
+                data_type_primary |= FIELD_NOCASE_BIT;
+            }
             ptr::copy_nonoverlapping(
                 &data_type_primary as *const u8,
                 buf.add(buf_pointer),
@@ -401,17 +857,14 @@ impl CreateStmt {
             );
             buf_pointer += FIELD_TYPE_PRIMARY_SIZE;
             match field_definition.data_type {
-                DataType::TEXT(size) => {
-                    ptr::copy_nonoverlapping(
-                        &size as *const usize as *const u8,
-                        buf.add(buf_pointer),
-                        TEXT_CHARS_NUM_SIZE,
-                    );
-                    buf_pointer += TEXT_CHARS_NUM_SIZE;
+                DataType::TEXT(size) | DataType::BLOB(size) => {
+                    write_uint(buf.add(buf_pointer), size, text_chars_num_size);
+                    buf_pointer += text_chars_num_size;
                 }
                 _ => {}
             }
         });
+        vec[buf_pointer] = self.get_fill_factor() as u8;
 
         if file.write(vec.as_slice()).is_err() {
             return Err(format!(
@@ -440,11 +893,7 @@ impl CreateStmt {
         );
         buf_pointer += INDEXED_FIELD_TYPE_PRIMARY;
 
-        ptr::copy_nonoverlapping(
-            &primary_field.data_type.get_size() as *const usize as *const u8,
-            buf.add(buf_pointer),
-            INDEXED_FIELD_SIZE,
-        );
+        write_uint(buf.add(buf_pointer), primary_field.data_type.get_size(), INDEXED_FIELD_SIZE);
         buf_pointer += INDEXED_FIELD_SIZE;
 
         ptr::copy_nonoverlapping(
@@ -459,15 +908,16 @@ impl CreateStmt {
         Ok(())
     }
 
-    unsafe fn write_seq_metadata(&self, mut file: File, row_size: usize) -> Result<(), String> {
+    unsafe fn write_seq_metadata(
+        &self,
+        mut file: File,
+        row_size: usize,
+        format: DbFormat,
+    ) -> Result<(), String> {
         let mut vec = vec![0; SEQUENTIAL_NODE_HEADER_SIZE];
         let buf = vec.as_mut_ptr();
-        let cells_num = (PAGE_SIZE - SEQUENTIAL_NODE_HEADER_SIZE) / row_size;
-        ptr::copy_nonoverlapping(
-            &cells_num as *const usize as *mut u8,
-            buf,
-            LEAF_NODE_NUM_CELLS_SIZE,
-        );
+        let cells_num = sequential_cells_num_by_page(format, row_size)?;
+        write_u32(buf, if self.variable_rows { VARIABLE_ROW_FORMAT } else { cells_num });
 
         if file.write(vec.as_slice()).is_err() {
             return Err(format!("Can not write metadata for table {}!", self.table));
@@ -477,110 +927,381 @@ impl CreateStmt {
 }
 
 #[derive(PartialEq, PartialOrd, Debug)]
-pub(crate) struct FieldDefinition {
-    pub field_name: String,
-    pub data_type: DataType,
-    pub is_primary_key: bool,
+pub(crate) struct DropStmt {
+    table: String,
+    // `drop table if exists`, a missing table is not an error
+    if_exists: bool,
 }
 
-impl FieldDefinition {
-    pub fn new(field: String, data_type: DataType, is_primary_key: bool) -> FieldDefinition {
-        FieldDefinition {
-            field_name: field,
-            data_type,
-            is_primary_key,
-        }
+impl DropStmt {
+    pub(crate) fn new(table: String, if_exists: bool) -> DropStmt {
+        DropStmt { table, if_exists }
     }
 
-    pub fn is_primary(&self) -> bool {
-        self.is_primary_key
+    /*
+        Removes the directory of the table with every file in it.
+        false when the table does not exist and the statement is `drop table if exists`
+     */
+    pub fn execute(&self, table_manager: &mut TableManager) -> Result<bool, String> {
+        let dir = build_path!(DATA_FOLDER, &self.table);
+        if !Path::new(&dir).exists() {
+            return match self.if_exists {
+                true => Ok(false),
+                false => Err(format!("Table `{}` does not exist.", self.table)),
+            };
+        }
+
+        // storage files must be closed before they can be removed
+        table_manager.close_table(&self.table);
+        match fs::remove_dir_all(dir) {
+            Ok(_) => Ok(true),
+            Err(_) => Err(format!("Can not drop table {}.", self.table)),
+        }
     }
 }
 
+/*
+    `REINDEX t;` rebuilds the B-tree of `t` from the rows of its leaf pages
+ */
 #[derive(PartialEq, PartialOrd, Debug)]
-pub(crate) struct OrderByCluster {
-    pub(crate) order_by_exprs: Vec<OrderByExpr>,
+pub(crate) struct ReindexStmt {
+    table: String,
 }
 
-impl OrderByCluster {
-    pub fn new(order_by_exprs: Vec<OrderByExpr>) -> OrderByCluster {
-        OrderByCluster { order_by_exprs }
+impl ReindexStmt {
+    pub(crate) fn new(table: String) -> ReindexStmt {
+        ReindexStmt { table }
+    }
+
+    pub(crate) fn table(&self) -> &str {
+        &self.table
+    }
+
+    /*
+        the number of rows in the rebuilt index
+     */
+    pub fn execute(&self, table_manager: &mut TableManager) -> Result<usize, String> {
+        table_manager.reindex(&self.table)
     }
 }
 
+/*
+    `PRAGMA name;` reads a setting, `PRAGMA name = value;` changes it
+ */
 #[derive(PartialEq, PartialOrd, Debug)]
-pub(crate) struct OrderByExpr {
-    field: String,
-    order: Order,
+pub(crate) struct PragmaStmt {
+    name: String,
+    value: Option<Value>,
 }
 
-impl OrderByExpr {
-    pub fn new(field: String, order: Order) -> OrderByExpr {
-        OrderByExpr { field, order }
+impl PragmaStmt {
+    pub(crate) fn new(name: String, value: Option<Value>) -> PragmaStmt {
+        PragmaStmt { name, value }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /*
+        returns the value of the setting, after the change if any
+     */
+    pub fn execute(&self, table_manager: &mut TableManager) -> Result<Value, String> {
+        if let Some(value) = &self.value {
+            table_manager.set_setting(&self.name, value)?;
+        }
+        table_manager.get_setting(&self.name)
     }
 }
 
-#[derive(PartialEq, Debug, PartialOrd, Clone)]
-pub enum Condition {
-    Cluster(ConditionCluster),
-    Expr(ConditionExpr)
+#[derive(PartialEq, PartialOrd, Debug)]
+pub(crate) enum AlterAction {
+    RenameTable(String),
+    RenameColumn(String, String),
 }
 
-impl Condition {
-    pub fn unwrap_as_expr(&self) -> Result<&ConditionExpr, String> {
-        match self {
-            Condition::Cluster(_) => {Err(String::from("The current condition is not an Expr!"))}
-            Condition::Expr(e) => {Ok(e)}
-        }
+#[derive(PartialEq, PartialOrd, Debug)]
+pub(crate) struct AlterStmt {
+    table: String,
+    action: AlterAction,
+}
+
+impl AlterStmt {
+    pub(crate) fn new(table: String, action: AlterAction) -> AlterStmt {
+        AlterStmt { table, action }
     }
 
-    pub fn unwrap_as_cluster(&self) -> Result<&ConditionCluster, String> {
-        match self {
-            Condition::Cluster(c) => {Ok(c)}
-            Condition::Expr(_) => {Err(String::from("The current condition is not a Cluster!"))}
+    pub fn execute(&self, table_manager: &mut TableManager) -> Result<(), String> {
+        let table_name = self.table.as_str();
+        let frm_path = build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".frm");
+        if !Path::new(&frm_path).exists() {
+            return Err(format!("Table `{}` does not exist.", table_name));
         }
-    }
 
-    pub fn is_expr(&self) -> bool {
-        match self {
-            Condition::Cluster(_) => {false}
-            Condition::Expr(_) => {
-                true
+        match &self.action {
+            AlterAction::RenameTable(new_name) => self.rename_table(new_name, table_manager),
+            AlterAction::RenameColumn(old_name, new_name) => {
+                self.rename_column(old_name, new_name, table_manager)
             }
         }
     }
 
-    pub fn get_field_max_size(&self, table_meta: &TableStructureMetadata) -> usize{
-        let mut max_size: usize = 0;
-        match self {
-            Condition::Cluster(cluster) => {
-                for condition in cluster.iter() {
-                    max_size = max(max_size, condition.get_field_max_size(table_meta));
+    fn rename_table(&self, new_name: &str, table_manager: &mut TableManager) -> Result<(), String> {
+        let new_dir = build_path!(DATA_FOLDER, new_name);
+        if Path::new(&new_dir).exists() {
+            return Err(format!("Table {} already exists.", new_name));
+        }
+
+        // storage files must be closed before they can be moved
+        table_manager.close_table(&self.table);
+
+        let old_dir = build_path!(DATA_FOLDER, &self.table);
+        for (file_name, path) in list_files_of_folder(&old_dir)? {
+            let file_name = file_name.into_string().unwrap();
+            let extension = match file_name.strip_prefix(self.table.as_str()) {
+                Some(extension) if extension.starts_with('.') => extension,
+                _ => continue,
+            };
+
+            if fs::rename(&path, build_path!(&old_dir, new_name.to_owned() + extension)).is_err() {
+                return Err(format!("Can not rename file {}.", file_name));
+            }
+        }
+
+        match fs::rename(old_dir, new_dir) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("Can not rename table {}.", self.table)),
+        }
+    }
+
+    fn rename_column(
+        &self,
+        old_name: &str,
+        new_name: &str,
+        table_manager: &mut TableManager,
+    ) -> Result<(), String> {
+        // offset of the field name inside the .frm file
+        let text_chars_num_size = table_manager.get_format().text_chars_num_size();
+        let name_offset = {
+            let table_meta = table_manager.get_table_metadata(&self.table)?;
+            table_meta.get_field_metadata(old_name)?;
+            if table_meta.get_field_metadata(new_name).is_ok() {
+                return Err(format!(
+                    "Field `{}` already exists in table `{}`.",
+                    new_name, self.table
+                ));
+            }
+
+            let mut offset = FIELD_NUMBER_SIZE;
+            for field in table_meta.fields.iter() {
+                if field.data_def.field_name == old_name {
+                    break;
+                }
+                offset += FIELD_NAME_SIZE + FIELD_TYPE_PRIMARY_SIZE;
+                if field.data_def.data_type.is_sized() {
+                    offset += text_chars_num_size;
                 }
             }
-            Condition::Expr(e) => {
-                max_size = max(max_size, table_meta.get_field_metadata(&e.field).unwrap().size);
+            offset
+        };
+
+        // cached metadata holds the old name, it will be reloaded on the next access
+        table_manager.close_table(&self.table);
+
+        for (file_name, path) in list_files_of_folder(&build_path!(DATA_FOLDER, &self.table))? {
+            let file_name = file_name.into_string().unwrap();
+            if file_name.ends_with(".frm") {
+                Self::rewrite_field_name(&path, name_offset, old_name, new_name)?;
+            } else if file_name.ends_with(".idx") {
+                // only indexes whose key is the renamed field are affected
+                Self::rewrite_field_name(&path, INDEXED_FIELD_NAME_SIZE_OFFSET, old_name, new_name)?;
             }
         }
-        max_size
+        Ok(())
+    }
+
+    fn rewrite_field_name(
+        path: &Path,
+        offset: usize,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<(), String> {
+        let error = || format!("Can not rename field `{}` in {}.", old_name, path.display());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|_| error())?;
+
+        let mut stored_name: [u8; FIELD_NAME_SIZE] = [0; FIELD_NAME_SIZE];
+        file.seek(SeekFrom::Start(offset as u64)).map_err(|_| error())?;
+        file.read_exact(&mut stored_name).map_err(|_| error())?;
+        if u8_array_to_string(&stored_name) != old_name {
+            return Ok(());
+        }
+
+        file.seek(SeekFrom::Start(offset as u64)).map_err(|_| error())?;
+        file.write_all(&to_u8_array!(new_name, FIELD_NAME_SIZE)).map_err(|_| error())
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Debug)]
+pub(crate) struct FieldDefinition {
+    pub field_name: String,
+    pub data_type: DataType,
+    pub is_primary_key: bool,
+    pub collation: Collation,
+}
+
+impl FieldDefinition {
+    pub fn new(field: String, data_type: DataType, is_primary_key: bool) -> FieldDefinition {
+        FieldDefinition {
+            field_name: field,
+            data_type,
+            is_primary_key,
+            collation: Collation::BINARY,
+        }
+    }
+
+    pub fn set_collation(&mut self, collation: Collation) {
+        self.collation = collation;
+    }
+
+    /*
+        A TEXT declared longer than TEXT_INLINE_MAX_SIZE is stored in the overflow pages of the table.
+        A primary key is always in the cell, the index compares it in place.
+     */
+    pub fn is_overflow(&self) -> bool {
+        !self.is_primary_key && matches!(self.data_type, DataType::TEXT(size) if size > TEXT_INLINE_MAX_SIZE)
+    }
+
+    /*
+        Bytes of the field in a cell, the position of the value for an overflow TEXT
+     */
+    pub fn stored_size(&self) -> usize {
+        if self.is_overflow() {
+            OVERFLOW_POINTER_SIZE
+        } else {
+            self.data_type.get_size()
+        }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.is_primary_key
+    }
+}
+
+/*
+    How the TEXT values of a field are compared, `COLLATE BINARY|NOCASE` after its type
+ */
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum Collation {
+    BINARY,
+    // letters are compared without their case, `'a' = 'A'` and `'a' < 'B'`
+    NOCASE,
+}
+
+impl TryFrom<&str> for Collation {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "binary" => Ok(Collation::BINARY),
+            "nocase" => Ok(Collation::NOCASE),
+            _ => Err(format!("Unknown collation `{}`, expected BINARY or NOCASE.", value)),
+        }
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Debug)]
+pub(crate) struct OrderByCluster {
+    pub(crate) order_by_exprs: Vec<OrderByExpr>,
+}
+
+impl OrderByCluster {
+    pub fn new(order_by_exprs: Vec<OrderByExpr>) -> OrderByCluster {
+        OrderByCluster { order_by_exprs }
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Debug)]
+pub(crate) struct OrderByExpr {
+    // a field or an expression over the fields, e.g. `order by price * qty desc`
+    expr: Expr,
+    order: Order,
+}
+
+impl OrderByExpr {
+    pub fn new(expr: Expr, order: Order) -> OrderByExpr {
+        OrderByExpr { expr, order }
+    }
+}
+
+#[derive(PartialEq, Debug, PartialOrd, Clone)]
+pub enum Condition {
+    Cluster(ConditionCluster),
+    Expr(ConditionExpr)
+}
+
+impl Condition {
+    pub fn unwrap_as_expr(&self) -> Result<&ConditionExpr, String> {
+        match self {
+            Condition::Cluster(_) => {Err(String::from("The current condition is not an Expr!"))}
+            Condition::Expr(e) => {Ok(e)}
+        }
     }
 
-    pub fn find_index<'a>(&'a self, table_name: &str, table_manager: &'a TableManager) -> Option<&Box<dyn Table>> {
-        let mut has_indexed: Option<&Box<dyn Table>> = None;
+    pub fn logical_operator(&self) -> LogicalOperator {
         match self {
-            Condition::Cluster(cluster) => {
-                for condition in cluster.iter() {
-                    has_indexed = condition.find_index(table_name, table_manager);
-                    if has_indexed.is_some() {
-                        break;
-                    }
-                }
+            Condition::Cluster(c) => c.logical_operator,
+            Condition::Expr(e) => e.logical_operator,
+        }
+    }
+
+    pub fn set_logical_operator(&mut self, logical_operator: LogicalOperator) {
+        match self {
+            Condition::Cluster(c) => c.logical_operator = logical_operator,
+            Condition::Expr(e) => e.logical_operator = logical_operator,
+        }
+    }
+
+    /*
+        The comparisons of a condition made only of ANDs, e.g. `a = 1 and b > 2`.
+     */
+    pub fn conjunction(&self) -> Option<Vec<&ConditionExpr>> {
+        match self {
+            Condition::Expr(e) => Some(vec![e]),
+            Condition::Cluster(c) if c.logical_operator == LogicalOperator::AND && !c.negated => {
+                c.iter().map(|condition| condition.unwrap_as_expr().ok()).collect()
             }
+            Condition::Cluster(_) => None,
+        }
+    }
+
+    /*
+        Fails on the first field of the condition that is not a field of the table
+        and on the first comparison of values that can not be compared
+     */
+    pub(crate) fn check_fields(&mut self, table_meta: &TableStructureMetadata) -> Result<(), String> {
+        match self {
+            Condition::Cluster(c) => c
+                .conditions
+                .iter_mut()
+                .try_for_each(|condition| condition.check_fields(table_meta)),
             Condition::Expr(e) => {
-                has_indexed = table_manager.find_index_for_field(table_name, &e.field);
+                e.left.check_fields(table_meta)?;
+                e.right.check_fields(table_meta)?;
+                e.check_types(table_meta)
             }
         }
-        has_indexed
+    }
+
+    pub(crate) fn matches(&self, row: &[u8], table_meta: &TableStructureMetadata) -> bool {
+        match self {
+            Condition::Cluster(c) => c.matches(row, table_meta),
+            Condition::Expr(e) => e.compare_expression(row, table_meta),
+        }
     }
 }
 
@@ -588,38 +1309,152 @@ impl Condition {
 pub(crate) struct ConditionCluster {
     pub logical_operator: LogicalOperator,
     pub conditions: Vec<Condition>,
+    // `NOT (...)`, the result of the conditions is inverted
+    pub negated: bool,
 }
 
 impl ConditionCluster {
     pub(crate) fn new(logical_operator: LogicalOperator, conditions: Vec<Condition>) -> ConditionCluster {
-        ConditionCluster { logical_operator, conditions }
+        ConditionCluster { logical_operator, conditions, negated: false }
+    }
+
+    /*
+        `NOT (condition)`, a single comparison becomes a cluster of its own
+     */
+    pub(crate) fn negate(condition: Condition) -> ConditionCluster {
+        match condition {
+            Condition::Cluster(mut cluster) => {
+                cluster.negated = !cluster.negated;
+                cluster
+            }
+            condition => ConditionCluster {
+                logical_operator: condition.logical_operator(),
+                conditions: vec![condition],
+                negated: true,
+            },
+        }
     }
 
     pub fn iter(&self) -> Iter<Condition> {
         self.conditions.iter()
     }
+
+    /*
+        Conditions are folded from left to right, each one is joined to the previous result by its own operator.
+     */
+    pub(crate) fn matches(&self, row: &[u8], table_meta: &TableStructureMetadata) -> bool {
+        let mut matched: Option<bool> = None;
+        for condition in self.iter() {
+            matched = Some(match matched {
+                None => condition.matches(row, table_meta),
+                // no need to evaluate a condition that can not change the result
+                Some(true) if condition.logical_operator() == LogicalOperator::OR => true,
+                Some(false) if condition.logical_operator() == LogicalOperator::AND => false,
+                Some(previous) => condition.logical_operator().operate(previous, condition.matches(row, table_meta)),
+            });
+        }
+        matched.unwrap_or(true) ^ self.negated
+    }
 }
 
 #[derive(PartialEq, Debug, PartialOrd, Clone)]
 pub(crate) struct ConditionExpr {
     pub logical_operator: LogicalOperator,
-    pub field: String,
+    pub left: Expr,
     pub operator: Operator,
-    pub value: Value,
+    pub right: Expr,
 }
 
 impl ConditionExpr {
     pub(crate) fn new(
         logical_operator: LogicalOperator,
-        field: String,
+        left: Expr,
         operator: Operator,
-        value: Value,
+        right: Expr,
     ) -> ConditionExpr {
         ConditionExpr {
             logical_operator,
-            field,
+            left,
             operator,
-            value,
+            right,
+        }
+    }
+
+    /*
+        Name of the compared field, None when one of the sides is computed.
+     */
+    pub fn field(&self) -> Option<&str> {
+        match (&self.left, &self.right) {
+            (Expr::Field(field), Expr::Literal(_)) => Some(field),
+            _ => None,
+        }
+    }
+
+    /*
+        The literal the field is compared to, None when the right-hand side is computed.
+     */
+    pub fn value(&self) -> Option<&Value> {
+        match &self.right {
+            Expr::Literal(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn is_on_field(&self, field_name: &str) -> bool {
+        self.field() == Some(field_name)
+    }
+
+    /*
+        Both sides must be of comparable types, every value of an IN list too.
+        A literal 0 or 1 compared to a BOOL reads as false or true, like on insert.
+        Computed sides whose type is only known once evaluated are not checked.
+     */
+    pub(crate) fn check_types(&mut self, table_meta: &TableStructureMetadata) -> Result<(), String> {
+        if let (Some(DataType::BOOLEAN), Expr::Literal(value)) = (self.left.data_type(table_meta), &mut self.right) {
+            *value = value.to_bool();
+        }
+        if let (Some(DataType::BOOLEAN), Expr::Literal(value)) = (self.right.data_type(table_meta), &mut self.left) {
+            *value = value.to_bool();
+        }
+
+        let left_type = match self.left.data_type(table_meta) {
+            Some(left_type) => left_type,
+            None => return Ok(()),
+        };
+        let right_values = match (&self.operator, &self.right) {
+            (Operator::IN(_), Expr::Literal(Value::ARRAY(values))) => values.iter().map(|v| Expr::Literal(v.clone())).collect(),
+            _ => vec![self.right.clone()],
+        };
+        for right in right_values.iter() {
+            match right.data_type(table_meta) {
+                Some(right_type) if !left_type.is_comparable_with(&right_type) => {
+                    return Err(format!(
+                        "`{}` is of type {}, it can not be compared to `{}` of type {}.",
+                        self.left,
+                        left_type.name(),
+                        right,
+                        right_type.name()
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /*
+        Compare computed sides, e.g. `price * 2 > 10` or `a > b`.
+        A row whose expression can not be evaluated (e.g. division by zero) does not match.
+        TEXT is compared without case when one of the sides is a NOCASE field.
+     */
+    pub(crate) fn compare_expression(&self, row: &[u8], table_meta: &TableStructureMetadata) -> bool {
+        let nocase = [&self.left, &self.right]
+            .iter()
+            .any(|side| side.collation(table_meta) == Collation::NOCASE);
+        match (self.left.evaluate(row, table_meta), self.right.evaluate(row, table_meta)) {
+            (Ok(left), Ok(right)) if nocase => self.operator.operate(&left.fold_case(), &right.fold_case()),
+            (Ok(left), Ok(right)) => self.operator.operate(&left, &right),
+            _ => false,
         }
     }
 }
@@ -655,6 +1490,395 @@ impl TryFrom<&str> for Order {
     }
 }
 
+#[derive(PartialEq, Debug, PartialOrd, Clone)]
+pub(crate) enum Expr {
+    Field(String),
+    Literal(Value),
+    Binary(Box<Expr>, ArithmeticOperator, Box<Expr>),
+    Function(ScalarFunction, Vec<Expr>),
+    // `count(*)`, only selected alone since rows are not grouped
+    CountAll,
+}
+
+impl Expr {
+    pub(crate) fn evaluate(&self, row: &[u8], table_meta: &TableStructureMetadata) -> Result<Value, String> {
+        match self {
+            Expr::Field(field_name) => {
+                let field_meta = table_meta.get_field_metadata(field_name)?;
+                Ok(Value::from_bytes(
+                    &field_meta.data_def.data_type,
+                    &row[field_meta.offset..field_meta.offset + field_meta.size],
+                ))
+            }
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Binary(left, operator, right) => operator.operate(
+                &left.evaluate(row, table_meta)?,
+                &right.evaluate(row, table_meta)?,
+            ),
+            Expr::Function(function, args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(arg.evaluate(row, table_meta)?);
+                }
+                function.apply(&values)
+            }
+            Expr::CountAll => Err(String::from("COUNT(*) can only be selected alone, rows are not grouped.")),
+        }
+    }
+
+    /*
+        Evaluates an expression that does not read any row, fields are an error
+     */
+    pub(crate) fn evaluate_constant(&self) -> Result<Value, String> {
+        match self {
+            Expr::Field(field_name) => Err(format!(
+                "Field `{}` can not be selected without a FROM clause.",
+                field_name
+            )),
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Binary(left, operator, right) => {
+                operator.operate(&left.evaluate_constant()?, &right.evaluate_constant()?)
+            }
+            Expr::Function(function, args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(arg.evaluate_constant()?);
+                }
+                function.apply(&values)
+            }
+            Expr::CountAll => Err(String::from("COUNT(*) can not be selected without a FROM clause.")),
+        }
+    }
+
+    /*
+        Fails on the first field of the expression that is not a field of the table
+     */
+    pub(crate) fn check_fields(&self, table_meta: &TableStructureMetadata) -> Result<(), String> {
+        match self {
+            Expr::Field(field_name) => table_meta.get_field_metadata(field_name).map(|_| ()),
+            Expr::Binary(left, _, right) => {
+                left.check_fields(table_meta)?;
+                right.check_fields(table_meta)
+            }
+            Expr::Function(_, args) => args.iter().try_for_each(|arg| arg.check_fields(table_meta)),
+            Expr::Literal(_) | Expr::CountAll => Ok(()),
+        }
+    }
+
+    pub(crate) fn is_field(&self, field_name: &str) -> bool {
+        match self {
+            Expr::Field(name) => name == field_name,
+            _ => false,
+        }
+    }
+
+    /*
+        Type of the values of the expression, None when it is only known once evaluated
+     */
+    pub(crate) fn data_type(&self, table_meta: &TableStructureMetadata) -> Option<DataType> {
+        match self {
+            Expr::Field(field_name) => table_meta.get_field_metadata(field_name).ok().map(|meta| meta.data_def.data_type),
+            Expr::Literal(value) => value.data_type(),
+            Expr::Binary(left, _, right) => match (left.data_type(table_meta)?, right.data_type(table_meta)?) {
+                (DataType::INTEGER, DataType::INTEGER) => Some(DataType::INTEGER),
+                (DataType::INTEGER | DataType::FLOAT, DataType::INTEGER | DataType::FLOAT) => Some(DataType::FLOAT),
+                _ => None,
+            },
+            Expr::Function(ScalarFunction::LENGTH, _) => Some(DataType::INTEGER),
+            Expr::Function(ScalarFunction::LOWER | ScalarFunction::UPPER | ScalarFunction::SUBSTR, _) => {
+                Some(DataType::TEXT(0))
+            }
+            // numeric functions keep the type of their argument
+            Expr::Function(_, args) => args.first()?.data_type(table_meta),
+            Expr::CountAll => Some(DataType::INTEGER),
+        }
+    }
+
+    /*
+        Collation of a field, computed values are compared as BINARY
+     */
+    pub(crate) fn collation(&self, table_meta: &TableStructureMetadata) -> Collation {
+        match self {
+            Expr::Field(field_name) => table_meta
+                .get_field_metadata(field_name)
+                .map_or(Collation::BINARY, |meta| meta.data_def.collation),
+            _ => Collation::BINARY,
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Field(field_name) => write!(f, "{}", field_name),
+            // Debug keeps the decimal point of whole floats, so `2.0` is not shown as `2`
+            Expr::Literal(value) => write!(f, "{}", value.to_sql_literal()),
+            Expr::Binary(left, operator, right) => {
+                // parentheses are only kept where precedence requires them
+                match left.as_ref() {
+                    Expr::Binary(_, left_operator, _) if left_operator.precedence() < operator.precedence() => {
+                        write!(f, "({})", left)?
+                    }
+                    _ => write!(f, "{}", left)?,
+                }
+                write!(f, " {} ", operator.symbol())?;
+                match right.as_ref() {
+                    Expr::Binary(_, right_operator, _) if right_operator.precedence() <= operator.precedence() => {
+                        write!(f, "({})", right)
+                    }
+                    _ => write!(f, "{}", right),
+                }
+            }
+            Expr::Function(function, args) => write!(
+                f,
+                "{}({})",
+                function.name(),
+                args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+            ),
+            Expr::CountAll => write!(f, "count(*)"),
+        }
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum ScalarFunction {
+    LOWER,
+    UPPER,
+    LENGTH,
+    SUBSTR,
+    ABS,
+    ROUND,
+    CEIL,
+    FLOOR,
+}
+
+impl ScalarFunction {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ScalarFunction::LOWER => "lower",
+            ScalarFunction::UPPER => "upper",
+            ScalarFunction::LENGTH => "length",
+            ScalarFunction::SUBSTR => "substr",
+            ScalarFunction::ABS => "abs",
+            ScalarFunction::ROUND => "round",
+            ScalarFunction::CEIL => "ceil",
+            ScalarFunction::FLOOR => "floor",
+        }
+    }
+
+    // minimum and maximum number of arguments
+    fn arity(&self) -> (usize, usize) {
+        match self {
+            ScalarFunction::SUBSTR => (3, 3),
+            ScalarFunction::ROUND => (1, 2),
+            _ => (1, 1),
+        }
+    }
+
+    pub(crate) fn apply(&self, args: &[Value]) -> Result<Value, String> {
+        let (min, max) = self.arity();
+        if args.len() < min || args.len() > max {
+            return Err(format!(
+                "Function `{}` takes {} argument(s) but {} were supplied.",
+                self.name(),
+                if min == max { min.to_string() } else { format!("{} to {}", min, max) },
+                args.len()
+            ));
+        }
+
+        match self {
+            ScalarFunction::LOWER | ScalarFunction::UPPER | ScalarFunction::LENGTH | ScalarFunction::SUBSTR => {
+                self.apply_text(args)
+            }
+            ScalarFunction::ABS | ScalarFunction::ROUND | ScalarFunction::CEIL | ScalarFunction::FLOOR => {
+                self.apply_numeric(args)
+            }
+        }
+    }
+
+    /*
+        Text functions count characters, `substr` starts at 1 like in SQLite.
+     */
+    fn apply_text(&self, args: &[Value]) -> Result<Value, String> {
+        let text = match &args[0] {
+            Value::TEXT(text) => text,
+            other => return Err(self.type_error(other)),
+        };
+
+        match self {
+            ScalarFunction::LOWER => Ok(Value::TEXT(text.to_lowercase())),
+            ScalarFunction::UPPER => Ok(Value::TEXT(text.to_uppercase())),
+            ScalarFunction::LENGTH => Ok(Value::INT(text.chars().count() as i32)),
+            _ => {
+                let (start, len) = match (&args[1], &args[2]) {
+                    (Value::INT(start), Value::INT(len)) if *start >= 1 && *len >= 0 => {
+                        (*start as usize, *len as usize)
+                    }
+                    _ => {
+                        return Err(String::from(
+                            "Function `substr` expects a start >= 1 and a length >= 0.",
+                        ))
+                    }
+                };
+                Ok(Value::TEXT(text.chars().skip(start - 1).take(len).collect()))
+            }
+        }
+    }
+
+    /*
+        An INTEGER argument keeps its type, a FLOAT one gives a FLOAT.
+        `round(x, n)` keeps n decimals, n defaults to 0.
+     */
+    fn apply_numeric(&self, args: &[Value]) -> Result<Value, String> {
+        match &args[0] {
+            Value::INT(i) => match self {
+                ScalarFunction::ABS => i
+                    .checked_abs()
+                    .map(Value::INT)
+                    .ok_or_else(|| format!("Integer overflow in `abs({})`.", i)),
+                _ => Ok(Value::INT(*i)),
+            },
+            Value::FLOAT(f) => Ok(Value::FLOAT(match self {
+                ScalarFunction::ABS => f.abs(),
+                ScalarFunction::CEIL => f.ceil(),
+                ScalarFunction::FLOOR => f.floor(),
+                _ => {
+                    let digits = match args.get(1) {
+                        None => 0,
+                        Some(Value::INT(digits)) if *digits >= 0 => *digits,
+                        Some(_) => {
+                            return Err(String::from(
+                                "Function `round` expects a number of decimals >= 0.",
+                            ))
+                        }
+                    };
+                    let factor = 10f64.powi(digits);
+                    let rounded = (*f as f64 * factor).round() / factor;
+                    // past the precision of a FLOAT the factor overflows, there is nothing left to round
+                    if rounded.is_finite() { rounded as f32 } else { *f }
+                }
+            })),
+            other => Err(self.type_error(other)),
+        }
+    }
+
+    fn type_error(&self, value: &Value) -> String {
+        format!(
+            "Function `{}` can not be applied to `{}`.",
+            self.name(),
+            value.to_string()
+        )
+    }
+}
+
+impl TryFrom<&str> for ScalarFunction {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "lower" => Ok(ScalarFunction::LOWER),
+            "upper" => Ok(ScalarFunction::UPPER),
+            "length" => Ok(ScalarFunction::LENGTH),
+            "substr" => Ok(ScalarFunction::SUBSTR),
+            "abs" => Ok(ScalarFunction::ABS),
+            "round" => Ok(ScalarFunction::ROUND),
+            "ceil" => Ok(ScalarFunction::CEIL),
+            "floor" => Ok(ScalarFunction::FLOOR),
+            _ => Err(format!("Unknown function `{}`.", value)),
+        }
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum ArithmeticOperator {
+    ADD,
+    SUBTRACT,
+    MULTIPLY,
+    DIVIDE,
+}
+
+impl ArithmeticOperator {
+    /*
+        INTEGER op INTEGER stays an INTEGER (division truncates),
+        as soon as one side is a FLOAT both are promoted to FLOAT.
+        The FLOAT result is computed in f64 like `compare_floats`, it is rounded to a FLOAT once.
+     */
+    pub(crate) fn operate(&self, a: &Value, b: &Value) -> Result<Value, String> {
+        match (a, b) {
+            (Value::INT(x), Value::INT(y)) => {
+                let result = match self {
+                    ArithmeticOperator::ADD => x.checked_add(*y),
+                    ArithmeticOperator::SUBTRACT => x.checked_sub(*y),
+                    ArithmeticOperator::MULTIPLY => x.checked_mul(*y),
+                    ArithmeticOperator::DIVIDE => {
+                        if *y == 0 {
+                            return Err(String::from("Division by zero."));
+                        }
+                        x.checked_div(*y)
+                    }
+                };
+                match result {
+                    None => Err(format!(
+                        "Integer overflow in `{} {} {}`.",
+                        x,
+                        self.symbol(),
+                        y
+                    )),
+                    Some(i) => Ok(Value::INT(i)),
+                }
+            }
+            (Value::INT(_) | Value::FLOAT(_), Value::INT(_) | Value::FLOAT(_)) => {
+                let x = a.unwrap_as_wide_number()?;
+                let y = b.unwrap_as_wide_number()?;
+                Ok(Value::FLOAT(match self {
+                    ArithmeticOperator::ADD => x + y,
+                    ArithmeticOperator::SUBTRACT => x - y,
+                    ArithmeticOperator::MULTIPLY => x * y,
+                    ArithmeticOperator::DIVIDE => x / y,
+                } as f32))
+            }
+            _ => Err(format!(
+                "Operator `{}` can not be applied to `{}` and `{}`.",
+                self.symbol(),
+                a.to_string(),
+                b.to_string()
+            )),
+        }
+    }
+
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            ArithmeticOperator::ADD | ArithmeticOperator::SUBTRACT => 1,
+            ArithmeticOperator::MULTIPLY | ArithmeticOperator::DIVIDE => 2,
+        }
+    }
+
+    pub(crate) fn symbol(&self) -> &'static str {
+        match self {
+            ArithmeticOperator::ADD => "+",
+            ArithmeticOperator::SUBTRACT => "-",
+            ArithmeticOperator::MULTIPLY => "*",
+            ArithmeticOperator::DIVIDE => "/",
+        }
+    }
+}
+
+impl TryFrom<&str> for ArithmeticOperator {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "+" => Ok(ArithmeticOperator::ADD),
+            "-" => Ok(ArithmeticOperator::SUBTRACT),
+            "*" => Ok(ArithmeticOperator::MULTIPLY),
+            "/" => Ok(ArithmeticOperator::DIVIDE),
+            _ => Err(format!("Unknown arithmetic operator `{}`.", value)),
+        }
+    }
+}
+
 #[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
 pub(crate) enum Operator {
     EQUALS(bool),
@@ -678,17 +1902,6 @@ impl LogicalOperator {
             LogicalOperator::AND => b1 & b2,
         }
     }
-
-    pub fn combine(&self, other: LogicalOperator) -> LogicalOperator {
-        match self {
-            LogicalOperator::OR => {
-                LogicalOperator::OR
-            }
-            LogicalOperator::AND => {
-                other
-            }
-        }
-    }
 }
 
 impl TryFrom<&str> for LogicalOperator {
@@ -711,6 +1924,7 @@ impl Operator {
             GTE => a >= b,
             LT => a < b,
             LTE => a <= b,
+            // nothing is in an empty array: `IN []` matches no row and `NOT IN []` every row
             IN(negative) => {
                 if let Value::ARRAY(vec) = b {
                     vec.contains(&a) ^ negative
@@ -728,7 +1942,7 @@ impl TryFrom<String> for Operator {
     fn try_from(value: String) -> Result<Operator, Self::Error> {
         match value.as_str() {
             "=" => Ok(EQUALS(false)),
-            "!=" => Ok(EQUALS(true)),
+            "!=" | "<>" => Ok(EQUALS(true)),
             ">" => Ok(GT),
             ">=" => Ok(GTE),
             "<" => Ok(LT),
@@ -741,82 +1955,116 @@ impl TryFrom<String> for Operator {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Value {
     INT(i32),
     FLOAT(f32),
     BOOL(bool),
     TEXT(String),
+    BLOB(Vec<u8>),
     ARRAY(Vec<Value>),
 }
 
+/*
+    Values of different types are not equal and not ordered, except INT and FLOAT which are compared as numbers
+ */
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        match self {
-            Value::INT(i) => *i == other.unwrap_as_int().unwrap(),
-            Value::FLOAT(f) => *f == other.unwrap_as_float().unwrap(),
-            Value::BOOL(b) => *b == other.unwrap_into_bool().unwrap(),
-            Value::TEXT(s) => s == other.unwrap_as_string().unwrap(),
-            Value::ARRAY(a) => a == other.unwrap_as_array().unwrap(),
+        match (self, other) {
+            (Value::INT(a), Value::INT(b)) => a == b,
+            (Value::INT(_) | Value::FLOAT(_), Value::INT(_) | Value::FLOAT(_)) => self.partial_cmp(other).is_some_and(|o| o.is_eq()),
+            (Value::BOOL(a), Value::BOOL(b)) => a == b,
+            (Value::TEXT(a), Value::TEXT(b)) => a == b,
+            (Value::BLOB(a), Value::BLOB(b)) => a == b,
+            (Value::ARRAY(a), Value::ARRAY(b)) => a == b,
+            _ => false,
         }
     }
 }
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self {
-            Value::INT(i) => i.partial_cmp(&other.unwrap_as_int().unwrap()),
-            Value::FLOAT(f) => f.partial_cmp(&other.unwrap_as_float().unwrap()),
-            Value::BOOL(b) => b.partial_cmp(&other.unwrap_into_bool().unwrap()),
-            Value::TEXT(s) => s.partial_cmp(&other.unwrap_as_string().unwrap()),
-            Value::ARRAY(_) => None,
+        match (self, other) {
+            (Value::INT(a), Value::INT(b)) => a.partial_cmp(b),
+            (Value::INT(_) | Value::FLOAT(_), Value::INT(_) | Value::FLOAT(_)) => Some(Value::compare_floats(
+                self.unwrap_as_wide_number().ok()?,
+                other.unwrap_as_wide_number().ok()?,
+            )),
+            (Value::BOOL(a), Value::BOOL(b)) => a.partial_cmp(b),
+            (Value::TEXT(a), Value::TEXT(b)) => a.partial_cmp(b),
+            (Value::BLOB(a), Value::BLOB(b)) => a.partial_cmp(b),
+            _ => None,
         }
     }
 }
 
 impl Value {
-    pub(crate) fn are_same_variant(a: &Value, b: &Value) -> bool {
-        match (a, b) {
-            (Value::INT(_), Value::INT(_)) => true,
-            (Value::FLOAT(_), Value::FLOAT(_)) => true,
-            (Value::TEXT(_), Value::TEXT(_)) => true,
-            (Value::ARRAY(_), Value::ARRAY(_)) => true,
-            (Value::BOOL(_), Value::BOOL(_)) => true,
-            _ => false,
+    /*
+        The value compared under NOCASE: TEXT in lowercase, the TEXT of an array too
+     */
+    pub(crate) fn fold_case(&self) -> Value {
+        match self {
+            Value::TEXT(s) => Value::TEXT(s.to_lowercase()),
+            Value::ARRAY(values) => Value::ARRAY(values.iter().map(|v| v.fold_case()).collect()),
+            value => value.clone(),
         }
     }
 
-    pub fn unwrap_as_int(&self) -> Result<i32, &str> {
-        match self {
-            Value::INT(v) => Ok(*v),
-            _ => Err("Current Value is not an Integer."),
+    /*
+        Total order over floats: NaN is equal to itself and greater than any other float,
+        so sorting never fails and NaN rows are grouped at the end of an ascending order.
+        INT and FLOAT are both compared as f64, which holds every i32 and f32 exactly.
+     */
+    pub(crate) fn compare_floats(a: f64, b: f64) -> Ordering {
+        match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => a.partial_cmp(&b).unwrap(),
         }
     }
 
-    pub fn unwrap_as_float(&self) -> Result<f32, &str> {
+    /*
+        Type of a value as declared in CREATE TABLE, an array has none
+     */
+    pub(crate) fn data_type(&self) -> Option<DataType> {
         match self {
-            Value::FLOAT(v) => Ok(*v),
-            _ => Err("Current Value is not a Float."),
+            Value::INT(_) => Some(DataType::INTEGER),
+            Value::FLOAT(_) => Some(DataType::FLOAT),
+            Value::BOOL(_) => Some(DataType::BOOLEAN),
+            Value::TEXT(s) => Some(DataType::TEXT(s.len())),
+            Value::BLOB(b) => Some(DataType::BLOB(b.len())),
+            Value::ARRAY(_) => None,
         }
     }
 
-    pub fn unwrap_as_string(&self) -> Result<&String, &str> {
+    /*
+        0 and 1 as false and true, the values of an array one by one, any other value unchanged
+     */
+    pub(crate) fn to_bool(&self) -> Value {
         match self {
-            Value::TEXT(v) => Ok(v),
-            _ => Err("Current Value is not a String."),
+            Value::ARRAY(values) => Value::ARRAY(values.iter().map(|v| v.to_bool()).collect()),
+            value => DataType::BOOLEAN.convert(value, false).unwrap_or_else(|| value.clone()),
         }
     }
 
-    pub fn unwrap_as_array(&self) -> Result<&Vec<Value>, &str> {
-        match self {
-            Value::ARRAY(v) => Ok(v),
-            _ => Err("Current Value is not an Array."),
+    pub(crate) fn are_same_variant(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::INT(_), Value::INT(_)) => true,
+            (Value::FLOAT(_), Value::FLOAT(_)) => true,
+            (Value::TEXT(_), Value::TEXT(_)) => true,
+            (Value::BLOB(_), Value::BLOB(_)) => true,
+            (Value::ARRAY(_), Value::ARRAY(_)) => true,
+            (Value::BOOL(_), Value::BOOL(_)) => true,
+            _ => false,
         }
     }
 
-    pub fn unwrap_into_bool(&self) -> Result<bool, &str> {
+    fn unwrap_as_wide_number(&self) -> Result<f64, &str> {
         match self {
-            Value::BOOL(v) => Ok(*v),
-            _ => Err("Current Value is not a Boolean."),
+            Value::INT(v) => Ok(*v as f64),
+            Value::FLOAT(v) => Ok(*v as f64),
+            _ => Err("Current Value is not a Number."),
         }
     }
 
@@ -833,33 +2081,52 @@ impl Value {
                     bytes.set_len(*size);
                     Value::TEXT(u8_array_to_string(bytes.as_slice()))
                 }
-                DataType::INTEGER => {
-                    let key: i32 = 0;
-                    ptr::copy_nonoverlapping(
-                        src,
-                        &key as *const i32 as *mut u8,
-                        key_type.get_size(),
-                    );
-                    Value::INT(key)
-                }
-                DataType::FLOAT => {
-                    let key: f32 = 0.0;
-                    ptr::copy_nonoverlapping(
-                        src,
-                        &key as *const f32 as *mut u8,
-                        key_type.get_size(),
-                    );
-                    Value::FLOAT(key)
-                }
-                DataType::BOOLEAN => {
-                    let key: bool = false;
-                    ptr::copy_nonoverlapping(
-                        src,
-                        &key as *const bool as *mut u8,
-                        key_type.get_size(),
-                    );
-                    Value::BOOL(key)
+                DataType::BLOB(size) => {
+                    let len = read_uint(src, BLOB_LENGTH_SIZE).min(*size);
+                    let mut bytes = Vec::<u8>::with_capacity(len);
+                    ptr::copy_nonoverlapping(src.add(BLOB_LENGTH_SIZE), bytes.as_mut_ptr(), len);
+                    bytes.set_len(len);
+                    Value::BLOB(bytes)
                 }
+                DataType::INTEGER => Value::INT(i32::from_le_bytes(ptr::read_unaligned(src as *const [u8; 4]))),
+                DataType::FLOAT => Value::FLOAT(f32::from_le_bytes(ptr::read_unaligned(src as *const [u8; 4]))),
+                // any byte other than 0 is true, copying it into a bool could build an invalid value
+                DataType::BOOLEAN => Value::BOOL(*src != 0),
+            }
+        }
+    }
+
+    /*
+        The value written the way the parser reads it back
+     */
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            Value::TEXT(s) => format!("'{}'", s.replace('\'', "''")),
+            // Debug keeps the decimal point, so the value is parsed back as a FLOAT
+            Value::FLOAT(f) => format!("{:?}", f),
+            Value::ARRAY(a) => {
+                let values: Vec<String> = a.iter().map(|v| v.to_sql_literal()).collect();
+                format!("[{}]", values.join(","))
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /*
+        JSON number, string or bool; a blob is written as its hex literal string
+     */
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::INT(i) => i.to_string(),
+            Value::FLOAT(f) if f.is_finite() => f.to_string(),
+            // JSON has no infinity or NaN
+            Value::FLOAT(_) => String::from("null"),
+            Value::BOOL(b) => b.to_string(),
+            Value::TEXT(s) => json_string(s),
+            Value::BLOB(_) => json_string(&self.to_string()),
+            Value::ARRAY(a) => {
+                let values: Vec<String> = a.iter().map(|v| v.to_json()).collect();
+                format!("[{}]", values.join(","))
             }
         }
     }
@@ -870,61 +2137,897 @@ impl Value {
             Value::FLOAT(f) => f.to_string(),
             Value::BOOL(b) => b.to_string(),
             Value::TEXT(s) => s.to_string(),
+            Value::BLOB(b) => {
+                let hex: String = b.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("x'{}'", hex)
+            }
             Value::ARRAY(a) => {
-                let mut s = String::new();
-                s.push('[');
-                a.iter().for_each(|v| {
-                    s.push_str(&v.to_string());
-                    s.push_str(",")
-                });
-                s.remove(s.len() - 1);
-                s.push(']');
-                s
+                let values: Vec<String> = a.iter().map(|v| v.to_string()).collect();
+                format!("[{}]", values.join(","))
             }
         }
     }
 }
 
 #[derive(PartialEq, PartialOrd, Debug, Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum DataType {
     TEXT(usize),
     INTEGER,
     FLOAT,
     BOOLEAN,
+    BLOB(usize),
 }
 
 impl DataType {
-    pub fn is_text(&self) -> bool {
+    /*
+        Types declared with a max size, which is saved in the metadata file
+     */
+    pub fn is_sized(&self) -> bool {
         match self {
-            DataType::TEXT(_) => true,
+            DataType::TEXT(_) | DataType::BLOB(_) => true,
             _ => false,
         }
     }
+
     pub fn to_bit_code(&self) -> u8 {
         match self {
             DataType::TEXT(_) => 0b0000_0000,
             DataType::INTEGER => 0b0000_0001,
             DataType::FLOAT => 0b0000_0010,
             DataType::BOOLEAN => 0b0000_0011,
+            DataType::BLOB(_) => 0b0000_0100,
         }
     }
 
-    pub fn from_bit_code(bit_code: u8) -> Result<DataType, String> {
+    /*
+        `size` is the declared size of TEXT and BLOB, it is ignored by the fixed size types
+     */
+    pub fn from_bit_code(bit_code: u8, size: usize) -> Result<DataType, String> {
         match bit_code {
-            0b0000_0000 => Ok(DataType::TEXT(TEXT_DEFAULT_SIZE)),
+            0b0000_0000 => Ok(DataType::TEXT(size)),
             0b0000_0001 => Ok(DataType::INTEGER),
             0b0000_0010 => Ok(DataType::FLOAT),
             0b0000_0011 => Ok(DataType::BOOLEAN),
+            0b0000_0100 => Ok(DataType::BLOB(size)),
             _ => Err(format!("Unknown bit code {}", bit_code)),
         }
     }
 
+    /*
+        Type name as accepted by CREATE TABLE
+     */
+    pub fn to_sql(self) -> String {
+        match self {
+            DataType::TEXT(size) => format!("TEXT({})", size),
+            DataType::INTEGER => String::from("INT"),
+            DataType::FLOAT => String::from("FLOAT"),
+            DataType::BOOLEAN => String::from("BOOL"),
+            DataType::BLOB(size) => format!("BLOB({})", size),
+        }
+    }
+
+    /*
+        Type name without its size
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataType::TEXT(_) => "TEXT",
+            DataType::INTEGER => "INT",
+            DataType::FLOAT => "FLOAT",
+            DataType::BOOLEAN => "BOOL",
+            DataType::BLOB(_) => "BLOB",
+        }
+    }
+
+    /*
+        Numbers are compared with numbers, TEXT and BLOB whatever their declared size
+     */
+    pub fn is_comparable_with(&self, other: &DataType) -> bool {
+        matches!(
+            (self, other),
+            (DataType::INTEGER | DataType::FLOAT, DataType::INTEGER | DataType::FLOAT)
+                | (DataType::TEXT(_), DataType::TEXT(_))
+                | (DataType::BLOB(_), DataType::BLOB(_))
+                | (DataType::BOOLEAN, DataType::BOOLEAN)
+        )
+    }
+
+    /*
+        `value` as stored in a column of this type, an INT is widened into a FLOAT column
+        and 0 or 1 into a BOOLEAN column reads as false or true.
+        With `coerce`, a TEXT is parsed into a number or a boolean column.
+        None when the value does not fit the type.
+     */
+    pub fn convert(&self, value: &Value, coerce: bool) -> Option<Value> {
+        match (self, value) {
+            (DataType::INTEGER, Value::INT(_))
+            | (DataType::FLOAT, Value::FLOAT(_))
+            | (DataType::BOOLEAN, Value::BOOL(_))
+            | (DataType::TEXT(_), Value::TEXT(_))
+            | (DataType::BLOB(_), Value::BLOB(_)) => Some(value.clone()),
+            (DataType::FLOAT, Value::INT(i)) => Some(Value::FLOAT(*i as f32)),
+            (DataType::BOOLEAN, Value::INT(i @ (0 | 1))) => Some(Value::BOOL(*i == 1)),
+            (DataType::INTEGER, Value::TEXT(s)) if coerce => s.trim().parse::<i32>().ok().map(Value::INT),
+            (DataType::FLOAT, Value::TEXT(s)) if coerce => {
+                s.trim().parse::<f32>().ok().filter(|f| f.is_finite()).map(Value::FLOAT)
+            }
+            (DataType::BOOLEAN, Value::TEXT(s)) if coerce => match s.trim().to_lowercase().as_str() {
+                "true" | "1" => Some(Value::BOOL(true)),
+                "false" | "0" => Some(Value::BOOL(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn get_size(&self) -> usize {
         match self {
             DataType::TEXT(size) => *size,
             DataType::INTEGER => INTEGER_SIZE,
             DataType::FLOAT => FLOAT_SIZE,
             DataType::BOOLEAN => BOOLEAN_SIZE,
+            DataType::BLOB(size) => BLOB_LENGTH_SIZE + *size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::build_path;
+    use crate::sql_engine::sql_parser::SqlParser;
+    use crate::sql_engine::sql_structs::{DataType, SqlStmt, Value};
+    use crate::storage_engine::common::SelectRows;
+    use crate::storage_engine::config::DATA_FOLDER;
+    use crate::utils::test_utils::TestDb;
+
+    #[test]
+    fn covering_index_scan_returns_only_the_keys() {
+        let mut db = TestDb::open(&["structs_covering"]);
+        db.run("create table structs_covering (id int primary key, name text(8));").unwrap();
+        for id in [3, 1, 7, 5] {
+            db.run(&format!("insert into structs_covering (id, name) values ({}, 'n{}');", id, id)).unwrap();
+        }
+        let result = db.run("select id from structs_covering where id > 2;").unwrap().unwrap();
+        assert_eq!(result.fields, vec!["id"]);
+        assert_eq!(
+            db.query("select id from structs_covering where id > 2;"),
+            vec![vec![Value::INT(3)], vec![Value::INT(5)], vec![Value::INT(7)]]
+        );
+    }
+
+    fn ids(db: &mut TestDb, sql: &str) -> Vec<i32> {
+        db.query(sql)
+          .iter()
+          .map(|row| match row[0] {
+              Value::INT(id) => id,
+              _ => panic!("`{}` is not an id", row[0].to_string()),
+          })
+          .collect()
+    }
+
+    #[test]
+    fn nocase_field_orders_and_compares_without_case() {
+        let mut db = TestDb::open(&["structs_collate", "structs_collate_copy"]);
+        db.run("create table structs_collate (id int primary key, exact text(8), folded text(8) collate nocase);").unwrap();
+        for (id, name) in [(1, "b"), (2, "A"), (3, "a"), (4, "B"), (5, "c")] {
+            db.run(&format!("insert into structs_collate (id, exact, folded) values ({}, '{}', '{}');", id, name, name)).unwrap();
         }
+        // the collation is read back from the table metadata
+        db.reopen();
+
+        // BINARY puts every uppercase letter before the lowercase ones
+        assert_eq!(ids(&mut db, "select id from structs_collate order by exact;"), vec![2, 4, 3, 1, 5]);
+        // equal NOCASE values are ordered by the ascending primary key in both directions
+        assert_eq!(ids(&mut db, "select id from structs_collate order by folded;"), vec![2, 3, 1, 4, 5]);
+        assert_eq!(ids(&mut db, "select id from structs_collate order by folded desc;"), vec![5, 1, 4, 2, 3]);
+        assert_eq!(
+            db.query("select folded from structs_collate where id = 4;"),
+            vec![vec![Value::TEXT(String::from("B"))]]
+        );
+
+        assert_eq!(ids(&mut db, "select id from structs_collate where exact = 'b';"), vec![1]);
+        assert_eq!(ids(&mut db, "select id from structs_collate where folded = 'b';"), vec![1, 4]);
+        assert_eq!(ids(&mut db, "select id from structs_collate where folded < 'B';"), vec![2, 3]);
+        assert_eq!(ids(&mut db, "select id from structs_collate where folded in ['C', 'z'];"), vec![5]);
+
+        assert_eq!(
+            db.table_manager.create_sql("structs_collate").unwrap(),
+            "CREATE TABLE structs_collate (id INT PRIMARY KEY, exact TEXT(8), folded TEXT(8) COLLATE NOCASE);"
+        );
+
+        // a table created from a select keeps the collation of the copied fields
+        db.run("create table structs_collate_copy as select id, folded from structs_collate;").unwrap();
+        assert_eq!(ids(&mut db, "select id from structs_collate_copy where folded = 'b';"), vec![1, 4]);
+        assert_eq!(ids(&mut db, "select id from structs_collate_copy order by folded desc;"), vec![5, 1, 4, 2, 3]);
+        assert_eq!(
+            db.table_manager.create_sql("structs_collate_copy").unwrap(),
+            "CREATE TABLE structs_collate_copy (id INT PRIMARY KEY, folded TEXT(8) COLLATE NOCASE);"
+        );
+    }
+
+    #[test]
+    fn renamed_table_and_columns_are_found_after_reopen() {
+        let mut db = TestDb::open(&["structs_rename_old", "structs_rename_new"]);
+        db.run("create table structs_rename_old (id int primary key, name text(8));").unwrap();
+        for id in 1..=3 {
+            db.run(&format!("insert into structs_rename_old (id, name) values ({}, 'n{}');", id, id)).unwrap();
+        }
+        db.run("alter table structs_rename_old rename to structs_rename_new;").unwrap();
+        // the primary key is also the key field of the index metadata
+        db.run("alter table structs_rename_new rename column id to code;").unwrap();
+        db.run("alter table structs_rename_new rename column name to label;").unwrap();
+        db.reopen();
+
+        assert_eq!(
+            db.query("select code, label from structs_rename_new where code = 2;"),
+            vec![vec![Value::INT(2), Value::TEXT(String::from("n2"))]]
+        );
+        assert_eq!(ids(&mut db, "select code from structs_rename_new where label > 'n1';"), vec![2, 3]);
+        assert!(db.run("select id from structs_rename_new;").is_err());
+        assert!(db.run("select code from structs_rename_old;").is_err());
+
+        db.run("insert into structs_rename_new (code, label) values (4, 'n4');").unwrap();
+        assert_eq!(ids(&mut db, "select code from structs_rename_new;"), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn nan_is_the_greatest_float() {
+        let mut db = TestDb::open(&["structs_nan"]);
+        db.run("create table structs_nan (id int primary key, x float);").unwrap();
+        for (id, x) in [(1, "2.5"), (2, "0.0"), (3, "-1.0"), (4, "0.0")] {
+            db.run(&format!("insert into structs_nan (id, x) values ({}, {});", id, x)).unwrap();
+        }
+
+        // 0.0 / 0.0 is NaN, equal NaN values keep the order of their primary key
+        assert_eq!(ids(&mut db, "select id from structs_nan order by x / 0.0;"), vec![3, 1, 2, 4]);
+        assert_eq!(ids(&mut db, "select id from structs_nan order by x / 0.0 desc;"), vec![2, 4, 1, 3]);
+        assert_eq!(ids(&mut db, "select id from structs_nan where x / 0.0 > 1.0;"), vec![1, 2, 4]);
+
+        let mut values = [Value::FLOAT(f32::NAN), Value::FLOAT(1.0), Value::FLOAT(f32::NAN), Value::FLOAT(-1.0)];
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(values[..2], [Value::FLOAT(-1.0), Value::FLOAT(1.0)]);
+        assert!(values[2..].iter().all(|v| matches!(v, Value::FLOAT(f) if f.is_nan())));
+        assert_eq!(Value::FLOAT(f32::NAN), Value::FLOAT(f32::NAN));
+    }
+
+    #[test]
+    fn sql_literals_are_parsed_back_as_the_same_values() {
+        let values = [
+            Value::TEXT(String::from("it's")),
+            Value::TEXT(String::new()),
+            Value::INT(-3),
+            Value::FLOAT(2.0),
+            Value::BOOL(true),
+            Value::ARRAY(vec![Value::INT(1), Value::INT(2)]),
+        ];
+        let literals: Vec<String> = values.iter().map(|v| v.to_sql_literal()).collect();
+        assert_eq!(literals, ["'it''s'", "''", "-3", "2.0", "true", "[1,2]"]);
+
+        let mut db = TestDb::open(&[]);
+        for value in values.iter().filter(|v| !matches!(v, Value::ARRAY(_))) {
+            assert_eq!(db.query(&format!("select {};", value.to_sql_literal())), vec![vec![value.clone()]]);
+        }
+    }
+
+    #[test]
+    fn empty_array_is_formatted_as_brackets() {
+        assert_eq!(Value::ARRAY(vec![]).to_string(), "[]");
+        assert_eq!(Value::ARRAY(vec![Value::INT(1)]).to_string(), "[1]");
+        assert_eq!(Value::ARRAY(vec![Value::INT(1), Value::INT(2)]).to_string(), "[1,2]");
+    }
+
+    #[test]
+    fn unknown_where_field_is_an_error() {
+        let mut db = TestDb::open(&["structs_where_btree", "structs_where_seq"]);
+        db.run("create table structs_where_btree (id int primary key, name text(8));").unwrap();
+        db.run("create table structs_where_seq (id int, name text(8));").unwrap();
+        for table in ["structs_where_btree", "structs_where_seq"] {
+            db.run(&format!("insert into {} (id, name) values (1, 'a');", table)).unwrap();
+            for condition in ["bogus = 1", "id = 1 and bogus > 2", "name = 'a' or bogus in [1]"] {
+                assert_eq!(
+                    db.run(&format!("select * from {} where {};", table, condition)).err().unwrap(),
+                    format!("Field `bogus` does not found in the table `{}`!", table)
+                );
+            }
+        }
+    }
+
+    fn create_typed(db: &mut TestDb, table: &str) {
+        db.run(&format!("create table {} (id int primary key, qty int, price float, name text(8), ok bool);", table)).unwrap();
+        db.run(&format!("insert into {} (id, qty, price, name, ok) values (1, 2, 2.0, 'a', true);", table)).unwrap();
+        db.run(&format!("insert into {} (id, qty, price, name, ok) values (2, 3, 1.5, 'b', false);", table)).unwrap();
+    }
+
+    #[test]
+    fn columns_of_different_types_are_not_compared() {
+        let mut db = TestDb::open(&["structs_column_types"]);
+        create_typed(&mut db, "structs_column_types");
+        assert_eq!(
+            db.run("select * from structs_column_types where qty = name;").err().unwrap(),
+            "`qty` is of type INT, it can not be compared to `name` of type TEXT."
+        );
+        assert_eq!(
+            db.run("select * from structs_column_types where qty * 2 > ok;").err().unwrap(),
+            "`qty * 2` is of type INT, it can not be compared to `ok` of type BOOL."
+        );
+        // INT and FLOAT columns are compared as numbers
+        assert_eq!(db.query("select id from structs_column_types where qty = price;"), vec![vec![Value::INT(1)]]);
+
+        assert_ne!(Value::INT(1), Value::TEXT(String::from("1")));
+        assert_eq!(Value::TEXT(String::from("a")).partial_cmp(&Value::BOOL(true)), None);
+    }
+
+    #[test]
+    fn literals_are_checked_against_the_type_of_any_field() {
+        let mut db = TestDb::open(&["structs_literal_types"]);
+        create_typed(&mut db, "structs_literal_types");
+        for (condition, error) in [
+            ("name = 1", "`name` is of type TEXT, it can not be compared to `1` of type INT."),
+            ("qty > 'x'", "`qty` is of type INT, it can not be compared to `'x'` of type TEXT."),
+            ("price <= true", "`price` is of type FLOAT, it can not be compared to `true` of type BOOL."),
+            ("id = 1 and ok = 'yes'", "`ok` is of type BOOL, it can not be compared to `'yes'` of type TEXT."),
+            ("upper(name) = 2", "`upper(name)` is of type TEXT, it can not be compared to `2` of type INT."),
+        ] {
+            assert_eq!(
+                db.run(&format!("select * from structs_literal_types where {};", condition)).err().unwrap(),
+                error
+            );
+        }
+        assert_eq!(db.query("select id from structs_literal_types where price > 1;"), vec![vec![Value::INT(1)], vec![Value::INT(2)]]);
+    }
+
+    #[test]
+    fn in_list_values_are_checked_against_the_field() {
+        let mut db = TestDb::open(&["structs_in_types"]);
+        create_typed(&mut db, "structs_in_types");
+        for (condition, error) in [
+            ("qty in ['x']", "`qty` is of type INT, it can not be compared to `'x'` of type TEXT."),
+            ("name in [1, 2]", "`name` is of type TEXT, it can not be compared to `1` of type INT."),
+            ("name not in [x'01']", "`name` is of type TEXT, it can not be compared to `x'01'` of type BLOB."),
+            ("id in ['a', 'b']", "`id` is of type INT, it can not be compared to `'a'` of type TEXT."),
+        ] {
+            assert_eq!(
+                db.run(&format!("select * from structs_in_types where {};", condition)).err().unwrap(),
+                error
+            );
+        }
+        // a list of INT values is compared to a FLOAT field as numbers, and the other way round
+        assert_eq!(db.query("select id from structs_in_types where price in [2, 7];"), vec![vec![Value::INT(1)]]);
+        assert_eq!(db.query("select id from structs_in_types where qty in [3.0];"), vec![vec![Value::INT(2)]]);
+    }
+
+    #[test]
+    fn zero_and_one_compare_as_booleans() {
+        let mut db = TestDb::open(&["structs_bool_literals"]);
+        create_typed(&mut db, "structs_bool_literals");
+        assert_eq!(db.query("select id from structs_bool_literals where ok = 1;"), vec![vec![Value::INT(1)]]);
+        assert_eq!(db.query("select id from structs_bool_literals where ok != 1;"), vec![vec![Value::INT(2)]]);
+        assert_eq!(db.query("select id from structs_bool_literals where 0 = ok;"), vec![vec![Value::INT(2)]]);
+        assert_eq!(db.query("select id from structs_bool_literals where ok in [0, 1];").len(), 2);
+        assert_eq!(
+            db.run("select id from structs_bool_literals where ok = 2;").err().unwrap(),
+            "`ok` is of type BOOL, it can not be compared to `2` of type INT."
+        );
+    }
+
+    #[test]
+    fn failed_create_as_select_leaves_no_table() {
+        let mut db = TestDb::open(&["structs_ctas_source", "structs_ctas_copy"]);
+        // the source packs its leaves, the copy splits them at the default fill factor and runs out of pages
+        db.run("create table structs_ctas_source (id int primary key, name text(400)) with (fillfactor = 90);").unwrap();
+        let mut id = 0;
+        while db.run(&format!("insert into structs_ctas_source (id, name) values ({}, 'r{}');", id, id)).is_ok() {
+            id += 1;
+        }
+
+        assert_eq!(
+            db.run("create table structs_ctas_copy as select id, name from structs_ctas_source;").err().unwrap(),
+            "Table `structs_ctas_copy` is full, a table can not use more than 100 pages."
+        );
+        assert!(!Path::new(&build_path!(DATA_FOLDER, "structs_ctas_copy")).exists());
+        assert!(db.run("select * from structs_ctas_copy;").is_err());
+
+        // the name is free again
+        db.run("create table structs_ctas_copy as select id from structs_ctas_source where id < 3;").unwrap();
+        assert_eq!(db.query("select count(*) from structs_ctas_copy;"), vec![vec![Value::INT(3)]]);
+    }
+
+    #[test]
+    fn arithmetic_promotes_integers_to_float() {
+        let mut db = TestDb::open(&["structs_arithmetic"]);
+        db.run("create table structs_arithmetic (id int primary key, a int, b float);").unwrap();
+        db.run("insert into structs_arithmetic (id, a, b) values (1, 7, 0.5);").unwrap();
+        db.run("insert into structs_arithmetic (id, a, b) values (2, -3, 2.0);").unwrap();
+
+        assert_eq!(
+            db.query("select a + b, a * 2, b * 2, a / 2, a / 2.0, a / b from structs_arithmetic;"),
+            vec![
+                vec![Value::FLOAT(7.5), Value::INT(14), Value::FLOAT(1.0), Value::INT(3), Value::FLOAT(3.5), Value::FLOAT(14.0)],
+                vec![Value::FLOAT(-1.0), Value::INT(-6), Value::FLOAT(4.0), Value::INT(-1), Value::FLOAT(-1.5), Value::FLOAT(-1.5)],
+            ]
+        );
+        // an INT beyond the precision of a FLOAT keeps its value until the result is rounded
+        assert_eq!(db.query("select 16777217 - 16777216.0;"), vec![vec![Value::FLOAT(1.0)]]);
+        assert_eq!(db.query("select 1 / 0.0;"), vec![vec![Value::FLOAT(f32::INFINITY)]]);
+    }
+
+    #[test]
+    fn numeric_functions_keep_the_type_of_their_argument() {
+        let mut db = TestDb::open(&[]);
+        assert_eq!(
+            db.query("select abs(-3), abs(4), round(-7), ceil(5), floor(-5);"),
+            vec![vec![Value::INT(3), Value::INT(4), Value::INT(-7), Value::INT(5), Value::INT(-5)]]
+        );
+        assert_eq!(
+            db.query("select abs(-2.5), round(-2.5), round(2.345, 2), ceil(-1.5), floor(-1.5), ceil(1.2), floor(1.8);"),
+            vec![vec![
+                Value::FLOAT(2.5),
+                Value::FLOAT(-3.0),
+                Value::FLOAT(2.35),
+                Value::FLOAT(-1.0),
+                Value::FLOAT(-2.0),
+                Value::FLOAT(2.0),
+                Value::FLOAT(1.0),
+            ]]
+        );
+        // more decimals than a FLOAT holds leave the value as is
+        assert_eq!(db.query("select round(1.5, 400), round(-0.25, 60);"), vec![vec![Value::FLOAT(1.5), Value::FLOAT(-0.25)]]);
+        assert_eq!(db.run("select round(1.5, -1);").err().unwrap(), "Function `round` expects a number of decimals >= 0.");
+        assert_eq!(db.run("select abs('a');").err().unwrap(), "Function `abs` can not be applied to `a`.");
+    }
+
+    #[test]
+    fn equal_sort_keys_are_ordered_by_the_primary_key() {
+        let mut db = TestDb::open(&["structs_ties", "structs_ties_seq"]);
+        db.run("create table structs_ties (id int primary key, grp int);").unwrap();
+        db.run("create table structs_ties_seq (id int, grp int);").unwrap();
+        // ids are inserted out of order, every group holds many rows
+        let inserted: Vec<i32> = (0..60).map(|i| (i * 37) % 60).collect();
+        for id in inserted.iter() {
+            for table in ["structs_ties", "structs_ties_seq"] {
+                db.run(&format!("insert into {} (id, grp) values ({}, {});", table, id, id % 3)).unwrap();
+            }
+        }
+
+        let expected: Vec<i32> = (0..3).flat_map(|grp| (0..60).filter(move |id| id % 3 == grp)).collect();
+        assert_eq!(ids(&mut db, "select id from structs_ties order by grp;"), expected);
+        // the same order whatever the scan and the WHERE clause
+        assert_eq!(ids(&mut db, "select id from structs_ties where grp < 5 or id > 100 order by grp;"), expected);
+        assert_eq!(ids(&mut db, "select id from structs_ties where id >= 0 order by grp;"), expected);
+        let descending: Vec<i32> = (0..3).rev().flat_map(|grp| (0..60).filter(move |id| id % 3 == grp)).collect();
+        assert_eq!(ids(&mut db, "select id from structs_ties order by grp desc;"), descending);
+
+        // without a primary key equal keys keep the insertion order
+        let in_insertion_order: Vec<i32> = (0..3).flat_map(|grp| inserted.iter().copied().filter(move |id| id % 3 == grp)).collect();
+        assert_eq!(ids(&mut db, "select id from structs_ties_seq order by grp;"), in_insertion_order);
+    }
+
+    #[test]
+    fn where_compares_an_arithmetic_expression() {
+        let mut db = TestDb::open(&["structs_computed", "structs_computed_seq"]);
+        db.run("create table structs_computed (id int primary key, price int, qty int);").unwrap();
+        db.run("create table structs_computed_seq (id int, price int, qty int);").unwrap();
+        for (id, price, qty) in [(1, 1, 5), (2, 2, 0), (3, 3, 1), (4, 4, 2)] {
+            for table in ["structs_computed", "structs_computed_seq"] {
+                db.run(&format!("insert into {} (id, price, qty) values ({}, {}, {});", table, id, price, qty)).unwrap();
+            }
+        }
+
+        for table in ["structs_computed", "structs_computed_seq"] {
+            assert_eq!(ids(&mut db, &format!("select id from {} where price * qty > 2;", table)), vec![1, 3, 4]);
+            assert_eq!(ids(&mut db, &format!("select id from {} where price - qty = 2;", table)), vec![2, 3, 4]);
+            // a computed condition next to a condition on the key
+            assert_eq!(ids(&mut db, &format!("select id from {} where id > 1 and price + qty < 5;", table)), vec![2, 3]);
+        }
+        // a division by zero matches no row
+        assert_eq!(ids(&mut db, "select id from structs_computed where price / qty >= 0;"), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn blob_bytes_are_read_back_unchanged() {
+        let mut db = TestDb::open(&["structs_blobs"]);
+        db.run("create table structs_blobs (id int primary key, b blob(4));").unwrap();
+        db.run("insert into structs_blobs (id, b) values (1, x'00ff0000');").unwrap();
+        db.run("insert into structs_blobs (id, b) values (2, x'00');").unwrap();
+        db.run("insert into structs_blobs (id, b) values (3, x'');").unwrap();
+        db.reopen();
+
+        // zero bytes are part of the value, a shorter blob keeps its length
+        assert_eq!(
+            db.query("select b from structs_blobs;"),
+            vec![
+                vec![Value::BLOB(vec![0x00, 0xff, 0x00, 0x00])],
+                vec![Value::BLOB(vec![0x00])],
+                vec![Value::BLOB(vec![])],
+            ]
+        );
+        assert_eq!(ids(&mut db, "select id from structs_blobs where b = x'00';"), vec![2]);
+        assert!(db.run("insert into structs_blobs (id, b) values (4, x'0102030405');").is_err());
+    }
+
+    #[test]
+    fn insert_without_fields_follows_the_declaration_order() {
+        let mut db = TestDb::open(&["structs_star"]);
+        db.run("create table structs_star (name text(8), id int primary key, qty int);").unwrap();
+        db.run("insert into structs_star values ('b', 2, 20);").unwrap();
+        db.run("insert into structs_star values ('a', 1, 10);").unwrap();
+
+        assert_eq!(db.run("select * from structs_star;").unwrap().unwrap().fields, vec!["name", "id", "qty"]);
+        assert_eq!(
+            db.query("select * from structs_star;"),
+            vec![
+                vec![Value::TEXT(String::from("a")), Value::INT(1), Value::INT(10)],
+                vec![Value::TEXT(String::from("b")), Value::INT(2), Value::INT(20)],
+            ]
+        );
+        assert_eq!(
+            db.run("insert into structs_star values ('c', 3);").err().unwrap(),
+            "Table `structs_star` has 3 columns but 2 values were supplied."
+        );
+    }
+
+    #[test]
+    fn validate_checks_a_create_without_writing_it() {
+        let mut db = TestDb::open(&["structs_dry_run"]);
+        let parse = |sql: &str| match SqlParser::parse_sql(sql.to_string()).unwrap() {
+            SqlStmt::CREATE(create) => create,
+            _ => panic!("`{}` is not a create", sql),
+        };
+
+        parse("create table structs_dry_run (id int primary key, name text(8));").validate(&db.table_manager).unwrap();
+        assert!(!Path::new(&build_path!(DATA_FOLDER, "structs_dry_run")).exists());
+        assert!(parse("create table structs_dry_run (id int primary key, b blob(5000));")
+            .validate(&db.table_manager)
+            .err()
+            .unwrap()
+            .contains("exceeds"));
+
+        db.run("create table structs_dry_run (id int primary key);").unwrap();
+        assert_eq!(
+            parse("create table structs_dry_run (id int primary key);").validate(&db.table_manager).err().unwrap(),
+            "Table structs_dry_run already exists."
+        );
+    }
+
+    #[test]
+    fn row_larger_than_a_page_is_rejected() {
+        let mut db = TestDb::open(&["structs_wide", "structs_wide_seq"]);
+        for sql in [
+            "create table structs_wide (id int primary key, b blob(4090));",
+            "create table structs_wide_seq (id int, b blob(4090));",
+        ] {
+            let error = db.run(sql).err().unwrap();
+            assert!(error.starts_with("Row size of 4096 bytes") && error.contains("available for cells in a page"), "{}", error);
+        }
+        assert!(!Path::new(&build_path!(DATA_FOLDER, "structs_wide")).exists());
+        assert!(!Path::new(&build_path!(DATA_FOLDER, "structs_wide_seq")).exists());
+
+        // a single row by page still fits
+        db.run("create table structs_wide (id int primary key, b blob(2000));").unwrap();
+        db.run("insert into structs_wide (id, b) values (1, x'01');").unwrap();
+        db.run("insert into structs_wide (id, b) values (2, x'02');").unwrap();
+        db.run("insert into structs_wide (id, b) values (3, x'03');").unwrap();
+        assert_eq!(ids(&mut db, "select id from structs_wide;"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn and_keeps_the_rows_matched_by_both_sides() {
+        let mut db = TestDb::open(&["structs_and"]);
+        db.run("create table structs_and (id int primary key, name text(8), tag text(4));").unwrap();
+        for (id, name, tag) in [(1, "a", "x"), (2, "b", "x"), (3, "b", "y"), (4, "bb", "x"), (5, "b", "xy")] {
+            db.run(&format!("insert into structs_and (id, name, tag) values ({}, '{}', '{}');", id, name, tag)).unwrap();
+        }
+
+        assert_eq!(ids(&mut db, "select id from structs_and where id > 1 and name = 'b';"), vec![2, 3, 5]);
+        assert_eq!(ids(&mut db, "select id from structs_and where name = 'b' and tag = 'x';"), vec![2]);
+        assert_eq!(ids(&mut db, "select id from structs_and where (id = 1 or name = 'b') and tag = 'x';"), vec![1, 2]);
+        assert_eq!(ids(&mut db, "select id from structs_and where (id < 3 or tag = 'x') and (id > 1 or name = 'a');"), vec![1, 2, 4]);
+
+        // a value longer than its field would spill into the next one
+        assert_eq!(
+            db.run("insert into structs_and (id, name, tag) values (6, 'b', 'xyzzy');").err().unwrap(),
+            "Value of field `tag` is longer than 4 bytes."
+        );
+    }
+
+    #[test]
+    fn select_without_from_evaluates_once() {
+        let mut db = TestDb::open(&[]);
+        assert_eq!(db.query("select 2 * 3;"), vec![vec![Value::INT(6)]]);
+        assert_eq!(
+            db.query("select 1 + 2 * 3, (1 + 2) * 3, 'a', 7 / 2;"),
+            vec![vec![Value::INT(7), Value::INT(9), Value::TEXT(String::from("a")), Value::INT(3)]]
+        );
+        assert!(db.run("select id;").is_err());
+    }
+
+    #[test]
+    fn text_functions_apply_to_a_text_field() {
+        let mut db = TestDb::open(&["structs_text_functions"]);
+        db.run("create table structs_text_functions (id int primary key, name text(16));").unwrap();
+        for (id, name) in [(1, "Ada"), (2, "grace HOPPER"), (3, "")] {
+            db.run(&format!("insert into structs_text_functions (id, name) values ({}, '{}');", id, name)).unwrap();
+        }
+        let text = |s: &str| Value::TEXT(String::from(s));
+
+        assert_eq!(
+            db.query("select lower(name), upper(name), length(name), substr(name, 2, 3) from structs_text_functions;"),
+            vec![
+                vec![text("ada"), text("ADA"), Value::INT(3), text("da")],
+                vec![text("grace hopper"), text("GRACE HOPPER"), Value::INT(12), text("rac")],
+                vec![text(""), text(""), Value::INT(0), text("")],
+            ]
+        );
+        assert_eq!(ids(&mut db, "select id from structs_text_functions where lower(name) = 'ada';"), vec![1]);
+        assert_eq!(ids(&mut db, "select id from structs_text_functions where length(name) > 3;"), vec![2]);
+        assert_eq!(ids(&mut db, "select id from structs_text_functions where substr(upper(name), 1, 5) = 'GRACE';"), vec![2]);
+
+        assert!(db.run("select substr(name, 0, 1) from structs_text_functions;").is_err());
+        assert!(db.run("select length(id) from structs_text_functions;").is_err());
+    }
+
+    #[test]
+    fn insert_fields_can_be_listed_in_any_order() {
+        let mut db = TestDb::open(&["structs_field_order"]);
+        db.run("create table structs_field_order (id int primary key, name text(8), qty int);").unwrap();
+        db.run("insert into structs_field_order (qty, name, id) values (10, 'a', 1);").unwrap();
+        db.run("insert into structs_field_order (name, id, qty) values ('b', 2, 20);").unwrap();
+        assert_eq!(
+            db.query("select id, name, qty from structs_field_order;"),
+            vec![
+                vec![Value::INT(1), Value::TEXT(String::from("a")), Value::INT(10)],
+                vec![Value::INT(2), Value::TEXT(String::from("b")), Value::INT(20)],
+            ]
+        );
+
+        assert_eq!(
+            db.run("insert into structs_field_order (id, name) values (3, 'c');").err().unwrap(),
+            "Field `qty` of table `structs_field_order` is required but no value was supplied."
+        );
+        assert_eq!(
+            db.run("insert into structs_field_order (id, qty, id) values (3, 30, 4);").err().unwrap(),
+            "Field `id` is listed more than once, at position 3."
+        );
+        assert_eq!(ids(&mut db, "select id from structs_field_order;"), vec![1, 2]);
+    }
+
+    #[test]
+    fn create_as_select_copies_the_selected_rows_and_fields() {
+        let mut db = TestDb::open(&["structs_ctas_source", "structs_ctas_keyed", "structs_ctas_seq"]);
+        db.run("create table structs_ctas_source (id int primary key, name text(8), price float);").unwrap();
+        for (id, name, price) in [(1, "a", "1.5"), (2, "b", "2.5"), (3, "c", "3.5")] {
+            db.run(&format!("insert into structs_ctas_source (id, name, price) values ({}, '{}', {});", id, name, price)).unwrap();
+        }
+
+        db.run("create table structs_ctas_keyed as select id, name from structs_ctas_source where price > 2.0;").unwrap();
+        db.run("create table structs_ctas_seq as select price, name from structs_ctas_source where id != 2;").unwrap();
+        db.reopen();
+
+        assert_eq!(
+            db.query("select * from structs_ctas_keyed;"),
+            vec![
+                vec![Value::INT(2), Value::TEXT(String::from("b"))],
+                vec![Value::INT(3), Value::TEXT(String::from("c"))],
+            ]
+        );
+        assert_eq!(
+            db.query("select * from structs_ctas_seq;"),
+            vec![
+                vec![Value::FLOAT(1.5), Value::TEXT(String::from("a"))],
+                vec![Value::FLOAT(3.5), Value::TEXT(String::from("c"))],
+            ]
+        );
+
+        // the fields keep their type and size, the key only when it is selected
+        let schema = |db: &mut TestDb, table: &str| -> Vec<(String, DataType, bool)> {
+            db.table_manager.get_table_metadata(table).unwrap().fields.iter()
+              .map(|f| (f.data_def.field_name.to_string(), f.data_def.data_type, f.data_def.is_primary_key))
+              .collect()
+        };
+        assert_eq!(
+            schema(&mut db, "structs_ctas_keyed"),
+            vec![(String::from("id"), DataType::INTEGER, true), (String::from("name"), DataType::TEXT(8), false)]
+        );
+        assert_eq!(
+            schema(&mut db, "structs_ctas_seq"),
+            vec![(String::from("price"), DataType::FLOAT, false), (String::from("name"), DataType::TEXT(8), false)]
+        );
+        assert!(db.table_manager.get_tables("structs_ctas_keyed").unwrap()[0].is_btree());
+        assert!(!db.table_manager.get_tables("structs_ctas_seq").unwrap()[0].is_btree());
+
+        assert!(db.run("create table structs_ctas_keyed as select id from structs_ctas_source;").is_err());
+    }
+
+    #[test]
+    fn order_by_the_key_reads_the_rows_in_key_order() {
+        let mut db = TestDb::open(&["structs_key_order"]);
+        db.run("create table structs_key_order (id int primary key, name text(8));").unwrap();
+        for id in (0..100).map(|i| i * 37 % 100) {
+            db.run(&format!("insert into structs_key_order (id, name) values ({}, 'n{:03}');", id, id)).unwrap();
+        }
+
+        // the rows come from the leaves in key order, nothing is decoded to be sorted
+        let by_key = db.run("select id, name from structs_key_order where id >= 10 order by id;").unwrap().unwrap();
+        assert!(matches!(by_key.rows, SelectRows::LAZY(_, _)));
+        let sorted = db.run("select id, name from structs_key_order where id >= 10 order by name;").unwrap().unwrap();
+        assert!(matches!(sorted.rows, SelectRows::DECODED(_)));
+        assert_eq!(by_key.len(), 90);
+        for row in 0..by_key.len() {
+            assert_eq!(by_key.get(row, 0), sorted.get(row, 0));
+            assert_eq!(by_key.get(row, 1), sorted.get(row, 1));
+        }
+
+        assert_eq!(ids(&mut db, "select id from structs_key_order order by id;"), (0..100).collect::<Vec<_>>());
+        assert_eq!(ids(&mut db, "select id from structs_key_order order by id desc;"), (0..100).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pragma_sets_and_reads_a_setting() {
+        let mut db = TestDb::open(&[]);
+        let pragma = |db: &mut TestDb, sql: &str| -> Result<Value, String> {
+            match SqlParser::parse_sql(sql.to_string())? {
+                SqlStmt::PRAGMA(pragma) => pragma.execute(&mut db.table_manager),
+                _ => panic!("`{}` is not a pragma", sql),
+            }
+        };
+
+        assert_eq!(pragma(&mut db, "pragma max_rows;"), Ok(Value::INT(0)));
+        assert_eq!(pragma(&mut db, "pragma max_rows = 5;"), Ok(Value::INT(5)));
+        assert_eq!(pragma(&mut db, "pragma max_rows;"), Ok(Value::INT(5)));
+        assert_eq!(db.table_manager.settings().max_rows, 5);
+        assert_eq!(pragma(&mut db, "pragma timer = true;"), Ok(Value::BOOL(true)));
+        assert!(db.table_manager.settings().timer);
+
+        assert_eq!(pragma(&mut db, "pragma max_rows = 'a';"), Err(String::from("Invalid value 'a' for pragma `max_rows`.")));
+        assert_eq!(pragma(&mut db, "pragma nothing;"), Err(String::from("Unknown pragma `nothing`.")));
+        assert!(pragma(&mut db, "pragma page_size = 8192;").is_err());
+        assert_eq!(pragma(&mut db, "pragma page_size;"), Ok(Value::INT(4096)));
+    }
+
+    #[test]
+    fn order_by_an_expression_over_the_fields() {
+        let mut db = TestDb::open(&["structs_order_expr"]);
+        db.run("create table structs_order_expr (id int primary key, price int, qty int, name text(8));").unwrap();
+        for (id, price, qty, name) in [(1, 5, 1, "b"), (2, 1, 3, "C"), (3, 2, 4, "a"), (4, 3, 2, "D")] {
+            db.run(&format!("insert into structs_order_expr (id, price, qty, name) values ({}, {}, {}, '{}');", id, price, qty, name)).unwrap();
+        }
+
+        assert_eq!(ids(&mut db, "select id from structs_order_expr order by price * qty desc;"), vec![3, 4, 1, 2]);
+        assert_eq!(ids(&mut db, "select id from structs_order_expr order by price - qty;"), vec![2, 3, 4, 1]);
+        assert_eq!(ids(&mut db, "select id from structs_order_expr order by 0 - id;"), vec![4, 3, 2, 1]);
+        assert_eq!(ids(&mut db, "select id from structs_order_expr order by upper(name);"), vec![3, 1, 2, 4]);
+        // equal expressions fall back to the next one
+        assert_eq!(ids(&mut db, "select id from structs_order_expr order by price / 2, qty;"), vec![2, 4, 3, 1]);
+        assert!(db.run("select id from structs_order_expr order by price * missing;").is_err());
+    }
+
+    #[test]
+    fn create_if_not_exists_keeps_the_existing_table() {
+        let mut db = TestDb::open(&["structs_if_not_exists"]);
+        let create = "create table if not exists structs_if_not_exists (id int primary key, name text(8));";
+        let execute = |db: &mut TestDb| match SqlParser::parse_sql(create.to_string()).unwrap() {
+            SqlStmt::CREATE(mut create) => create.execute(&mut db.table_manager),
+            _ => panic!("`{}` is not a create", create),
+        };
+
+        assert_eq!(execute(&mut db), Ok(true));
+        db.run("insert into structs_if_not_exists (id, name) values (1, 'kept');").unwrap();
+        assert_eq!(execute(&mut db), Ok(false));
+        assert_eq!(db.query("select name from structs_if_not_exists;"), vec![vec![Value::TEXT(String::from("kept"))]]);
+        assert!(db.run("create table structs_if_not_exists (id int primary key);").is_err());
+    }
+
+    #[test]
+    fn drop_if_exists_ignores_a_missing_table() {
+        let mut db = TestDb::open(&["structs_if_exists"]);
+        let execute = |db: &mut TestDb, sql: &str| match SqlParser::parse_sql(sql.to_string()).unwrap() {
+            SqlStmt::DROP(drop) => drop.execute(&mut db.table_manager),
+            _ => panic!("`{}` is not a drop", sql),
+        };
+
+        assert_eq!(execute(&mut db, "drop table if exists structs_if_exists;"), Ok(false));
+        assert_eq!(
+            execute(&mut db, "drop table structs_if_exists;"),
+            Err(String::from("Table `structs_if_exists` does not exist."))
+        );
+        db.run("create table structs_if_exists (id int primary key);").unwrap();
+        assert_eq!(execute(&mut db, "drop table if exists structs_if_exists;"), Ok(true));
+        assert!(!Path::new(&build_path!(DATA_FOLDER, "structs_if_exists")).exists());
+    }
+
+    #[test]
+    fn empty_in_list_matches_no_row() {
+        let mut db = TestDb::open(&["structs_empty_in"]);
+        db.run("create table structs_empty_in (id int primary key, qty int);").unwrap();
+        for (id, qty) in [(1, 5), (5, 1), (7, 5)] {
+            db.run(&format!("insert into structs_empty_in (id, qty) values ({}, {});", id, qty)).unwrap();
+        }
+
+        for field in ["id", "qty"] {
+            let matching = |db: &mut TestDb, condition: &str| ids(db, &format!("select id from structs_empty_in where {} {};", field, condition));
+            assert_eq!(matching(&mut db, "in []"), Vec::<i32>::new());
+            assert_eq!(matching(&mut db, "not in []"), vec![1, 5, 7]);
+            assert_eq!(matching(&mut db, "not in [5]"), if field == "id" { vec![1, 7] } else { vec![5] });
+        }
+        assert_eq!(ids(&mut db, "select id from structs_empty_in where id in [5];"), vec![5]);
+        assert_eq!(ids(&mut db, "select id from structs_empty_in where qty in [5];"), vec![1, 7]);
+
+        assert!(db.run("select id from structs_empty_in where id in [1 5];").err().unwrap().contains("',' or ']' is expected"));
+    }
+
+    #[test]
+    fn coerce_parses_text_into_the_column_type() {
+        let mut db = TestDb::open(&["structs_coerce"]);
+        db.run("create table structs_coerce (id int primary key, price float, ok bool, name text(8));").unwrap();
+        let insert = "insert into structs_coerce (id, price, ok, name) values ('1', ' 2.5', 'TRUE', '42');";
+        assert_eq!(db.run(insert).err().unwrap(), "Value '1' of field `id` is not of type INT.");
+
+        db.run("pragma coerce = true;").unwrap();
+        db.run(insert).unwrap();
+        assert_eq!(
+            db.query("select * from structs_coerce;"),
+            vec![vec![Value::INT(1), Value::FLOAT(2.5), Value::BOOL(true), Value::TEXT(String::from("42"))]]
+        );
+        assert_eq!(
+            db.run("insert into structs_coerce (id, price, ok, name) values ('2x', 1.0, true, 'a');").err().unwrap(),
+            "Value '2x' of field `id` can not be converted to INT."
+        );
+        assert!(db.run("insert into structs_coerce (id, price, ok, name) values (3, 1.0, 'yes', 'a');").is_err());
+        db.run("pragma coerce = false;").unwrap();
+    }
+
+    #[test]
+    fn distinct_on_keeps_the_first_sorted_row_of_each_group() {
+        let mut db = TestDb::open(&["structs_distinct_on"]);
+        db.run("create table structs_distinct_on (id int primary key, team text(8), score int);").unwrap();
+        for (id, team, score) in [(1, "red", 5), (2, "blue", 7), (3, "red", 9), (4, "green", 1), (5, "blue", 3), (6, "red", 9)] {
+            db.run(&format!("insert into structs_distinct_on (id, team, score) values ({}, '{}', {});", id, team, score)).unwrap();
+        }
+
+        assert_eq!(ids(&mut db, "select distinct on (team) id, team from structs_distinct_on;"), vec![1, 2, 4]);
+        assert_eq!(ids(&mut db, "select distinct on (team) id from structs_distinct_on order by score desc;"), vec![3, 2, 4]);
+        assert_eq!(ids(&mut db, "select distinct on (team) id from structs_distinct_on order by team, score;"), vec![5, 4, 1]);
+        assert_eq!(ids(&mut db, "select distinct on (team, score) id from structs_distinct_on;"), vec![1, 2, 3, 4, 5]);
+        assert_eq!(ids(&mut db, "select distinct on (score / 5) id from structs_distinct_on;"), vec![1, 4]);
+        assert_eq!(ids(&mut db, "select distinct on (team) id from structs_distinct_on limit 2;"), vec![1, 2]);
+        assert!(db.run("select distinct id from structs_distinct_on;").err().unwrap().contains("`ON` is expected"));
+    }
+
+    #[test]
+    fn not_negates_a_whole_group() {
+        let mut db = TestDb::open(&["structs_not_group"]);
+        db.run("create table structs_not_group (id int primary key, a int, b int);").unwrap();
+        for id in 0..9 {
+            db.run(&format!("insert into structs_not_group (id, a, b) values ({}, {}, {});", id, id % 3, id / 3)).unwrap();
+        }
+        let select = |condition: &str| format!("select id from structs_not_group where {};", condition);
+
+        let expected = ids(&mut db, &select("a <> 1 and b <> 2"));
+        assert_eq!(expected, vec![0, 2, 3, 5]);
+        assert_eq!(ids(&mut db, &select("not (a = 1 or b = 2)")), expected);
+        assert_eq!(ids(&mut db, &select("not (a = 1 and b = 2)")), ids(&mut db, &select("a <> 1 or b <> 2")));
+        assert_eq!(ids(&mut db, &select("not (not (a = 1))")), vec![1, 4, 7]);
+        assert_eq!(ids(&mut db, &select("not (id < 7) and a = 1")), vec![7]);
+        assert_eq!(ids(&mut db, &select("id > 1 and not (a = 0 or (b = 0 and a = 2))")), vec![4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn same_field_can_be_selected_twice() {
+        let mut db = TestDb::open(&["structs_twice"]);
+        db.run("create table structs_twice (id int primary key, name text(8));").unwrap();
+        db.run("insert into structs_twice (id, name) values (1, 'a');").unwrap();
+        db.run("insert into structs_twice (id, name) values (2, 'b');").unwrap();
+
+        let result = db.run("select id, name, id, id from structs_twice;").unwrap().unwrap();
+        assert_eq!(result.fields, vec!["id", "name", "id:1", "id:2"]);
+        assert_eq!(
+            db.query("select id, id from structs_twice where id = 2;"),
+            vec![vec![Value::INT(2), Value::INT(2)]]
+        );
+        assert_eq!(db.query("select id, id from structs_twice order by name desc;").concat(), [2, 2, 1, 1].map(Value::INT));
+        assert!(SqlParser::parse_sql(String::from("select *, * from structs_twice;")).is_err());
     }
 }
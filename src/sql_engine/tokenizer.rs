@@ -14,11 +14,26 @@ pub const NOT: &str = "NOT";
 pub const VALUES: &str = "VALUES";
 pub const CREATE: &str = "CREATE";
 pub const TABLE: &str = "TABLE";
+pub const AS: &str = "AS";
+pub const WITH: &str = "WITH";
 pub const PRIMARY: &str = "PRIMARY";
 pub const KEY: &str = "KEY";
 pub const OR: &str = "OR";
 pub const AND: &str = "AND";
 pub const IN: &str = "IN";
+pub const ALTER: &str = "ALTER";
+pub const RENAME: &str = "RENAME";
+pub const TO: &str = "TO";
+pub const COLUMN: &str = "COLUMN";
+pub const PRAGMA: &str = "PRAGMA";
+pub const IF: &str = "IF";
+pub const DROP: &str = "DROP";
+pub const EXISTS: &str = "EXISTS";
+pub const LIMIT: &str = "LIMIT";
+pub const DISTINCT: &str = "DISTINCT";
+pub const ON: &str = "ON";
+pub const REINDEX: &str = "REINDEX";
+pub const COLLATE: &str = "COLLATE";
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum TokenType {
@@ -28,7 +43,9 @@ pub enum TokenType {
     Boolean,
     Number,
     StringLiteral,
+    BlobLiteral,
     Operator,
+    ArithmeticOperator,
     LogicalOperator,
     COMMA,
     Lparen,
@@ -41,7 +58,7 @@ pub enum TokenType {
     Mismatch,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Token {
     token_type: TokenType,
     value: String,
@@ -75,6 +92,23 @@ lazy_static! {
         set.insert("KEY");
         set.insert("CREATE");
         set.insert("TABLE");
+        set.insert("AS");
+        set.insert("WITH");
+        set.insert("ALTER");
+        set.insert("RENAME");
+        set.insert("TO");
+        set.insert("COLUMN");
+        set.insert("PRAGMA");
+        set.insert("IF");
+        set.insert("DROP");
+        set.insert("EXISTS");
+        set.insert("NOT");
+        set.insert("IN");
+        set.insert("LIMIT");
+        set.insert("DISTINCT");
+        set.insert("ON");
+        set.insert("REINDEX");
+        set.insert("COLLATE");
         set
     };
     static ref OPERATORS: HashSet<&'static str> = {
@@ -100,15 +134,25 @@ lazy_static! {
         set.insert("INT");
         set.insert("FLOAT");
         set.insert("BOOL");
+        set.insert("BLOB");
         set
     };
     static ref TOKEN_REGEX: Regex = {
         let number = r"\b\d+(\.\d*)?\b";
         let all_column = r"\*";
         let ident = r"\b[a-zA-Z_][a-zA-Z0-9_]*\b";
-        let boolean = r"true|false|True|False|TRUE|FALSE";
-        let string_literal = r"'[^']*'";
-        let operators = OPERATORS.iter().map(|&op| regex::escape(op)).collect::<Vec<_>>().join("|");
+        // matched before IDENT, otherwise `true`/`false` would be read as column names
+        let boolean = r"\b(?i:true|false)\b";
+        // a doubled single quote `''` inside a literal stands for one escaped `'`
+        let string_literal = r"'(?:[^']|'')*'";
+        // hex digits of a blob, e.g. x'0aff'
+        let blob_literal = r"\b[xX]'[0-9a-fA-F]*'";
+        // longest first, otherwise `<=` is read as `<` followed by `=`
+        let mut operators = OPERATORS.iter().copied().collect::<Vec<_>>();
+        operators.sort_by_key(|op| std::cmp::Reverse(op.len()));
+        let operators = operators.iter().map(|&op| regex::escape(op)).collect::<Vec<_>>().join("|");
+        // `*` is tokenized as ALL_COLUMN, the parser reads it as a multiplication inside expressions
+        let arithmetic_operators = r"[+\-/]";
         let logical_ops = LOGICAL_OPERATORS.iter().map(|&op| regex::escape(op)).collect::<Vec<_>>().join("|");
         let data_types = DATA_TYPE.iter().map(|&op| regex::escape(op)).collect::<Vec<_>>().join("|");
         let comma = r",";
@@ -121,14 +165,16 @@ lazy_static! {
         let eof = r"\;";
 
         let regex_str = format!(
-            "(?P<NUMBER>{})|(?P<ALL_COLUMN>{})|(?P<IDENT>{})|(?P<BOOLEAN>{})|(?P<STRING_LITERAL>{})|(?P<OPERATOR>{})|(?P<LOGICAL_OPERATOR>{})|(?P<DATA_TYPE>{})|(?P<COMMA>{})|(?P<LPAREN>{})|(?P<RPAREN>{})|(?P<LBRACKET>{})|(?P<RBRACKET>{})|(?P<SKIP>{})|(?P<MISMATCH>{})|(?P<EOF>{})",
-            number, all_column, ident, boolean, string_literal, operators, logical_ops, data_types, comma, lparen, rparen,left_bracket, right_bracket, skip, mismatch, eof
+            "(?P<NUMBER>{})|(?P<ALL_COLUMN>{})|(?P<BLOB_LITERAL>{})|(?P<BOOLEAN>{})|(?P<IDENT>{})|(?P<STRING_LITERAL>{})|(?P<OPERATOR>{})|(?P<ARITHMETIC_OPERATOR>{})|(?P<LOGICAL_OPERATOR>{})|(?P<DATA_TYPE>{})|(?P<COMMA>{})|(?P<LPAREN>{})|(?P<RPAREN>{})|(?P<LBRACKET>{})|(?P<RBRACKET>{})|(?P<SKIP>{})|(?P<MISMATCH>{})|(?P<EOF>{})",
+            number, all_column, blob_literal, boolean, ident, string_literal, operators, arithmetic_operators, logical_ops, data_types, comma, lparen, rparen,left_bracket, right_bracket, skip, mismatch, eof
         );
 
         Regex::new(&regex_str).unwrap()
     };
 }
 
+// cloned by a parser that has to go back and try another rule
+#[derive(Clone)]
 pub struct Tokenizer {
     current_token: Option<Token>,
     position: usize,
@@ -150,9 +196,13 @@ impl Tokenizer {
                 let typ = match TOKEN_REGEX.captures(&self.sql[self.position..]).unwrap() {
                     caps if caps.name("ALL_COLUMN").is_some() => TokenType::AllColumn,
                     caps if caps.name("NUMBER").is_some() => TokenType::Number,
+                    caps if caps.name("BLOB_LITERAL").is_some() => TokenType::BlobLiteral,
                         caps if caps.name("IDENT").is_some() => {
                         if KEYWORDS.contains(&token_str.to_uppercase().as_str()) {
                             TokenType::Keyword
+                        } else if LOGICAL_OPERATORS.contains(&token_str.to_uppercase().as_str()) {
+                            // `and` is matched by the IDENT group before the LOGICAL_OPERATOR one
+                            TokenType::LogicalOperator
                         } else {
                             TokenType::Ident
                         }
@@ -160,12 +210,13 @@ impl Tokenizer {
                     caps if caps.name("BOOLEAN").is_some() => TokenType::Boolean,
                     caps if caps.name("STRING_LITERAL").is_some() => TokenType::StringLiteral,
                     caps if caps.name("OPERATOR").is_some() => TokenType::Operator,
+                    caps if caps.name("ARITHMETIC_OPERATOR").is_some() => TokenType::ArithmeticOperator,
                     caps if caps.name("LOGICAL_OPERATOR").is_some() => TokenType::LogicalOperator,
                     caps if caps.name("COMMA").is_some() => TokenType::COMMA,
                     caps if caps.name("LPAREN").is_some() => TokenType::Lparen,
                     caps if caps.name("RPAREN").is_some() => TokenType::Rparen,
-                    caps if caps.name("LEFT_BRACKET").is_some() => TokenType::LeftBracket,
-                    caps if caps.name("RIGHT_BRACKET").is_some() => TokenType::RightBracket,
+                    caps if caps.name("LBRACKET").is_some() => TokenType::LeftBracket,
+                    caps if caps.name("RBRACKET").is_some() => TokenType::RightBracket,
                     caps if caps.name("DATA_TYPE").is_some() => TokenType::DataType,
                     caps if caps.name("SKIP").is_some() => {
                         self.position += mat.end();
@@ -181,10 +232,13 @@ impl Tokenizer {
 
                 self.position += mat.end();
 
-                let token = Token {
-                    token_type: typ,
-                    value: if typ == TokenType::Keyword { token_str.to_uppercase() } else { token_str.to_string() },
+                // keywords and identifiers are case-insensitive, literals are kept as typed
+                let value = match typ {
+                    TokenType::Keyword | TokenType::LogicalOperator => token_str.to_uppercase(),
+                    TokenType::Ident | TokenType::DataType => token_str.to_lowercase(),
+                    _ => token_str.to_string(),
                 };
+                let token = Token { token_type: typ, value };
                 self.current_token = Some(token);
                 return Ok(self.current_token());
             } else {
@@ -202,4 +256,58 @@ impl Tokenizer {
     pub fn has_more(&self) -> bool {
         self.current_token.as_ref().unwrap().token_type != TokenType::EOF
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql_engine::sql_structs::Value;
+    use crate::utils::test_utils::TestDb;
+
+    #[test]
+    fn literals_keep_their_case() {
+        let mut tokenizer = Tokenizer::new(String::from("Select Name FROM People where Name = 'O''Brien' and b = X'0A';"));
+        let mut tokens = vec![];
+        while tokenizer.next_token().unwrap().token_type() != TokenType::EOF {
+            tokens.push(tokenizer.current_token().value().to_string());
+        }
+        assert_eq!(tokens, ["SELECT", "name", "FROM", "people", "WHERE", "name", "=", "'O''Brien'", "AND", "b", "=", "X'0A'"]);
+    }
+
+    #[test]
+    fn escaped_quote_round_trip() {
+        let mut db = TestDb::open(&["tokenizer_names"]);
+        db.run("create table Tokenizer_Names (id int primary key, Name text(16));").unwrap();
+        db.run("insert into tokenizer_names (ID, name) values (1, 'O''Brien');").unwrap();
+        assert_eq!(
+            db.query("select NAME from tokenizer_names where name = 'O''Brien';"),
+            vec![vec![Value::TEXT(String::from("O'Brien"))]]
+        );
+        assert!(db.query("select name from tokenizer_names where name = 'o''brien';").is_empty());
+    }
+
+    #[test]
+    fn booleans_are_tokenized_in_every_position() {
+        let mut tokenizer = Tokenizer::new(String::from("true = FALSE or True != trueish;"));
+        let mut types = vec![];
+        while tokenizer.next_token().unwrap().token_type() != TokenType::EOF {
+            types.push(tokenizer.current_token().token_type());
+        }
+        assert_eq!(types[0], TokenType::Boolean);
+        assert_eq!(types[2], TokenType::Boolean);
+        assert_eq!(types[4], TokenType::Boolean);
+        assert_eq!(types[6], TokenType::Ident);
+
+        let mut db = TestDb::open(&["tokenizer_flags"]);
+        db.run("create table tokenizer_flags (id int primary key, done bool);").unwrap();
+        for (id, done) in [(1, "TRUE"), (2, "false"), (3, "True"), (4, "False")] {
+            db.run(&format!("insert into tokenizer_flags (id, done) values ({}, {});", id, done)).unwrap();
+        }
+        let ids = |db: &mut TestDb, sql: &str| -> Vec<Value> { db.query(sql).into_iter().map(|row| row[0].clone()).collect() };
+        assert_eq!(ids(&mut db, "select id from tokenizer_flags where done = true;"), [Value::INT(1), Value::INT(3)]);
+        assert_eq!(ids(&mut db, "select id from tokenizer_flags where done != TRUE;"), [Value::INT(2), Value::INT(4)]);
+        assert_eq!(
+            ids(&mut db, "select id from tokenizer_flags order by done desc;"),
+            [Value::INT(1), Value::INT(3), Value::INT(2), Value::INT(4)]
+        );
+    }
+}
@@ -1,34 +1,243 @@
 extern crate core;
 
 use std::{fs, ptr};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
 use std::io::Write;
 use std::ops::Deref;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
 
 use prettytable::Row;
 
 use crate::build_path;
-use crate::sql_engine::sql_structs::{DataType, FieldDefinition, Value};
+use crate::sql_engine::sql_structs::{Collation, CreateStmt, DataType, FieldDefinition, InsertStmt, Value};
 use crate::storage_engine::config::*;
 use crate::storage_engine::tables::{BtreeTable, SequentialTable, Table};
-use crate::utils::utils::{copy, copy_nonoverlapping, list_files_of_folder, u8_array_to_string};
+use crate::utils::utils::{copy_nonoverlapping, json_string, list_files_of_folder, read_uint, u8_array_to_string, write_uint};
+
+/*
+    A kind of storage file of a table: the extension of the file, the size of the file header
+    in front of the pages and how the file is opened.
+    Files are dispatched by extension through STORAGE_TYPES, a new Table implementation is
+    loaded and repaired once its entry is added there.
+ */
+pub(crate) type OpenTable = fn(&PathBuf, Rc<TableStructureMetadata>, DbFormat) -> Result<Box<dyn Table>, String>;
+
+pub(crate) struct StorageType {
+    pub(crate) extension: &'static str,
+    pub(crate) header_size: usize,
+    pub(crate) open: OpenTable,
+}
+
+pub(crate) const STORAGE_TYPES: [StorageType; 2] = [
+    StorageType {
+        extension: ".idx",
+        header_size: BTREE_METADATA_SIZE,
+        open: |path, table_meta, format| Ok(Box::new(BtreeTable::new(path, table_meta, format)?)),
+    },
+    StorageType {
+        extension: ".seq",
+        header_size: SEQUENTIAL_NODE_HEADER_SIZE,
+        open: |path, table_meta, format| Ok(Box::new(SequentialTable::new(path, table_meta, format)?)),
+    },
+];
+
+impl StorageType {
+    /*
+        The storage type of a file of a table folder, None for the `.frm` file and unknown files
+     */
+    pub(crate) fn of_file(file_name: &str) -> Option<&'static StorageType> {
+        STORAGE_TYPES.iter().find(|s| file_name.ends_with(s.extension))
+    }
+}
+
+// the metadata of a table and its storage files, the base table first
+type LoadedTable = (Rc<TableStructureMetadata>, Vec<Box<dyn Table>>);
 
 pub struct TableManager {
-    tables: HashMap<String, (Rc<TableStructureMetadata>, Vec<Box<dyn Table>>)>
+    tables: HashMap<String, LoadedTable>,
+    // names of the cached tables, least recently used first
+    recently_used: VecDeque<String>,
+    format: DbFormat,
+    settings: Settings,
+}
+
+/*
+    Runtime settings, read with `PRAGMA name;` and changed with `PRAGMA name = value;`.
+    They are not persisted, every session starts with the defaults.
+ */
+pub struct Settings {
+    // flush the changed table after every statement, turned off for batch loads
+    pub autocommit: bool,
+    // print the run time of every statement
+    pub timer: bool,
+    // rows printed by a select, 0 prints them all
+    pub max_rows: usize,
+    // used by `create table` without `WITH (FILLFACTOR = n)`
+    pub fill_factor: usize,
+    // how select results are printed, changed with `.mode table|csv|json|list`
+    pub output: OutputMode,
+    // insert parses a TEXT value given to a number or boolean column, off keeps typing strict
+    pub coerce: bool,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum OutputMode {
+    TABLE,
+    CSV,
+    JSON,
+    // one line per row, the values separated by `|`
+    LIST,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            autocommit: true,
+            timer: false,
+            max_rows: 0,
+            fill_factor: DEFAULT_FILL_FACTOR,
+            output: OutputMode::TABLE,
+            coerce: false,
+        }
+    }
 }
 
 impl TableManager {
-    pub fn new() -> TableManager {
-        TableManager {
-            tables: HashMap::new()
+    pub fn new(page_size: Option<usize>) -> Result<TableManager, String> {
+        Ok(TableManager {
+            tables: HashMap::new(),
+            recently_used: VecDeque::new(),
+            format: Self::load_format(page_size)?,
+            settings: Settings::default(),
+        })
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn set_output(&mut self, output: OutputMode) {
+        self.settings.output = output;
+    }
+
+    pub fn get_setting(&self, name: &str) -> Result<Value, String> {
+        match name {
+            "page_size" => Ok(Value::INT(self.format.page_size as i32)),
+            "autocommit" => Ok(Value::BOOL(self.settings.autocommit)),
+            "timer" => Ok(Value::BOOL(self.settings.timer)),
+            "max_rows" => Ok(Value::INT(self.settings.max_rows as i32)),
+            "fill_factor" => Ok(Value::INT(self.settings.fill_factor as i32)),
+            "coerce" => Ok(Value::BOOL(self.settings.coerce)),
+            _ => Err(format!("Unknown pragma `{}`.", name)),
+        }
+    }
+
+    pub fn set_setting(&mut self, name: &str, value: &Value) -> Result<(), String> {
+        match (name, value) {
+            ("page_size", _) => {
+                return Err(String::from(
+                    "page_size is fixed when the database is created, use --page-size=n on a new data folder.",
+                ))
+            }
+            ("autocommit", Value::BOOL(on)) => self.settings.autocommit = *on,
+            ("timer", Value::BOOL(on)) => self.settings.timer = *on,
+            ("coerce", Value::BOOL(on)) => self.settings.coerce = *on,
+            ("max_rows", Value::INT(n)) if *n >= 0 => self.settings.max_rows = *n as usize,
+            ("fill_factor", Value::INT(n)) if (MIN_FILL_FACTOR as i32..=MAX_FILL_FACTOR as i32).contains(n) => {
+                self.settings.fill_factor = *n as usize
+            }
+            ("fill_factor", Value::INT(_)) => {
+                return Err(format!("FILLFACTOR must be between {} and {}.", MIN_FILL_FACTOR, MAX_FILL_FACTOR))
+            }
+            ("autocommit" | "timer" | "max_rows" | "fill_factor" | "coerce", _) => {
+                return Err(format!("Invalid value {} for pragma `{}`.", value.to_sql_literal(), name))
+            }
+            _ => return Err(format!("Unknown pragma `{}`.", name)),
+        }
+        Ok(())
+    }
+
+    /*
+        Called once a statement changed `table_name`, its pages are written so they survive a crash
+        before the next `flush;`. Does nothing when autocommit is off.
+     */
+    pub fn commit(&mut self, table_name: &str) {
+        if !self.settings.autocommit {
+            return;
+        }
+        if let Some((_, tables)) = self.tables.get_mut(table_name) {
+            tables.iter_mut().for_each(|t| t.flush_to_disk())
+        }
+    }
+
+    pub fn get_format(&self) -> DbFormat {
+        self.format
+    }
+
+    /*
+        The page size and the format version are fixed when the database is created and read back
+        from the db header afterwards. A data folder without header was created before the header
+        existed, it keeps the default page size and the legacy format.
+     */
+    fn load_format(requested_page_size: Option<usize>) -> Result<DbFormat, String> {
+        let header_path = build_path!(DATA_FOLDER, DB_HEADER_FILE);
+        let format = match fs::read(&header_path) {
+            Ok(header) => {
+                if header.len() != DB_HEADER_SIZE {
+                    return Err(String::from("Db header is corrupt."));
+                }
+                let format = DbFormat {
+                    page_size: u32::from_le_bytes(header[..DB_PAGE_SIZE_SIZE].try_into().unwrap()) as usize,
+                    version: u32::from_le_bytes(header[DB_PAGE_SIZE_SIZE..].try_into().unwrap()),
+                };
+                if format.version > CURRENT_FORMAT_VERSION {
+                    return Err(format!(
+                        "Db format version {} is newer than the supported version {}.",
+                        format.version, CURRENT_FORMAT_VERSION
+                    ));
+                }
+                format
+            }
+            Err(_) if Path::new(DATA_FOLDER).exists() => {
+                DbFormat { page_size: DEFAULT_PAGE_SIZE, version: LEGACY_FORMAT_VERSION }
+            }
+            Err(_) => {
+                let page_size = requested_page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+                if !page_size.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+                    return Err(format!(
+                        "Page size must be a power of two between {} and {}.",
+                        MIN_PAGE_SIZE, MAX_PAGE_SIZE
+                    ));
+                }
+                let format = DbFormat::current(page_size);
+                let mut header = Vec::with_capacity(DB_HEADER_SIZE);
+                header.extend_from_slice(&(format.page_size as u32).to_le_bytes());
+                header.extend_from_slice(&format.version.to_le_bytes());
+                if fs::create_dir_all(DATA_FOLDER).is_err() || fs::write(&header_path, header).is_err() {
+                    return Err(String::from("Can not create db header."));
+                }
+                return Ok(format);
+            }
+        };
+
+        match requested_page_size {
+            Some(requested) if requested != format.page_size => Err(format!(
+                "Database already exists with a page size of {}.",
+                format.page_size
+            )),
+            _ => Ok(format),
         }
     }
 
     pub fn find_index_for_field(&self, table_name: &str, field: &str) -> Option<&Box<dyn Table>> {
         let tables = self.tables.get(table_name).unwrap();
-        tables.1.iter().find(|t| t.as_any().downcast_ref::<BtreeTable>().unwrap().key_field_name == field)
+        tables.1.iter().find(|t| match t.as_any().downcast_ref::<BtreeTable>() {
+            Some(btree) => btree.key_field_name == field,
+            None => false,
+        })
     }
 
     pub fn register_new_table(
@@ -37,11 +246,29 @@ impl TableManager {
         storage_file: &PathBuf,
     ) -> Result<(), String> {
         if !self.tables.contains_key(table_name) {
-            self.load_tables(table_name)
+            self.load_tables(table_name)?;
         } else {
             let (meta, tables) = self.tables.get_mut(table_name).unwrap();
-            let table = Self::load_table(storage_file, Rc::clone(&meta))?;
-            Ok(tables.push(table))
+            let table = Self::load_table(storage_file, Rc::clone(meta), self.format)?;
+            tables.push(table);
+        }
+        self.touch(table_name);
+        Ok(())
+    }
+
+    /*
+        Mark a table as the most recently used one. When more than MAX_CACHED_TABLES are cached
+        the least recently used is flushed and dropped, closing its files; it is loaded again on demand.
+     */
+    fn touch(&mut self, table_name: &str) {
+        if self.recently_used.back().map(|t| t.as_str()) == Some(table_name) {
+            return;
+        }
+        self.recently_used.retain(|t| t != table_name);
+        self.recently_used.push_back(table_name.to_string());
+        while self.recently_used.len() > MAX_CACHED_TABLES {
+            let least_recently_used = self.recently_used.front().unwrap().clone();
+            self.close_table(&least_recently_used);
         }
     }
 
@@ -49,30 +276,42 @@ impl TableManager {
         if !self.tables.contains_key(table_name) {
             self.load_tables(table_name)?;
         }
+        self.touch(table_name);
 
         let result: &mut (Rc<TableStructureMetadata>, Vec<Box<dyn Table>>) =
             self.tables.get_mut(table_name).unwrap();
         Ok(&mut result.1)
     }
 
+    /*
+        The table holding every row: the sequential file of a keyless table, the primary B-tree otherwise.
+        Full scans go through it, never through a secondary index.
+     */
+    pub fn get_base_table(&mut self, table_name: &str) -> Result<&mut dyn Table, String> {
+        match self.get_tables(table_name)?.first_mut() {
+            Some(table) => Ok(table.as_mut()),
+            None => Err(format!("Table `{}` has no storage file.", table_name)),
+        }
+    }
+
     fn load_tables(&mut self, table_name: &str) -> Result<(), String> {
         let table_meta = Rc::new(self.load_metadata(table_name)?);
         let storage_files = list_files_of_folder(&build_path!(DATA_FOLDER, table_name))?;
         let mut tables = Vec::<Box<dyn Table>>::new();
 
         for (file_name, path) in storage_files {
-            let file_name = file_name.into_string().unwrap();
-            if file_name.ends_with(".frm") {
-                continue;
+            if let Some(storage_type) = StorageType::of_file(&file_name.to_string_lossy()) {
+                tables.push((storage_type.open)(&path, Rc::clone(&table_meta), self.format)?);
             }
-            let index = file_name.ends_with(".idx");
-            let table: Box<dyn Table> = if index {
-                Box::new(BtreeTable::new(&path, Rc::clone(&table_meta))?)
-            } else {
-                Box::new(SequentialTable::new(&path, Rc::clone(&table_meta)).unwrap())
-            };
-            tables.push(table);
         }
+        if tables.is_empty() {
+            return Err(format!(
+                "Storage of table `{}` is incomplete, its data file is missing. `.repair {};` recreates it empty.",
+                table_name, table_name
+            ));
+        }
+        // the base table comes first whatever the order of the directory listing
+        tables.sort_by_key(|t| !Self::is_base_table(t.as_ref()));
         self.tables
             .insert(table_name.to_string(), (table_meta, tables));
         Ok(())
@@ -81,18 +320,19 @@ impl TableManager {
     fn load_table(
         storage_file_name: &PathBuf,
         table_meta: Rc<TableStructureMetadata>,
+        format: DbFormat,
     ) -> Result<Box<dyn Table>, String> {
-        let is_index = storage_file_name.ends_with(".idx");
-        if is_index {
-            Ok(Box::new(BtreeTable::new(
-                storage_file_name,
-                Rc::clone(&table_meta),
-            )?))
-        } else {
-            Ok(Box::new(SequentialTable::new(
-                storage_file_name,
-                Rc::clone(&table_meta),
-            )?))
+        let file_name = storage_file_name.file_name().unwrap_or_default().to_string_lossy();
+        match StorageType::of_file(&file_name) {
+            Some(storage_type) => (storage_type.open)(storage_file_name, table_meta, format),
+            None => Err(format!("`{}` is not a storage file.", file_name)),
+        }
+    }
+
+    fn is_base_table(table: &dyn Table) -> bool {
+        match table.as_any().downcast_ref::<BtreeTable>() {
+            Some(btree) => btree.is_primary,
+            None => true,
         }
     }
 
@@ -100,9 +340,10 @@ impl TableManager {
         &mut self,
         table_name: &str,
     ) -> Result<&TableStructureMetadata, String> {
-        if self.tables.is_empty() {
+        if !self.tables.contains_key(table_name) {
             self.load_tables(table_name)?;
         }
+        self.touch(table_name);
         match self.tables.get(table_name) {
             None => {
                 return Err(format!("Table {} is not exist.", table_name));
@@ -113,9 +354,19 @@ impl TableManager {
 
     fn load_metadata(&mut self, table_name: &str) -> Result<TableStructureMetadata, String> {
         let path = build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".frm");
-        let metadata = unsafe { Self::load_metadata_from_disk(&path, table_name)? };
-        let tm = TableStructureMetadata::new(table_name, metadata);
-        Ok(tm)
+        let (metadata, fill_factor) = unsafe { Self::load_metadata_from_disk(&path, table_name, self.format)? };
+        TableStructureMetadata::new(table_name, metadata, fill_factor)
+    }
+
+    /*
+        Flush and drop the cached storage of a table, so its files can be
+        moved or rewritten. The table is loaded again on the next access.
+     */
+    pub fn close_table(&mut self, table_name: &str) {
+        if let Some((_, mut tables)) = self.tables.remove(table_name) {
+            tables.iter_mut().for_each(|t| t.flush_to_disk())
+        }
+        self.recently_used.retain(|t| t != table_name);
     }
 
     pub fn flush_to_disk(&mut self) {
@@ -124,14 +375,248 @@ impl TableManager {
         }
     }
 
+    /*
+        Stream every row of a table through `f` without collecting them.
+     */
+    pub fn for_each_row(&mut self, table_name: &str, f: impl FnMut(&RowBytes)) -> Result<(), String> {
+        let mut f = f;
+        self.get_base_table(table_name)?.for_each_row(&mut f);
+        Ok(())
+    }
+
+    /*
+        Check the B-tree invariants of every index of a table.
+     */
+    pub fn verify(&mut self, table_name: &str) -> Result<(), String> {
+        for table in self.get_tables(table_name)?.iter() {
+            if let Some(btree) = table.as_any().downcast_ref::<BtreeTable>() {
+                btree.verify()?;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+        Cut the storage files of a table back to a whole number of pages, dropping
+        the tail of a page that was only partially written. Returns a line per file.
+     */
+    pub fn repair(&mut self, table_name: &str) -> Result<Vec<String>, String> {
+        self.close_table(table_name);
+
+        let mut report = vec![];
+        for (file_name, path) in list_files_of_folder(&build_path!(DATA_FOLDER, table_name))? {
+            let file_name = file_name.into_string().unwrap();
+            let header_size = match StorageType::of_file(&file_name) {
+                Some(storage_type) => storage_type.header_size,
+                None => continue,
+            };
+
+            let file = match OpenOptions::new().write(true).open(&path) {
+                Ok(file) => file,
+                Err(_) => return Err(format!("Can not open {} for repair.", file_name)),
+            };
+            let size = file.metadata().unwrap().len() as usize;
+            if size < header_size {
+                return Err(format!("Header of {} is truncated, it can not be repaired.", file_name));
+            }
+
+            let page_size = self.format.page_size;
+            let valid_size = header_size + (size - header_size) / page_size * page_size;
+            if file.set_len(valid_size as u64).is_err() {
+                return Err(format!("Can not truncate {}.", file_name));
+            }
+            report.push(format!("{}: discarded {} bytes.", file_name, size - valid_size));
+        }
+
+        /* the table was not created completely, e.g. a crash after writing its .frm file */
+        if report.is_empty() {
+            let definitions = self.load_metadata(table_name)?.field_definitions();
+            let path = CreateStmt::new(table_name.to_string(), definitions, None).create_storage_file(self.format)?;
+            report.push(format!("{}: recreated empty.", path.file_name().unwrap().to_string_lossy()));
+        }
+        Ok(report)
+    }
+
+    /*
+        Rebuilds the B-tree of a table with a primary key from the rows of its leaf pages.
+        Internal nodes, parent and sibling pointers are not trusted: the leaf pages are read
+        in page order and their rows are inserted into a new empty tree.
+        The original file is written back when the rebuild fails. Returns the number of rows.
+     */
+    pub fn reindex(&mut self, table_name: &str) -> Result<usize, String> {
+        let (rows, fill_factor) = match self
+            .get_tables(table_name)?
+            .iter()
+            .find_map(|t| t.as_any().downcast_ref::<BtreeTable>())
+        {
+            Some(btree) => (btree.leaf_rows(), btree.fill_factor),
+            None => return Err(format!("Table `{}` has no primary key, it has no index to rebuild.", table_name)),
+        };
+        let table_meta = Rc::clone(&self.tables.get(table_name).unwrap().0);
+        self.close_table(table_name);
+
+        let path = build_path!(DATA_FOLDER, table_name, table_name.to_owned() + ".idx");
+        let original = match fs::read(&path) {
+            Ok(original) => original,
+            Err(_) => return Err(format!("Can not read the index of table `{}`.", table_name)),
+        };
+        // the overflow pages are kept as they are, the rebuilt cells point to the same values
+        CreateStmt::new(table_name.to_string(), table_meta.field_definitions(), Some(fill_factor))
+            .create_storage_file(self.format)?;
+
+        let rebuilt = self.insert_rows(table_name, &table_meta, &rows);
+        self.close_table(table_name);
+        if let Err(e) = rebuilt {
+            if fs::write(&path, original).is_err() {
+                return Err(format!("{} The index of table `{}` could not be restored.", e, table_name));
+            }
+            return Err(e);
+        }
+        Ok(rows.len())
+    }
+
+    /*
+        Inserts rows as stored in their cells, only their primary key is decoded.
+        The pointers to the overflow pages are copied, the long TEXT values are not stored again.
+     */
+    fn insert_rows(&mut self, table_name: &str, table_meta: &TableStructureMetadata, rows: &[RowBytes]) -> Result<(), String> {
+        let key_meta = match table_meta.fields.iter().find(|f| f.data_def.is_primary_key) {
+            Some(key_meta) => key_meta,
+            None => return Err(format!("Table `{}` has no primary key.", table_name)),
+        };
+        let key_offset = table_meta.stored_offset(&key_meta.data_def.field_name)?;
+        for row in rows {
+            let key = row.read_key(&key_meta.data_def.data_type, key_offset, key_meta.size);
+            let row = RowToInsert {
+                field_value_pairs: vec![(&key_meta.data_def.field_name, &key)],
+                raw_data: row.clone(),
+                stored: true,
+            };
+            for table in self.get_tables(table_name)?.iter_mut() {
+                table.insert(&row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+        Copies the database folder `source` to `destination`, the copy is a data folder that can be opened as is.
+        Every cached page is written first when `source` holds tables of the open data folder.
+        Returns the number of copied files.
+     */
+    pub fn backup(&mut self, source: &str, destination: &str) -> Result<usize, String> {
+        let source = PathBuf::from(source);
+        let destination = PathBuf::from(destination);
+        if !source.is_dir() {
+            return Err(format!("Backup source `{}` is not a directory.", source.display()));
+        }
+        if destination.exists() {
+            return Err(format!("Backup destination `{}` already exists.", destination.display()));
+        }
+        let data_folder = Self::absolute_path(Path::new(DATA_FOLDER));
+        let absolute_source = Self::absolute_path(&source);
+        let absolute_destination = Self::absolute_path(&destination);
+        if absolute_destination.starts_with(&data_folder) || absolute_destination.starts_with(&absolute_source) {
+            return Err(String::from("Backup destination can not be inside the data folder or the source."));
+        }
+        if absolute_source.starts_with(&data_folder) || data_folder.starts_with(&absolute_source) {
+            self.flush_to_disk();
+        }
+        Self::copy_folder(&source, &destination)
+    }
+
+    /*
+        `path` from the root with `.` and `..` resolved, it does not have to exist
+     */
+    fn absolute_path(path: &Path) -> PathBuf {
+        let mut absolute = std::env::current_dir().unwrap_or_default();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    absolute.pop();
+                }
+                component => absolute.push(component),
+            }
+        }
+        absolute
+    }
+
+    fn copy_folder(source: &PathBuf, destination: &PathBuf) -> Result<usize, String> {
+        if fs::create_dir_all(destination).is_err() {
+            return Err(format!("Can not create directory {}.", destination.display()));
+        }
+        let mut copied = 0;
+        for (file_name, path) in list_files_of_folder(source)? {
+            let target = build_path!(destination, &file_name);
+            if path.is_dir() {
+                copied += Self::copy_folder(&path, &target)?;
+            } else if fs::copy(&path, &target).is_ok() {
+                copied += 1;
+            } else {
+                return Err(format!("Can not copy {}.", path.display()));
+            }
+        }
+        Ok(copied)
+    }
+
+    /*
+        Inserts one row from (field, value) pairs without going through SQL text,
+        with the same checks as an INSERT statement.
+     */
+    pub fn insert_row(&mut self, table_name: &str, pairs: &[(&str, Value)]) -> Result<(), String> {
+        let (fields, values) = pairs.iter().map(|(field, value)| (field.to_string(), value.clone())).unzip();
+        InsertStmt::new(table_name.to_string(), fields, values).execute(self)
+    }
+
+    /*
+        The CREATE TABLE statement of a table, with the fill factor of its index
+     */
+    pub fn create_sql(&mut self, table_name: &str) -> Result<String, String> {
+        let tables = self.get_tables(table_name)?;
+        let fill_factor = tables
+            .iter()
+            .find_map(|t| t.as_any().downcast_ref::<BtreeTable>().map(|b| b.fill_factor));
+        let variable_rows = tables
+            .iter()
+            .any(|t| t.as_any().downcast_ref::<SequentialTable>().is_some_and(|s| s.variable_rows));
+        Ok(self.get_table_metadata(table_name)?.to_create_sql(fill_factor, variable_rows))
+    }
+
+    /*
+        SQL statements that recreate the table and its rows when replayed.
+     */
+    pub fn dump_table(&mut self, table_name: &str) -> Result<Vec<String>, String> {
+        let mut statements = vec![self.create_sql(table_name)?];
+        let table_meta = Rc::clone(&self.tables.get(table_name).unwrap().0);
+
+        self.for_each_row(table_name, |row| {
+            let values: Vec<String> = table_meta
+                .fields
+                .iter()
+                .map(|f| {
+                    let value = row.read_key(&f.data_def.data_type, f.offset, f.size);
+                    value.to_sql_literal()
+                })
+                .collect();
+            statements.push(format!(
+                "INSERT INTO {} VALUES ({});",
+                table_name,
+                values.join(", ")
+            ));
+        })?;
+        Ok(statements)
+    }
+
     pub fn print_btree(&mut self, table_name: &str) {
         println!("{}", table_name)
     }
 
     unsafe fn load_metadata_from_disk(
         path: &Path,
-        table_name: &str
-    ) -> Result<Vec<(String, u32, Rc<FieldMetadata>)>, String> {
+        table_name: &str,
+        format: DbFormat,
+    ) -> Result<(Vec<NamedField>, usize), String> {
         let metadata = match fs::read(path) {
             Ok(metadata) => { metadata }
             Err(_) => {return Err(format!("Table `{}` does not exist.", table_name))}
@@ -140,16 +625,10 @@ impl TableManager {
         let mut metadata_pointer = 0; // pointer that points to the position where we should start reading
 
         let ptr = metadata.as_ptr();
-        let fields_number: usize = 0;
-        copy_nonoverlapping(
-            ptr,
-            &fields_number as *const usize as *mut u8,
-            FIELD_NUMBER_SIZE,
-        );
+        let fields_number = read_uint(ptr, FIELD_NUMBER_SIZE);
         metadata_pointer += FIELD_NUMBER_SIZE;
-        let mut fields: Vec<(String, u32, Rc<FieldMetadata>)> = Vec::with_capacity(fields_number);
+        let mut fields: Vec<NamedField> = Vec::with_capacity(fields_number);
 
-        let data_type_mask: u8 = 0b0000_0000;
         let primary: u8 = 0b0000_0001;
         let mut value_offset = 0; // offset of the current field's value
 
@@ -166,36 +645,22 @@ impl TableManager {
             );
             metadata_pointer += FIELD_TYPE_PRIMARY_SIZE;
 
-            let data_type_bit_code = (field_type_primary >> 1) | data_type_mask;
+            let data_type_bit_code = (field_type_primary >> 1) & FIELD_DATA_TYPE_MASK;
             let mut size: usize = 0;
-
-            let data_type = match DataType::from_bit_code(data_type_bit_code)? {
-                DataType::TEXT(_) => {
-                    copy(
-                        ptr.add(metadata_pointer),
-                        &size as *const usize as *mut u8,
-                        TEXT_CHARS_NUM_SIZE,
-                    );
-                    metadata_pointer += TEXT_CHARS_NUM_SIZE;
-                    DataType::TEXT(size)
-                }
-                DataType::INTEGER => {
-                    size = INTEGER_SIZE;
-                    DataType::INTEGER
-                }
-                DataType::FLOAT => {
-                    size = FLOAT_SIZE;
-                    DataType::FLOAT
-                }
-                DataType::BOOLEAN => {
-                    size = BOOLEAN_SIZE;
-                    DataType::BOOLEAN
-                }
-            };
+            // TEXT and BLOB are followed by their declared size
+            if DataType::from_bit_code(data_type_bit_code, 0)?.is_sized() {
+                size = read_uint(ptr.add(metadata_pointer), format.text_chars_num_size());
+                metadata_pointer += format.text_chars_num_size();
+            }
+            let data_type = DataType::from_bit_code(data_type_bit_code, size)?;
+            let size = data_type.get_size();
 
             let is_primary = (field_type_primary & primary) == 1;
 
-            let definition = FieldDefinition::new(u8_array_to_string(&buf), data_type, is_primary);
+            let mut definition = FieldDefinition::new(u8_array_to_string(&buf), data_type, is_primary);
+            if field_type_primary & FIELD_NOCASE_BIT != 0 {
+                definition.set_collation(Collation::NOCASE);
+            }
 
             fields.push((
                 u8_array_to_string(&buf),
@@ -206,13 +671,35 @@ impl TableManager {
             value_offset += size;
         }
 
-        Ok(fields)
+        // tables created before the fill factor was configurable end after their fields
+        let fill_factor = match metadata.get(metadata_pointer) {
+            None => DEFAULT_FILL_FACTOR,
+            Some(&fill_factor) if (MIN_FILL_FACTOR..=MAX_FILL_FACTOR).contains(&(fill_factor as usize)) => {
+                fill_factor as usize
+            }
+            Some(_) => return Err(format!("Metadata of table `{}` is corrupt.", table_name)),
+        };
+
+        Ok((fields, fill_factor))
     }
 }
 
-pub(crate) type Page = [u8; PAGE_SIZE];
+/*
+    Writes the cached pages when the manager goes away, so a forgotten `flush;` loses nothing.
+    Only dirty pages are written, nothing is left to write after an explicit flush.
+ */
+impl Drop for TableManager {
+    fn drop(&mut self) {
+        self.flush_to_disk();
+    }
+}
+
+pub(crate) type Page = Box<[u8]>;
 
-#[derive(Debug, Hash, Eq, PartialEq)]
+// (name, position, metadata) of a field as read from `.frm`
+type NamedField = (String, u32, Rc<FieldMetadata>);
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct RowBytes {
     pub data: Vec<u8>,
 }
@@ -247,17 +734,56 @@ impl RowBytes {
     }
 }
 
-pub struct SelectResult<'a> {
-    pub fields: Vec<&'a str>,
-    pub rows: Vec<RowValues>,
+/*
+    Rows of a select result
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub enum SelectRows {
+    // values decoded while executing, e.g. to sort them
+    DECODED(Vec<RowValues>),
+    // raw rows with the metadata of each selected field, a value is decoded when it is read
+    LAZY(Vec<RowBytes>, Vec<Rc<FieldMetadata>>),
+}
+
+pub struct SelectResult {
+    pub fields: Vec<String>,
+    pub rows: SelectRows,
 }
 
-impl<'a> SelectResult<'a> {
-    pub fn new(fields: Vec<&'a str>, rows: Vec<RowValues>) -> SelectResult {
-        SelectResult { fields, rows }
+impl SelectResult {
+    pub fn new(fields: Vec<String>, rows: Vec<RowValues>) -> SelectResult {
+        SelectResult { fields, rows: SelectRows::DECODED(rows) }
+    }
+
+    pub fn lazy(fields: Vec<String>, rows: Vec<RowBytes>, columns: Vec<Rc<FieldMetadata>>) -> SelectResult {
+        SelectResult { fields, rows: SelectRows::LAZY(rows, columns) }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.rows {
+            SelectRows::DECODED(rows) => rows.len(),
+            SelectRows::LAZY(rows, _) => rows.len(),
+        }
     }
 
-    pub(crate) fn print(&self) {
+    pub fn get(&self, row: usize, column: usize) -> Rc<Value> {
+        match &self.rows {
+            SelectRows::DECODED(rows) => Rc::clone(&rows[row].fields[column]),
+            SelectRows::LAZY(rows, columns) => {
+                let field_meta = &columns[column];
+                Rc::new(rows[row].read_key(
+                    &field_meta.data_def.data_type,
+                    field_meta.offset,
+                    field_meta.size,
+                ))
+            }
+        }
+    }
+
+    /*
+        prints at most `max_rows` rows, 0 prints them all
+     */
+    pub(crate) fn print(&self, max_rows: usize, writer: &mut dyn Write) -> std::io::Result<()> {
         let mut table = prettytable::Table::new();
 
         table.add_row(Row::new(
@@ -267,22 +793,86 @@ impl<'a> SelectResult<'a> {
                 .collect(),
         ));
 
-        self.rows.iter().for_each(|r| {
+        let shown = if max_rows == 0 { self.len() } else { self.len().min(max_rows) };
+        for row in 0..shown {
             table.add_row(Row::new(
-                r.fields
-                    .iter()
-                    .map(|f| prettytable::Cell::new(f.to_string().as_str()))
+                (0..self.fields.len())
+                    .map(|column| prettytable::Cell::new(self.get(row, column).to_string().as_str()))
                     .collect(),
             ));
-        });
+        }
 
-        table.printstd();
+        table.print(writer)?;
+        if shown < self.len() {
+            writeln!(writer, "... {} more rows", self.len() - shown)?;
+        }
+        Ok(())
+    }
+
+    /*
+        Writes the rows as a JSON array of objects keyed by the field names, one row at a time
+     */
+    pub(crate) fn to_json(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        let keys: Vec<String> = self.fields.iter().map(|f| json_string(f)).collect();
+        write!(writer, "[")?;
+        for row in 0..self.len() {
+            if row > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "\n  {{")?;
+            for (column, key) in keys.iter().enumerate() {
+                if column > 0 {
+                    write!(writer, ", ")?;
+                }
+                write!(writer, "{}: {}", key, self.get(row, column).to_json())?;
+            }
+            write!(writer, "}}")?;
+        }
+        writeln!(writer, "{}]", if self.len() > 0 { "\n" } else { "" })
+    }
+
+    /*
+        Writes a header line with the field names then one line per row,
+        a value holding a comma, a quote or a line break is quoted as in RFC 4180
+     */
+    pub(crate) fn to_csv(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        let csv_field = |s: &str| {
+            if s.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.to_string()
+            }
+        };
+        let header: Vec<String> = self.fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(writer, "{}", header.join(","))?;
+        for row in 0..self.len() {
+            let values: Vec<String> = (0..self.fields.len())
+                .map(|column| csv_field(&self.get(row, column).to_string()))
+                .collect();
+            writeln!(writer, "{}", values.join(","))?;
+        }
+        Ok(())
+    }
+
+    /*
+        Writes the values of each row on one line separated by `|`, without a header
+     */
+    pub(crate) fn to_list(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        for row in 0..self.len() {
+            let values: Vec<String> = (0..self.fields.len())
+                .map(|column| self.get(row, column).to_string())
+                .collect();
+            writeln!(writer, "{}", values.join("|"))?;
+        }
+        Ok(())
     }
 }
 
 pub struct RowToInsert<'a> {
     pub(crate) field_value_pairs: Vec<(&'a String, &'a Value)>,
     pub(crate) raw_data: RowBytes,
+    // `raw_data` is a row as stored in a cell, its long TEXT values are already in the overflow pages
+    pub(crate) stored: bool,
 }
 
 impl<'a> RowToInsert<'a> {
@@ -297,6 +887,7 @@ impl<'a> RowToInsert<'a> {
         RowToInsert {
             field_value_pairs,
             raw_data: bytes,
+            stored: false,
         }
     }
 
@@ -304,6 +895,10 @@ impl<'a> RowToInsert<'a> {
         field_value_pair: &Vec<(&'a String, &'a Value)>,
         table_meta: &TableStructureMetadata,
     ) -> RowBytes {
+        /*
+            the row starts zeroed so the padding after a short TEXT or BLOB is always the same,
+            RowBytes equality and hash compare the whole buffer
+         */
         let mut data = vec![0; table_meta.row_size];
         let buf = data.as_mut_ptr();
 
@@ -312,18 +907,10 @@ impl<'a> RowToInsert<'a> {
                 let field_meta = table_meta.get_field_metadata(name).unwrap();
                 match value {
                     Value::INT(i) => {
-                        copy_nonoverlapping(
-                            i as *const i32 as *const u8,
-                            buf.add(field_meta.offset),
-                            field_meta.size,
-                        );
+                        copy_nonoverlapping(i.to_le_bytes().as_ptr(), buf.add(field_meta.offset), field_meta.size);
                     }
                     Value::FLOAT(f) => {
-                        copy_nonoverlapping(
-                            f as *const f32 as *const u8,
-                            buf.add(field_meta.offset),
-                            field_meta.size,
-                        );
+                        copy_nonoverlapping(f.to_le_bytes().as_ptr(), buf.add(field_meta.offset), field_meta.size);
                     }
                     Value::BOOL(b) => {
                         copy_nonoverlapping(
@@ -333,7 +920,13 @@ impl<'a> RowToInsert<'a> {
                         );
                     }
                     Value::TEXT(s) => {
-                        copy_nonoverlapping(s.as_ptr(), buf.add(field_meta.offset), s.len());
+                        let len = s.len().min(field_meta.size);
+                        copy_nonoverlapping(s.as_ptr(), buf.add(field_meta.offset), len);
+                    }
+                    Value::BLOB(b) => {
+                        let len = b.len().min(field_meta.size - BLOB_LENGTH_SIZE);
+                        write_uint(buf.add(field_meta.offset), len, BLOB_LENGTH_SIZE);
+                        copy_nonoverlapping(b.as_ptr(), buf.add(field_meta.offset + BLOB_LENGTH_SIZE), len);
                     }
                     Value::ARRAY(_) => {}
                 }
@@ -362,59 +955,161 @@ impl RowValues {
     }
 }
 
-pub fn new_input_buffer() -> String {
-    let mut input = String::new();
-    print!("sql>");
-    loop {
-        std::io::stdout().flush().expect("flush failed!");
-        std::io::stdin().read_line(&mut input).unwrap();
-        if input.trim().ends_with(";") {
-            break;
-        }
-        print!(">")
-    }
-    input.to_lowercase().leak().trim().to_string()
-}
-
 pub struct TableStructureMetadata {
     pub table_name: String,
     pub row_size: usize,
     pub fields_meta_map: HashMap<String, (u32, Rc<FieldMetadata>)>,
     pub fields: Vec<Rc<FieldMetadata>>,
-    pub fields_max_size: usize
+    pub fill_factor: usize,
 }
 
 impl TableStructureMetadata {
+    /*
+        Fails on a schema without columns or with rows of 0 bytes, which no table is created with
+     */
     fn new(
         table_name: &str,
-        fields_metadata: Vec<(String, u32, Rc<FieldMetadata>)>,
-    ) -> TableStructureMetadata {
-        let row_size = fields_metadata
+        fields_metadata: Vec<NamedField>,
+        fill_factor: usize,
+    ) -> Result<TableStructureMetadata, String> {
+        let row_size: usize = fields_metadata
             .iter()
             .map(|(_, _, m)| m.size)
-            .reduce(|a, b| a + b)
-            .unwrap();
+            .sum();
+        if row_size == 0 {
+            return Err(format!(
+                "Metadata of table `{}` describes rows of 0 bytes, the `.frm` file is damaged.",
+                table_name
+            ));
+        }
 
         let fields:Vec<Rc<FieldMetadata>> = fields_metadata
                                                 .iter()
                                                 .map(|(_, _, m)| Rc::clone(m))
                                                 .collect();
 
-        let fields_max_size = fields.iter().map(|f| f.size).max().unwrap();
-
         let fields_meta_map: HashMap<String, (u32, Rc<FieldMetadata>)> = fields_metadata
             .into_iter()
             .map(|(name, offset, m)| (name, (offset, Rc::clone(&m))))
             .collect();
-        TableStructureMetadata {
+        Ok(TableStructureMetadata {
             table_name: table_name.to_string(),
             row_size,
             fields_meta_map,
             fields,
-            fields_max_size
+            fill_factor,
+        })
+    }
+
+    /*
+        The definitions the table was created with
+     */
+    pub fn field_definitions(&self) -> Vec<FieldDefinition> {
+        self.fields
+            .iter()
+            .map(|f| {
+                let mut definition = FieldDefinition::new(
+                    f.data_def.field_name.to_string(),
+                    f.data_def.data_type,
+                    f.data_def.is_primary_key,
+                );
+                definition.set_collation(f.data_def.collation);
+                definition
+            })
+            .collect()
+    }
+
+    pub fn to_create_sql(&self, fill_factor: Option<usize>, variable_rows: bool) -> String {
+        let definitions: Vec<String> = self
+            .fields
+            .iter()
+            .map(|f| {
+                let mut definition = format!("{} {}", f.data_def.field_name, f.data_def.data_type.to_sql());
+                if f.data_def.collation == Collation::NOCASE {
+                    definition.push_str(" COLLATE NOCASE");
+                }
+                if f.data_def.is_primary_key {
+                    definition.push_str(" PRIMARY KEY");
+                }
+                definition
+            })
+            .collect();
+        match fill_factor {
+            Some(fill_factor) if fill_factor != DEFAULT_FILL_FACTOR => format!(
+                "CREATE TABLE {} ({}) WITH (FILLFACTOR = {});",
+                self.table_name,
+                definitions.join(", "),
+                fill_factor
+            ),
+            _ if variable_rows => format!(
+                "CREATE TABLE {} ({}) WITH (ROW_FORMAT = VARIABLE);",
+                self.table_name,
+                definitions.join(", ")
+            ),
+            _ => format!("CREATE TABLE {} ({});", self.table_name, definitions.join(", ")),
         }
     }
 
+    /*
+        Bytes of a row in a cell, fewer than `row_size` when TEXT fields are in overflow pages
+     */
+    pub fn stored_row_size(&self) -> usize {
+        self.fields.iter().map(|f| f.data_def.stored_size()).sum()
+    }
+
+    /*
+        Offset of a field in the row stored in a cell
+     */
+    pub fn stored_offset(&self, field_name: &str) -> Result<usize, String> {
+        let field = self.get_field_metadata(field_name)?;
+        Ok(self.fields.iter().take_while(|f| f.offset < field.offset).map(|f| f.data_def.stored_size()).sum())
+    }
+
+    pub fn has_overflow(&self) -> bool {
+        self.fields.iter().any(|f| f.data_def.is_overflow())
+    }
+
+    /*
+        (name, offset, size) of every field in row order
+     */
+    pub fn layout(&self) -> Vec<(&str, usize, usize)> {
+        self.fields
+            .iter()
+            .map(|f| (f.data_def.field_name.as_str(), f.offset, f.size))
+            .collect()
+    }
+
+    /*
+        The fields read back from `.frm` must be the declared ones in the declared order,
+        packed without padding: each one starts where the previous one ends.
+     */
+    pub(crate) fn check_layout(&self, definitions: &[FieldDefinition]) -> Result<(), String> {
+        let layout = self.layout();
+        if layout.len() != definitions.len() {
+            return Err(format!(
+                "Table `{}` has {} fields but {} were declared.",
+                self.table_name, layout.len(), definitions.len()
+            ));
+        }
+        let mut expected_offset = 0;
+        for ((name, offset, size), definition) in layout.into_iter().zip(definitions) {
+            let expected_size = definition.data_type.get_size();
+            if (name, offset, size) != (definition.field_name.as_str(), expected_offset, expected_size) {
+                return Err(format!(
+                    "Field `{}` of table `{}` is stored as `{}` at {} in {} bytes instead of at {} in {} bytes.",
+                    definition.field_name, self.table_name, name, offset, size, expected_offset, expected_size
+                ));
+            }
+            expected_offset += expected_size;
+        }
+        Ok(())
+    }
+
+    pub fn get_shared_field_metadata(&self, field_name: &str) -> Result<Rc<FieldMetadata>, String> {
+        self.get_field_metadata(field_name)?;
+        Ok(Rc::clone(&self.fields_meta_map[field_name].1))
+    }
+
     pub fn get_field_metadata(&self, field_name: &str) -> Result<&FieldMetadata, String> {
         match self.fields_meta_map.get(field_name) {
             None => Err(format!(
@@ -441,3 +1136,523 @@ impl FieldMetadata {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use crate::build_path;
+    use crate::sql_engine::sql_parser::SqlParser;
+    use crate::sql_engine::sql_structs::{DataType, FieldDefinition, SqlStmt, Value};
+    use crate::storage_engine::common::{SelectRows, StorageType, TableManager};
+    use crate::storage_engine::config::*;
+    use crate::storage_engine::tables::{BtreeTable, SequentialTable};
+    use crate::utils::test_utils::TestDb;
+
+    /*
+        `.frm` of `(id int primary key, name text(20))` with the sizes in `size_width` bytes
+     */
+    fn frm_bytes(size_width: usize) -> Vec<u8> {
+        let mut bytes = 2u16.to_le_bytes().to_vec();
+        for (name, type_primary) in [("id", DataType::INTEGER.to_bit_code() << 1 | 1), ("name", DataType::TEXT(0).to_bit_code() << 1)] {
+            let mut field_name = name.as_bytes().to_vec();
+            field_name.resize(FIELD_NAME_SIZE, 0);
+            bytes.extend(field_name);
+            bytes.push(type_primary);
+        }
+        bytes.extend(&20u32.to_le_bytes()[..size_width]);
+        bytes
+    }
+
+    #[test]
+    fn legacy_frm_sizes_take_two_bytes() {
+        let formats = [
+            (DbFormat { page_size: DEFAULT_PAGE_SIZE, version: LEGACY_FORMAT_VERSION }, LEGACY_TEXT_CHARS_NUM_SIZE),
+            (DbFormat::current(DEFAULT_PAGE_SIZE), TEXT_CHARS_NUM_SIZE),
+        ];
+        for (format, size_width) in formats {
+            let path = std::env::temp_dir().join(format!("common_frm_{}.frm", format.version));
+            std::fs::write(&path, frm_bytes(size_width)).unwrap();
+            let (fields, _) = unsafe { TableManager::load_metadata_from_disk(&path, "t", format).unwrap() };
+            let types: Vec<DataType> = fields.iter().map(|(_, _, meta)| meta.data_def.data_type).collect();
+            assert_eq!(types, vec![DataType::INTEGER, DataType::TEXT(20)]);
+        }
+    }
+
+    #[test]
+    fn corrupt_page_is_an_error() {
+        let mut db = TestDb::open(&["common_corrupt"]);
+        db.run("create table common_corrupt (id int primary key, name text(16));").unwrap();
+        for id in 0..10 {
+            db.run(&format!("insert into common_corrupt (id, name) values ({}, 'row {}');", id, id)).unwrap();
+        }
+        db.reopen();
+
+        let path = build_path!(DATA_FOLDER, "common_corrupt", "common_corrupt.idx");
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[BTREE_METADATA_SIZE + LEAF_NODE_BODY_OFFSET] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+
+        db.reopen();
+        let error = db.run("select * from common_corrupt;").err().unwrap();
+        assert!(error.contains("Page 0 failed its checksum verification"), "{}", error);
+        db.run("drop table common_corrupt;").unwrap();
+    }
+
+    #[test]
+    fn reordered_create_keeps_the_declared_layout() {
+        let mut db = TestDb::open(&["common_layout_a", "common_layout_b"]);
+        db.run("create table common_layout_a (flag bool, id int primary key, name text(10), price float);").unwrap();
+        db.run("create table common_layout_b (name text(10), price float, flag bool, id int primary key);").unwrap();
+        db.reopen();
+
+        let layout_a: Vec<(String, usize, usize)> = db.table_manager.get_table_metadata("common_layout_a").unwrap()
+            .layout().into_iter().map(|(name, offset, size)| (name.to_string(), offset, size)).collect();
+        let layout_b = db.table_manager.get_table_metadata("common_layout_b").unwrap().layout();
+        assert_eq!(layout_a, [("flag".to_string(), 0, 1), ("id".to_string(), 1, 4), ("name".to_string(), 5, 10), ("price".to_string(), 15, 4)]);
+        assert_eq!(layout_b, [("name", 0, 10), ("price", 10, 4), ("flag", 14, 1), ("id", 15, 4)]);
+
+        let declared_a = [
+            FieldDefinition::new(String::from("flag"), DataType::BOOLEAN, false),
+            FieldDefinition::new(String::from("id"), DataType::INTEGER, true),
+            FieldDefinition::new(String::from("name"), DataType::TEXT(10), false),
+            FieldDefinition::new(String::from("price"), DataType::FLOAT, false),
+        ];
+        let metadata_b = db.table_manager.get_table_metadata("common_layout_b").unwrap();
+        assert_eq!(
+            metadata_b.check_layout(&declared_a).unwrap_err(),
+            "Field `flag` of table `common_layout_b` is stored as `name` at 0 in 10 bytes instead of at 0 in 1 bytes."
+        );
+        assert!(db.table_manager.get_table_metadata("common_layout_a").unwrap().check_layout(&declared_a).is_ok());
+    }
+
+    #[test]
+    fn dump_replays_into_the_same_table() {
+        let mut db = TestDb::open(&["common_dump_keyed", "common_dump_seq"]);
+        db.run("create table common_dump_keyed (id int primary key, name text(16), price float, flag bool, b blob(4)) with (fillfactor = 70);").unwrap();
+        db.run("insert into common_dump_keyed values (2, 'it''s', 1.5, true, x'0aff');").unwrap();
+        db.run("insert into common_dump_keyed values (1, '', 3.0, false, x'00');").unwrap();
+        db.run("create table common_dump_seq (id int, name text(16)) with (row_format = variable);").unwrap();
+        db.run("insert into common_dump_seq values (1, 'a, b');").unwrap();
+
+        for table in ["common_dump_keyed", "common_dump_seq"] {
+            let select = format!("select * from {};", table);
+            let rows = db.query(&select);
+            let dump = db.table_manager.dump_table(table).unwrap();
+            db.run(&format!("drop table {};", table)).unwrap();
+            for statement in dump.iter() {
+                db.run(statement).unwrap();
+            }
+            db.reopen();
+            assert_eq!(db.query(&select), rows);
+            assert_eq!(db.table_manager.dump_table(table).unwrap(), dump);
+        }
+    }
+
+    /*
+        the test data folder has 4096-byte pages, the tables of a session opened with
+        another format are written with its page size
+     */
+    fn reopen_with_format(db: &mut TestDb, format: DbFormat) {
+        db.table_manager.flush_to_disk();
+        db.table_manager = TableManager::new(None).unwrap();
+        db.table_manager.format = format;
+    }
+
+    #[test]
+    fn larger_pages_hold_more_rows() {
+        let mut db = TestDb::open(&["common_large_pages_btree", "common_large_pages_seq"]);
+        let format = DbFormat::current(8192);
+        reopen_with_format(&mut db, format);
+        db.run("create table common_large_pages_btree (id int primary key, name text(200));").unwrap();
+        db.run("create table common_large_pages_seq (id int, name text(200));").unwrap();
+        for id in 0..200 {
+            for table in ["common_large_pages_btree", "common_large_pages_seq"] {
+                db.run(&format!("insert into {} (id, name) values ({}, 'row {}');", table, id, id)).unwrap();
+            }
+        }
+        reopen_with_format(&mut db, format);
+
+        for (table, extension, header_size) in [
+            ("common_large_pages_btree", ".idx", BTREE_METADATA_SIZE),
+            ("common_large_pages_seq", ".seq", SEQUENTIAL_NODE_HEADER_SIZE),
+        ] {
+            let size = std::fs::metadata(build_path!(DATA_FOLDER, table, table.to_owned() + extension)).unwrap().len() as usize;
+            assert_eq!((size - header_size) % 8192, 0);
+            assert!((size - header_size) / 8192 > 2, "{} bytes", size);
+            assert_eq!(db.query(&format!("select count(*) from {};", table)), vec![vec![Value::INT(200)]]);
+            assert_eq!(
+                db.query(&format!("select name from {} where id = 150;", table)),
+                vec![vec![Value::TEXT(String::from("row 150"))]]
+            );
+        }
+        db.table_manager.verify("common_large_pages_btree").unwrap();
+        db.run("drop table common_large_pages_btree;").unwrap();
+        db.run("drop table common_large_pages_seq;").unwrap();
+    }
+
+    #[test]
+    fn storage_files_are_dispatched_by_extension() {
+        let extension = |file_name: &str| StorageType::of_file(file_name).map(|s| s.extension);
+        assert_eq!(extension("t.idx"), Some(".idx"));
+        assert_eq!(extension("t.seq"), Some(".seq"));
+        for file_name in ["t.frm", "t.ovf", "t.idx.tmp"] {
+            assert_eq!(extension(file_name), None);
+        }
+
+        let mut db = TestDb::open(&["common_dispatch_btree", "common_dispatch_seq"]);
+        db.run("create table common_dispatch_btree (id int primary key, note text(2000));").unwrap();
+        db.run("create table common_dispatch_seq (id int, note text(8));").unwrap();
+        db.reopen();
+        // the `.frm` and `.ovf` files of a table are not tables
+        assert_eq!(db.table_manager.get_tables("common_dispatch_btree").unwrap().len(), 1);
+
+        let meta = Rc::clone(&db.table_manager.tables["common_dispatch_btree"].0);
+        let folder = build_path!(DATA_FOLDER, "common_dispatch_btree");
+        let btree = TableManager::load_table(&folder.join("common_dispatch_btree.idx"), Rc::clone(&meta), db.table_manager.format).unwrap();
+        assert!(btree.as_any().downcast_ref::<BtreeTable>().is_some());
+        let error = TableManager::load_table(&folder.join("common_dispatch_btree.frm"), meta, db.table_manager.format).err().unwrap();
+        assert_eq!(error, "`common_dispatch_btree.frm` is not a storage file.");
+
+        db.table_manager.get_tables("common_dispatch_seq").unwrap();
+        let meta = Rc::clone(&db.table_manager.tables["common_dispatch_seq"].0);
+        let path = build_path!(DATA_FOLDER, "common_dispatch_seq", "common_dispatch_seq.seq");
+        let seq = TableManager::load_table(&path, meta, db.table_manager.format).unwrap();
+        assert!(seq.as_any().downcast_ref::<SequentialTable>().is_some());
+    }
+
+    #[test]
+    fn reindex_keeps_the_overflow_pages_of_the_rows() {
+        let mut db = TestDb::open(&["common_reindex"]);
+        db.run("create table common_reindex (id int primary key, note text(3000), tag text(8));").unwrap();
+        for id in 0..400 {
+            let note = "n".repeat(50 + id % 400);
+            db.run(&format!("insert into common_reindex (id, note, tag) values ({}, '{}', 't{}');", id, note, id)).unwrap();
+        }
+        db.reopen();
+        let scan = db.query("select * from common_reindex;");
+        let overflow_path = build_path!(DATA_FOLDER, "common_reindex", "common_reindex.ovf");
+        let overflow_size = fs::metadata(&overflow_path).unwrap().len();
+
+        // the first key of the root sends the lookups of the smallest keys to the wrong child
+        let btree = db.table_manager.get_tables("common_reindex").unwrap()[0].as_any().downcast_ref::<BtreeTable>().unwrap();
+        let root = btree.pager.get_page(btree.root_page_index) as *mut u8;
+        btree.pager.set_internal_node_cell_key(root, 0, btree.key_size, &Value::INT(-1));
+        assert!(db.table_manager.verify("common_reindex").is_err());
+        assert!(db.query("select tag from common_reindex where id = 5;").is_empty());
+
+        // the rebuilt cells point to the stored values, the overflow pages do not grow
+        for _ in 0..3 {
+            db.run("reindex common_reindex;").unwrap();
+            assert_eq!(fs::metadata(&overflow_path).unwrap().len(), overflow_size);
+        }
+        db.table_manager.verify("common_reindex").unwrap();
+        assert_eq!(db.query("select * from common_reindex;"), scan);
+        for id in [0, 5, 399] {
+            assert_eq!(
+                db.query(&format!("select * from common_reindex where id = {};", id)),
+                vec![scan[id].clone()]
+            );
+        }
+    }
+
+    #[test]
+    fn backup_copies_the_flushed_rows() {
+        let mut db = TestDb::open(&["common_backup_btree", "common_backup_seq"]);
+        let destination = "./target/test_backup";
+        let _ = fs::remove_dir_all(destination);
+        db.run("create table common_backup_btree (id int primary key, note text(2000));").unwrap();
+        db.run("create table common_backup_seq (id int, name text(8));").unwrap();
+        // the rows of the last insert are still in the page cache
+        db.run("pragma autocommit = off;").unwrap();
+        for id in 0..300 {
+            db.run(&format!("insert into common_backup_btree (id, note) values ({}, '{}');", id, "n".repeat(id * 5))).unwrap();
+            db.run(&format!("insert into common_backup_seq (id, name) values ({}, 's{}');", id, id)).unwrap();
+        }
+
+        assert_eq!(db.table_manager.backup("./target/missing", destination).err().unwrap(), "Backup source `./target/missing` is not a directory.");
+        assert!(db.table_manager.backup(DATA_FOLDER, &build_path!(DATA_FOLDER, "copy").to_string_lossy()).is_err());
+        assert!(db.table_manager.backup(DATA_FOLDER, destination).unwrap() > 0);
+        assert!(db.table_manager.backup(DATA_FOLDER, destination).is_err());
+
+        // the copied storage files are opened with the metadata of the tables and hold the same rows
+        for (table, extension) in [("common_backup_btree", ".idx"), ("common_backup_seq", ".seq")] {
+            let meta = Rc::clone(&db.table_manager.tables[table].0);
+            let copy = build_path!(destination, table, table.to_owned() + extension);
+            let copied_rows = TableManager::load_table(&copy, meta, db.table_manager.format).unwrap().get_all();
+            let rows = db.table_manager.get_base_table(table).unwrap().get_all();
+            assert_eq!(copied_rows.len(), 300);
+            assert!(copied_rows.iter().zip(rows.iter()).all(|(copied, row)| copied.data == row.data));
+        }
+        fs::remove_dir_all(destination).unwrap();
+    }
+
+    #[test]
+    fn repair_drops_the_bytes_after_the_last_page() {
+        let mut db = TestDb::open(&["common_repair", "common_repair_seq"]);
+        db.run("create table common_repair (id int primary key, name text(16));").unwrap();
+        db.run("create table common_repair_seq (id int, name text(16));").unwrap();
+        for id in 0..200 {
+            for table in ["common_repair", "common_repair_seq"] {
+                db.run(&format!("insert into {} (id, name) values ({}, 'row {}');", table, id, id)).unwrap();
+            }
+        }
+        db.reopen();
+
+        for (table, file) in [("common_repair", "common_repair.idx"), ("common_repair_seq", "common_repair_seq.seq")] {
+            let select = format!("select * from {};", table);
+            let rows = db.query(&select);
+            db.reopen();
+
+            let path = build_path!(DATA_FOLDER, table, file);
+            let mut bytes = fs::read(&path).unwrap();
+            bytes.extend([0xab; 10]);
+            fs::write(&path, bytes).unwrap();
+            let error = db.run(&select).err().unwrap();
+            assert!(error.contains("not a whole number of pages"), "{}", error);
+
+            assert_eq!(db.table_manager.repair(table).unwrap(), vec![format!("{}: discarded 10 bytes.", file)]);
+            assert_eq!(db.query(&select), rows);
+        }
+    }
+
+    #[test]
+    fn for_each_row_visits_every_row() {
+        let mut db = TestDb::open(&["common_each", "common_each_seq"]);
+        db.run("create table common_each (id int primary key, name text(32));").unwrap();
+        db.run("create table common_each_seq (id int, name text(32));").unwrap();
+        for id in 0..300 {
+            for table in ["common_each", "common_each_seq"] {
+                db.run(&format!("insert into {} (id, name) values ({}, 'row {}');", table, (id * 7) % 300, id)).unwrap();
+            }
+        }
+
+        for table in ["common_each", "common_each_seq"] {
+            let mut count = 0;
+            let mut bytes = 0;
+            db.table_manager.for_each_row(table, |row| {
+                count += 1;
+                bytes += row.len();
+            }).unwrap();
+            let all = db.table_manager.get_tables(table).unwrap()[0].get_all();
+            assert_eq!(count, 300);
+            assert_eq!(count, all.len());
+            assert_eq!(bytes, all.iter().map(|row| row.len()).sum::<usize>());
+        }
+        assert!(db.table_manager.for_each_row("common_each_missing", |_| {}).is_err());
+    }
+
+    #[test]
+    fn cache_keeps_at_most_max_cached_tables() {
+        let tables: Vec<String> = (0..MAX_CACHED_TABLES + 3).map(|i| format!("common_cache_{}", i)).collect();
+        let mut db = TestDb::open(&tables.iter().map(|t| t.as_str()).collect::<Vec<_>>());
+        for (i, table) in tables.iter().enumerate() {
+            db.run(&format!("create table {} (id int primary key, n int);", table)).unwrap();
+            db.run(&format!("insert into {} (id, n) values (1, {});", table, i)).unwrap();
+            assert!(db.table_manager.tables.len() <= MAX_CACHED_TABLES);
+        }
+
+        // a closed table is loaded again with its rows
+        for (i, table) in tables.iter().enumerate().rev() {
+            assert_eq!(db.query(&format!("select n from {};", table)), vec![vec![Value::INT(i as i32)]]);
+            assert!(db.table_manager.tables.len() <= MAX_CACHED_TABLES);
+        }
+        assert_eq!(db.table_manager.recently_used.back().unwrap(), &tables[0]);
+        assert!(!db.table_manager.tables.contains_key(tables.last().unwrap()));
+    }
+
+    #[test]
+    fn lazy_result_reads_like_a_decoded_one() {
+        let mut db = TestDb::open(&["common_lazy"]);
+        db.run("create table common_lazy (id int primary key, name text(8), price float, ok bool, b blob(2));").unwrap();
+        for id in 1..=3 {
+            db.run(&format!("insert into common_lazy (id, name, price, ok, b) values ({}, 'n{}', {}.5, {}, x'0{}');", id, id, id, id % 2 == 0, id)).unwrap();
+        }
+
+        // nothing to sort or compute, the rows are decoded when read
+        let lazy = db.run("select name, id, price, ok, b from common_lazy;").unwrap().unwrap();
+        assert!(matches!(lazy.rows, SelectRows::LAZY(_, _)));
+        let decoded = db.run("select name, id, price, ok, b from common_lazy order by name;").unwrap().unwrap();
+        assert!(matches!(decoded.rows, SelectRows::DECODED(_)));
+
+        assert_eq!(lazy.fields, decoded.fields);
+        assert_eq!(lazy.len(), 3);
+        assert_eq!(lazy.len(), decoded.len());
+        for row in 0..lazy.len() {
+            for column in 0..lazy.fields.len() {
+                assert_eq!(lazy.get(row, column).to_string(), decoded.get(row, column).to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn autocommit_writes_each_insert() {
+        let mut db = TestDb::open(&["common_autocommit", "common_batch"]);
+        db.run("create table common_autocommit (id int primary key, n int);").unwrap();
+        db.run("create table common_batch (id int primary key, n int);").unwrap();
+        db.table_manager.flush_to_disk();
+
+        db.run("insert into common_autocommit (id, n) values (1, 10);").unwrap();
+        db.table_manager.set_setting("autocommit", &Value::BOOL(false)).unwrap();
+        db.run("insert into common_batch (id, n) values (1, 10);").unwrap();
+
+        // another session only reads what is on disk, nothing was flushed explicitly
+        let mut other = TableManager::new(None).unwrap();
+        assert_eq!(other.get_tables("common_autocommit").unwrap()[0].get_all().len(), 1);
+        assert_eq!(other.get_tables("common_batch").unwrap()[0].get_all().len(), 0);
+        drop(other);
+
+        db.table_manager.flush_to_disk();
+        let mut other = TableManager::new(None).unwrap();
+        assert_eq!(other.get_tables("common_batch").unwrap()[0].get_all().len(), 1);
+    }
+
+    #[test]
+    fn create_sql_parses_back_to_the_same_create() {
+        let mut db = TestDb::open(&["common_ddl", "common_ddl_seq"]);
+        let creates = [
+            ("common_ddl", "create table common_ddl (name text, id int primary key, price float, ok bool, b blob(3));"),
+            ("common_ddl_seq", "create table common_ddl_seq (id int, code text(12));"),
+        ];
+        for (table, sql) in creates {
+            db.run(sql).unwrap();
+            db.reopen();
+            let ddl = db.table_manager.create_sql(table).unwrap();
+            let parse = |sql: &str| match SqlParser::parse_sql(sql.to_string()).unwrap() {
+                SqlStmt::CREATE(create) => create,
+                _ => panic!("`{}` is not a create", sql),
+            };
+            assert_eq!(parse(&ddl), parse(sql), "{}", ddl);
+        }
+        assert_eq!(
+            db.table_manager.create_sql("common_ddl_seq").unwrap(),
+            "CREATE TABLE common_ddl_seq (id INT, code TEXT(12));"
+        );
+    }
+
+    #[test]
+    fn insert_row_takes_field_value_pairs() {
+        let mut db = TestDb::open(&["common_insert_row"]);
+        db.run("create table common_insert_row (id int primary key, name text(8), price float);").unwrap();
+        db.table_manager
+          .insert_row("common_insert_row", &[("name", Value::TEXT(String::from("it's"))), ("price", Value::FLOAT(1.5)), ("id", Value::INT(2))])
+          .unwrap();
+        db.table_manager
+          .insert_row("common_insert_row", &[("id", Value::INT(1)), ("name", Value::TEXT(String::new())), ("price", Value::FLOAT(-2.0))])
+          .unwrap();
+
+        assert_eq!(
+            db.query("select id, name, price from common_insert_row;"),
+            vec![
+                vec![Value::INT(1), Value::TEXT(String::new()), Value::FLOAT(-2.0)],
+                vec![Value::INT(2), Value::TEXT(String::from("it's")), Value::FLOAT(1.5)],
+            ]
+        );
+        // the checks of an INSERT statement apply
+        assert!(db.table_manager.insert_row("common_insert_row", &[("id", Value::INT(3)), ("name", Value::TEXT(String::from("c")))]).is_err());
+        assert!(db.table_manager.insert_row("common_insert_row_missing", &[("id", Value::INT(1))]).is_err());
+    }
+
+    #[test]
+    fn dropped_table_manager_flushes_its_tables() {
+        let mut db = TestDb::open(&["common_drop_flush"]);
+        db.run("create table common_drop_flush (id int primary key, n int);").unwrap();
+        db.table_manager.set_setting("autocommit", &Value::BOOL(false)).unwrap();
+        for id in 0..3 {
+            db.run(&format!("insert into common_drop_flush (id, n) values ({}, {});", id, id * 10)).unwrap();
+        }
+        // an explicit flush can come before the one run by the drop
+        db.table_manager.flush_to_disk();
+        db.run("insert into common_drop_flush (id, n) values (3, 30);").unwrap();
+
+        // the row inserted after the flush is written when the old manager is dropped
+        db.table_manager = TableManager::new(None).unwrap();
+        assert_eq!(
+            db.query("select n from common_drop_flush;"),
+            vec![vec![Value::INT(0)], vec![Value::INT(10)], vec![Value::INT(20)], vec![Value::INT(30)]]
+        );
+    }
+
+    #[test]
+    fn full_scan_reads_the_base_table_only() {
+        let mut db = TestDb::open(&["common_base"]);
+        db.run("create table common_base (id int primary key, n int);").unwrap();
+        for id in 0..2 {
+            db.run(&format!("insert into common_base (id, n) values ({}, {});", id, id)).unwrap();
+        }
+        db.reopen();
+        let index = build_path!(DATA_FOLDER, "common_base", "common_base.idx");
+        let mut stale_index = fs::read(&index).unwrap();
+        for id in 2..5 {
+            db.run(&format!("insert into common_base (id, n) values ({}, {});", id, id)).unwrap();
+        }
+        db.reopen();
+
+        // a second, non primary B-tree over `id` holding the first two rows only
+        stale_index[0] &= !1;
+        fs::write(build_path!(DATA_FOLDER, "common_base", "a_common_base_id.idx"), stale_index).unwrap();
+        db.reopen();
+
+        assert_eq!(db.table_manager.get_tables("common_base").unwrap().len(), 2);
+        let ids: Vec<Value> = (0..5).map(Value::INT).collect();
+        assert_eq!(db.query("select id from common_base;").concat(), ids);
+        assert_eq!(db.query("select id from common_base where n >= 0;").concat(), ids);
+        assert_eq!(db.table_manager.get_base_table("common_base").unwrap().get_all().len(), 5);
+        db.run("drop table common_base;").unwrap();
+    }
+
+    #[test]
+    fn table_without_data_file_is_reported_and_repaired() {
+        let mut db = TestDb::open(&["common_frm_only"]);
+        db.run("create table common_frm_only (id int primary key, name text(8));").unwrap();
+        db.run("insert into common_frm_only (id, name) values (1, 'lost');").unwrap();
+        db.reopen();
+        fs::remove_file(build_path!(DATA_FOLDER, "common_frm_only", "common_frm_only.idx")).unwrap();
+
+        let error = db.run("select * from common_frm_only;").err().unwrap();
+        assert!(error.contains("is incomplete"), "{}", error);
+
+        let report = db.table_manager.repair("common_frm_only").unwrap();
+        assert_eq!(report, vec!["common_frm_only.idx: recreated empty.".to_string()]);
+        assert!(db.query("select * from common_frm_only;").is_empty());
+        db.run("insert into common_frm_only (id, name) values (2, 'new');").unwrap();
+        assert_eq!(db.query("select id from common_frm_only;").concat(), vec![Value::INT(2)]);
+    }
+
+    #[test]
+    fn json_output_is_an_array_of_objects() {
+        let mut db = TestDb::open(&["common_json"]);
+        db.run("create table common_json (id int primary key, name text(16), ok bool, price float);").unwrap();
+        db.run("insert into common_json (id, name, ok, price) values (1, 'say \"hi\"', true, 1.5);").unwrap();
+        db.run("insert into common_json (id, name, ok, price) values (2, 'a\\b', false, -2);").unwrap();
+
+        let mut json = vec![];
+        db.run("select * from common_json;").unwrap().unwrap().to_json(&mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "[\n  {\"id\": 1, \"name\": \"say \\\"hi\\\"\", \"ok\": true, \"price\": 1.5},\
+             \n  {\"id\": 2, \"name\": \"a\\\\b\", \"ok\": false, \"price\": -2}\n]\n"
+        );
+
+        let mut json = vec![];
+        db.run("select id from common_json where id > 2;").unwrap().unwrap().to_json(&mut json).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn table_without_columns_is_an_error() {
+        let mut db = TestDb::open(&["common_no_columns"]);
+        let error = db.run("create table common_no_columns ();").err().unwrap();
+        assert!(error.contains("a table needs at least one column"), "{}", error);
+        assert!(!Path::new(&build_path!(DATA_FOLDER, "common_no_columns")).exists());
+
+        // a damaged `.frm` without fields
+        db.run("create table common_no_columns (id int primary key);").unwrap();
+        db.reopen();
+        fs::write(build_path!(DATA_FOLDER, "common_no_columns", "common_no_columns.frm"), 0u16.to_le_bytes()).unwrap();
+        let error = db.run("select * from common_no_columns;").err().unwrap();
+        assert!(error.contains("describes rows of 0 bytes"), "{}", error);
+    }
+}
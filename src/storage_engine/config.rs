@@ -8,22 +8,46 @@ pub struct BtreeLeafNodeBodyLayout {
     pub leaf_node_max_cells: usize,
     pub leaf_node_right_split_count: usize,
     pub leaf_node_left_split_count: usize,
+    pub internal_node_cell_size: usize,
 }
 
 impl BtreeLeafNodeBodyLayout {
-    pub(crate) fn new(key_size: usize, row_size: usize) -> BtreeLeafNodeBodyLayout {
+    pub(crate) fn new(
+        key_size: usize,
+        row_size: usize,
+        format: DbFormat,
+        fill_factor: usize,
+    ) -> Result<BtreeLeafNodeBodyLayout, String> {
         let leaf_node_key_size: usize = key_size;
         let leaf_node_key_offset: usize = 0;
         let leaf_node_value_size: usize = row_size;
         let leaf_node_value_offset: usize = leaf_node_key_offset + leaf_node_key_size;
         let leaf_node_cell_size: usize = leaf_node_key_size + leaf_node_value_size;
-        let leaf_node_space_for_cells: usize = PAGE_SIZE - LEAF_NODE_HEADER_SIZE;
+        let leaf_node_space_for_cells: usize =
+            format.page_size - BTREE_METADATA_SIZE - LEAF_NODE_HEADER_SIZE - format.checksum_size();
         let leaf_node_max_cells: usize = leaf_node_space_for_cells / leaf_node_cell_size;
-        let leaf_node_right_split_count: usize = (leaf_node_max_cells + 1) / 2;
+        if leaf_node_max_cells == 0 {
+            return Err(format!(
+                "Row size of {} bytes with a key of {} bytes exceeds the {} bytes available for cells in a page.",
+                row_size, key_size, leaf_node_space_for_cells
+            ));
+        }
+        // `fill_factor` percent of the cells stay in the old (left) leaf, both leaves keep at least one cell
         let leaf_node_left_split_count: usize =
-            (leaf_node_max_cells + 1) - leaf_node_right_split_count;
+            ((leaf_node_max_cells + 1) * fill_factor / 100).clamp(1, leaf_node_max_cells);
+        let leaf_node_right_split_count: usize =
+            (leaf_node_max_cells + 1) - leaf_node_left_split_count;
+
+        let internal_node_cell_size: usize = INTERNAL_NODE_CHILD_SIZE + key_size.max(INTERNAL_NODE_KEY_SIZE);
+        let internal_node_space_for_cells: usize = format.page_size - INTERNAL_NODE_BODY_OFFSET - format.checksum_size();
+        if INTERNAL_NODE_MAX_KEYS * internal_node_cell_size > internal_node_space_for_cells {
+            return Err(format!(
+                "Key of {} bytes exceeds the {} bytes available for the {} keys of an internal node.",
+                key_size, internal_node_space_for_cells, INTERNAL_NODE_MAX_KEYS
+            ));
+        }
 
-        BtreeLeafNodeBodyLayout {
+        Ok(BtreeLeafNodeBodyLayout {
             leaf_node_key_size,
             leaf_node_key_offset,
             leaf_node_value_size,
@@ -33,12 +57,71 @@ impl BtreeLeafNodeBodyLayout {
             leaf_node_max_cells,
             leaf_node_right_split_count,
             leaf_node_left_split_count,
-        }
+            internal_node_cell_size,
+        })
     }
 }
 
-pub const PAGE_SIZE: usize = 4096;
+// page size of databases created without an explicit one
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+pub const MIN_PAGE_SIZE: usize = 1024;
+pub const MAX_PAGE_SIZE: usize = 65536;
 pub const TABLE_MAX_PAGES: usize = 100;
+// tables kept open by the TableManager, the least recently used one is closed beyond that
+pub const MAX_CACHED_TABLES: usize = 16;
+
+/*
+* Database Header
+* stored in DATA_FOLDER, holds the page size shared by every table of the database
+* followed by the format version of the files. A data folder without header is in LEGACY_FORMAT_VERSION.
+*/
+pub const DB_HEADER_FILE: &str = "db.header";
+pub const DB_PAGE_SIZE_SIZE: usize = std::mem::size_of::<u32>();
+pub const DB_FORMAT_VERSION_SIZE: usize = std::mem::size_of::<u32>();
+pub const DB_HEADER_SIZE: usize = DB_PAGE_SIZE_SIZE + DB_FORMAT_VERSION_SIZE;
+// pages without checksum footer, TEXT and BLOB sizes stored in LEGACY_TEXT_CHARS_NUM_SIZE bytes
+pub const LEGACY_FORMAT_VERSION: u32 = 0;
+// every page ends with its checksum, TEXT and BLOB sizes stored in TEXT_CHARS_NUM_SIZE bytes
+pub const CURRENT_FORMAT_VERSION: u32 = 1;
+
+/*
+    Layout shared by the files of a database, read from its header
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct DbFormat {
+    pub page_size: usize,
+    pub version: u32,
+}
+
+impl DbFormat {
+    pub(crate) fn current(page_size: usize) -> DbFormat {
+        DbFormat { page_size, version: CURRENT_FORMAT_VERSION }
+    }
+
+    // bytes at the end of a page holding its checksum, the pages of a legacy database have none
+    pub(crate) fn checksum_size(&self) -> usize {
+        if self.version == LEGACY_FORMAT_VERSION {
+            0
+        } else {
+            PAGE_CHECKSUM_SIZE
+        }
+    }
+
+    // bytes of the declared size of a TEXT or BLOB field in `.frm`
+    pub(crate) fn text_chars_num_size(&self) -> usize {
+        if self.version == LEGACY_FORMAT_VERSION {
+            LEGACY_TEXT_CHARS_NUM_SIZE
+        } else {
+            TEXT_CHARS_NUM_SIZE
+        }
+    }
+}
+
+/*
+* Page Footer Layout
+* every page ends with a CRC32 of the bytes before it, written when the page is flushed
+*/
+pub const PAGE_CHECKSUM_SIZE: usize = std::mem::size_of::<u32>();
 
 /*
  * Btree Table Metadata
@@ -50,6 +133,10 @@ pub const INDEXED_FIELD_NAME_SIZE: usize = FIELD_NAME_SIZE;
 pub const INDEXED_FIELD_NAME_SIZE_OFFSET: usize = INDEXED_FIELD_SIZE_OFFSET + INDEXED_FIELD_SIZE;
 pub const BTREE_METADATA_SIZE: usize =
     INDEXED_FIELD_TYPE_PRIMARY + INDEXED_FIELD_SIZE + INDEXED_FIELD_NAME_SIZE;
+// percentage of the cells kept in the left leaf when a leaf splits, `WITH (FILLFACTOR = n)`
+pub const DEFAULT_FILL_FACTOR: usize = 50;
+pub const MIN_FILL_FACTOR: usize = 10;
+pub const MAX_FILL_FACTOR: usize = 90;
 
 /*
 * Common Node Header Layout
@@ -87,8 +174,8 @@ pub const INTERNAL_NODE_BODY_OFFSET: usize =
 * Internal Node Body Layout
 */
 pub const INTERNAL_NODE_CHILD_SIZE: usize = std::mem::size_of::<u32>();
+// a key takes at least these bytes in an internal cell, a longer TEXT key takes its own size
 pub const INTERNAL_NODE_KEY_SIZE: usize = std::mem::size_of::<u32>();
-pub const INTERNAL_NODE_CELL_SIZE: usize = INTERNAL_NODE_CHILD_SIZE + INTERNAL_NODE_KEY_SIZE;
 pub const INTERNAL_NODE_MAX_KEYS: usize = 3;
 pub const INVALID_PAGE_NUM: usize = u32::MAX as usize;
 
@@ -105,23 +192,76 @@ the following 2 bits for data
 01 -> int
 10 -> float
 11 -> boolean
+100 -> blob
+the bit after the data type is set for a TEXT field with COLLATE NOCASE
  */
 pub const FIELD_TYPE_PRIMARY_SIZE: usize = 1;
+pub const FIELD_DATA_TYPE_MASK: u8 = 0b0000_0111;
+pub const FIELD_NOCASE_BIT: u8 = 0b0001_0000;
 pub const FIELD_NAME_SIZE: usize = 64;
+// the fill factor follows the fields, a `.frm` file without it takes DEFAULT_FILL_FACTOR
+pub const FRM_FILL_FACTOR_SIZE: usize = std::mem::size_of::<u8>();
+// deepest nesting of parentheses the parser accepts, a deeper statement is an error rather than a stack overflow
+pub const MAX_NESTING_DEPTH: usize = 64;
 
 // by default, Text can have at max 255 bytes.
 pub const TEXT_DEFAULT_SIZE: usize = 255;
+pub const BLOB_DEFAULT_SIZE: usize = 255;
+// a Blob value is stored as its length followed by the raw bytes
+pub const BLOB_LENGTH_SIZE: usize = 2;
 
 // number of bytes used to store each data type size in metadata file
-// if the type is TEXT, there is an additional byte that represents the max size of this text
-pub const TEXT_CHARS_NUM_SIZE: usize = 2;
+// if the type is TEXT or BLOB, there are additional bytes that represent its max size
+pub const TEXT_CHARS_NUM_SIZE: usize = std::mem::size_of::<u32>();
+pub const LEGACY_TEXT_CHARS_NUM_SIZE: usize = std::mem::size_of::<u16>();
 pub const INTEGER_SIZE: usize = 4;
 pub const FLOAT_SIZE: usize = 4;
 pub const BOOLEAN_SIZE: usize = 1;
 
+#[cfg(not(test))]
 pub const DATA_FOLDER: &str = "./data";
+// the unit tests never touch the data of a real database
+#[cfg(test)]
+pub const DATA_FOLDER: &str = "./target/test_data";
 
 // Sequential Page Header
 pub const SEQUENTIAL_CELLS_NUM_SIZE: usize = 4;
 pub const SEQUENTIAL_NODE_HEADER_SIZE: usize = SEQUENTIAL_CELLS_NUM_SIZE;
 pub const SEQUENTIAL_NODE_BODY_OFFSET: usize = SEQUENTIAL_NODE_HEADER_SIZE;
+// `WITH (ROW_FORMAT = VARIABLE)`: a cell is the length of the encoded row followed by the row,
+// a TEXT field is stored as its length followed by its real bytes
+pub const VARIABLE_ROW_LENGTH_SIZE: usize = 2;
+pub const VARIABLE_TEXT_LENGTH_SIZE: usize = 2;
+// written in place of the number of cells by page in the header of a `.seq` file
+pub const VARIABLE_ROW_FORMAT: usize = 0;
+
+pub(crate) fn sequential_cells_num_by_page(format: DbFormat, row_size: usize) -> Result<usize, String> {
+    let space_for_cells = format.page_size - SEQUENTIAL_NODE_BODY_OFFSET - format.checksum_size();
+    if row_size == 0 {
+        return Err(String::from("A row of 0 bytes can not be stored."));
+    }
+    if row_size > space_for_cells {
+        return Err(format!(
+            "Row size of {} bytes exceeds the {} bytes available for cells in a page.",
+            row_size, space_for_cells
+        ));
+    }
+    Ok(space_for_cells / row_size)
+}
+
+/*
+* Overflow Pages
+* a TEXT field declared longer than TEXT_INLINE_MAX_SIZE is stored in the `.ovf` file of its table.
+* Values are appended one after the other, a value continues on the next page when a page is full.
+* The cell holds the position of the value: its first page, its offset in that page and its length.
+*/
+pub const TEXT_INLINE_MAX_SIZE: usize = 1024;
+pub const OVERFLOW_EXTENSION: &str = ".ovf";
+// every overflow page starts with the number of its bytes in use
+pub const OVERFLOW_PAGE_USED_SIZE: usize = 4;
+pub const OVERFLOW_PAGE_BODY_OFFSET: usize = OVERFLOW_PAGE_USED_SIZE;
+pub const OVERFLOW_POINTER_PAGE_SIZE: usize = 4;
+pub const OVERFLOW_POINTER_OFFSET_SIZE: usize = 4;
+pub const OVERFLOW_POINTER_LENGTH_SIZE: usize = 4;
+pub const OVERFLOW_POINTER_SIZE: usize =
+    OVERFLOW_POINTER_PAGE_SIZE + OVERFLOW_POINTER_OFFSET_SIZE + OVERFLOW_POINTER_LENGTH_SIZE;
@@ -53,9 +53,8 @@ impl<'a> WriteReadCursor<'a> {
         }
     }
 
-    pub(crate) fn cursor_value(&mut self) -> *mut u8 {
-        self.table
-            .get_row_value_mut(self.page_index, self.cell_index)
+    pub(crate) fn cursor_value(&self) -> *const u8 {
+        self.table.get_row_value(self.page_index, self.cell_index)
     }
 
     pub(crate) fn cursor_advance(&mut self) {
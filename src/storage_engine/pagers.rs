@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::fs::File;
 use std::io::{Seek, SeekFrom, Write};
 use std::os::windows::fs::FileExt;
@@ -8,6 +9,7 @@ use crate::sql_engine::sql_structs::{DataType, Value};
 use crate::storage_engine::common::Page;
 use crate::storage_engine::config::*;
 use crate::storage_engine::enums::NodeType;
+use crate::utils::utils::{crc32, read_u32, read_uint, write_u32, write_uint};
 
 pub trait Pager {
     fn get_page(&self, page_index: usize) -> *const u8;
@@ -16,110 +18,213 @@ pub trait Pager {
 
 #[derive(Debug)]
 pub struct AbstractPager {
-    pages: Box<[Option<Page>; TABLE_MAX_PAGES]>,
+    pages: Vec<Option<Page>>,
+    // pages changed since they were last written, a flush only writes those
+    dirty: Vec<bool>,
     total_pages: usize,
     fd: File,
-    header_size: usize
+    header_size: usize,
+    page_size: usize,
+    // pages fetched since the pager was opened, each one counted once: an index lookup reads a
+    // path of the tree, a full scan every leaf. Reads go through `&self`, hence the cells.
+    fetched: Vec<Cell<bool>>,
+    pages_read: Cell<usize>,
+    checksum_size: usize,
+    // what `get_page` reads for a page that is not in the file yet, it stays zeroed
+    empty_page: Page,
 }
 
 impl AbstractPager {
-    pub(crate) fn new(total_pages: usize, file: File, header_size: usize) -> AbstractPager {
-        AbstractPager {
-            pages: Box::new([None; TABLE_MAX_PAGES]),
-            total_pages,
+    /*
+        Every page of the file is read and its checksum verified when it is opened,
+        a corrupt page is reported here rather than while a statement runs
+     */
+    pub(crate) fn open(file: File, header_size: usize, format: DbFormat) -> Result<AbstractPager, String> {
+        let page_size = format.page_size;
+        let size = file.metadata().unwrap().len() as usize;
+        if !(size - header_size).is_multiple_of(page_size) {
+            return Err(String::from("Db file is not a whole number of pages. Corrupt file, `.repair <table>;` truncates it."));
+        }
+        let mut pager = AbstractPager {
+            pages: vec![],
+            dirty: vec![],
+            fetched: vec![],
+            total_pages: (size - header_size) / page_size,
             fd: file,
-            header_size
-        }
+            header_size,
+            page_size,
+            pages_read: Cell::new(0),
+            checksum_size: format.checksum_size(),
+            empty_page: vec![0; page_size].into_boxed_slice(),
+        };
+        pager.load_pages()?;
+        Ok(pager)
+    }
+
+    fn checksum_offset(&self) -> usize {
+        self.page_size - self.checksum_size
+    }
+
+    /*
+        Whether `pages` new pages keep the table within TABLE_MAX_PAGES pages
+     */
+    fn can_allocate(&self, pages: usize) -> bool {
+        self.total_pages + pages <= TABLE_MAX_PAGES
     }
 }
 
 impl AbstractPager {
-    fn page_in_disk(&self, page_num: usize) -> bool {
-        self.total_pages > page_num
+    fn read_page_from_disk(&self, page_index: usize) -> Result<Page, String> {
+        let mut bytes: Page = vec![0; self.page_size].into_boxed_slice();
+        let offset = (page_index * self.page_size + self.header_size) as u64;
+        if self.fd.seek_read(&mut bytes, offset).ok() != Some(self.page_size) {
+            return Err(format!("Can not read page {}.", page_index));
+        }
+        if self.checksum_size == 0 {
+            return Ok(bytes);
+        }
+
+        let checksum_offset = self.checksum_offset();
+        let stored_checksum = u32::from_le_bytes(bytes[checksum_offset..].try_into().unwrap());
+        if stored_checksum != crc32(&bytes[..checksum_offset]) {
+            return Err(format!(
+                "Page {} failed its checksum verification. Corrupt file.",
+                page_index
+            ));
+        }
+        Ok(bytes)
     }
 
-    fn read_page_from_disk(&self, page_index: usize) -> Page {
-        let mut bytes = [0; PAGE_SIZE];
-        self.fd
-            .seek_read(
-                &mut bytes,
-                (page_index * PAGE_SIZE + self.header_size) as u64,
-            )
-            .unwrap();
-        bytes
+    fn load_pages(&mut self) -> Result<(), String> {
+        self.pages = Vec::with_capacity(self.total_pages);
+        for page_index in 0..self.total_pages {
+            let page = self.read_page_from_disk(page_index)?;
+            self.pages.push(Some(page));
+        }
+        self.dirty = vec![false; self.total_pages];
+        self.fetched = vec![Cell::new(false); self.total_pages];
+        Ok(())
     }
 
-    fn flush_page_to_disk(&mut self, page_index: usize) -> bool {
-        let page: Option<&Page> = self.pages[page_index].as_ref();
+    /*
+        Writes the dirty pages, each run of contiguous pages with a single write call.
+        Returns the number of write calls.
+     */
+    fn flush_dirty_pages(&mut self) -> usize {
+        let checksum_offset = self.checksum_offset();
+        let dirty: Vec<usize> = (0..self.dirty.len()).filter(|i| self.dirty[*i]).collect();
+        for page_index in dirty.iter().filter(|_| self.checksum_size > 0) {
+            let page = self.pages[*page_index].as_mut().unwrap();
+            let checksum = crc32(&page[..checksum_offset]);
+            page[checksum_offset..].copy_from_slice(&checksum.to_le_bytes());
+        }
+
+        let mut writes = 0;
+        let mut run_start = 0;
+        while run_start < dirty.len() {
+            let mut run_end = run_start + 1;
+            while run_end < dirty.len() && dirty[run_end] == dirty[run_end - 1] + 1 {
+                run_end += 1;
+            }
 
-        if page.is_none() {
-            return false;
+            let mut buffer = Vec::<u8>::with_capacity((run_end - run_start) * self.page_size);
+            for page_index in dirty[run_start..run_end].iter() {
+                buffer.extend_from_slice(self.pages[*page_index].as_ref().unwrap());
+            }
+            self.fd
+                .seek(SeekFrom::Start(
+                    (dirty[run_start] * self.page_size + self.header_size) as u64,
+                ))
+                .unwrap();
+            self.fd.write_all(&buffer).unwrap();
+
+            writes += 1;
+            run_start = run_end;
         }
+        dirty.iter().for_each(|page_index| self.dirty[*page_index] = false);
+        writes
+    }
 
-        self.fd
-            .seek(SeekFrom::Start(
-                (page_index * PAGE_SIZE + self.header_size) as u64,
-            ))
-            .unwrap();
-        self.fd.write(page.unwrap()).unwrap();
-        true
+    /*
+        The stored pages are in the cache since the file was opened, a page past the end of the file
+        is created and dirty
+     */
+    fn fetch_page(&mut self, page_index: usize) -> *mut u8 {
+        // the cache grows with the file, inserts check `can_allocate` against TABLE_MAX_PAGES first
+        if page_index >= self.pages.len() {
+            self.pages.resize(page_index + 1, None);
+            self.dirty.resize(page_index + 1, false);
+            self.fetched.resize(page_index + 1, Cell::new(false));
+        }
+
+        self.count_read(page_index);
+        if self.pages[page_index].is_none() {
+            self.pages[page_index] = Some(vec![0; self.page_size].into_boxed_slice());
+            self.total_pages += 1;
+            self.dirty[page_index] = true;
+        }
+        self.pages[page_index].as_mut().unwrap().as_mut_ptr()
+    }
+
+    fn count_read(&self, page_index: usize) {
+        if let Some(fetched) = self.fetched.get(page_index) {
+            if !fetched.replace(true) {
+                self.pages_read.set(self.pages_read.get() + 1);
+            }
+        }
     }
 }
 
+/*
+    A page read through `get_page` stays clean and is never created, a page past the end of the file
+    reads as an empty page. `get_or_create_page` hands out a page to change and marks it dirty.
+ */
 impl Pager for AbstractPager {
     fn get_page(&self, page_index: usize) -> *const u8 {
-        unsafe {
-            let s_ptr: &mut Self = std::mem::transmute(self as *const Self);
-            (*s_ptr).get_or_create_page(page_index).cast_const()
+        self.count_read(page_index);
+        match self.pages.get(page_index) {
+            Some(Some(page)) => page.as_ptr(),
+            _ => self.empty_page.as_ptr(),
         }
     }
 
     fn get_or_create_page(&mut self, page_index: usize) -> *mut u8 {
-        if page_index > TABLE_MAX_PAGES {
-            println!(
-                "Tried to fetch page number out of bounds. {} > {}\n",
-                page_index, TABLE_MAX_PAGES
-            );
-            exit(1);
-        }
-
-        let page = self.pages[page_index];
-        if page.is_none() {
-            let loaded_page;
-            if self.page_in_disk(page_index) {
-                loaded_page = self.read_page_from_disk(page_index);
-            } else {
-                let new_page: Page = [0; PAGE_SIZE];
-                loaded_page = new_page;
-                self.total_pages += 1;
-            }
-            self.pages[page_index] = Some(loaded_page);
-        }
-        self.pages[page_index].as_mut().unwrap().as_mut_ptr()
+        let page = self.fetch_page(page_index);
+        self.dirty[page_index] = true;
+        page
     }
 }
 
 pub struct BtreePager {
     abstract_pager: AbstractPager,
-    updated: [bool; TABLE_MAX_PAGES],
     size: usize,
     btree_leaf_node_body_layout: BtreeLeafNodeBodyLayout,
 }
 
 impl BtreePager {
-    pub(crate) fn open(key_size: usize, row_size: usize, file: File) -> BtreePager {
+    pub(crate) fn open(
+        key_size: usize,
+        row_size: usize,
+        format: DbFormat,
+        fill_factor: usize,
+        file: File,
+    ) -> Result<BtreePager, String> {
         let size = file.metadata().unwrap().len() as usize;
-        if (size - BTREE_METADATA_SIZE) % PAGE_SIZE != 0 {
-            println!("Db file is not a whole number of pages. Corrupt file.");
-            exit(1);
-        }
-        let total_pages = size / PAGE_SIZE;
-        BtreePager {
-            abstract_pager: AbstractPager::new(total_pages, file, BTREE_METADATA_SIZE),
-            updated: [false; TABLE_MAX_PAGES],
+        let abstract_pager = AbstractPager::open(file, BTREE_METADATA_SIZE, format)?;
+        Ok(BtreePager {
+            abstract_pager,
             size,
-            btree_leaf_node_body_layout: BtreeLeafNodeBodyLayout::new(key_size, row_size),
-        }
+            btree_leaf_node_body_layout: BtreeLeafNodeBodyLayout::new(key_size, row_size, format, fill_factor)?,
+        })
+    }
+
+    pub fn get_page_size(&self) -> usize {
+        self.abstract_pager.page_size
+    }
+
+    #[cfg(test)]
+    pub fn pages_read(&self) -> usize {
+        self.abstract_pager.pages_read.get()
     }
 
     pub(crate) fn get_or_create_page(&mut self, page_index: usize) -> *mut u8 {
@@ -142,50 +247,25 @@ impl BtreePager {
         self.abstract_pager.total_pages
     }
 
-    pub(crate) fn get_node_type_by_index(&mut self, page_index: usize) -> NodeType {
-        let page = self.abstract_pager.get_or_create_page(page_index);
+    pub(crate) fn can_allocate(&self, pages: usize) -> bool {
+        self.abstract_pager.can_allocate(pages)
+    }
+
+    pub(crate) fn get_node_type_by_index(&self, page_index: usize) -> NodeType {
+        let page = self.abstract_pager.get_page(page_index);
         Self::get_node_type(page)
     }
 
     pub(crate) fn get_leaf_node_num_cells(page: *const u8) -> usize {
-        unsafe {
-            let page_ptr = page.add(LEAF_NODE_NUM_CELLS_OFFSET);
-            let cells_num: usize = 0;
-            ptr::copy_nonoverlapping(
-                page_ptr,
-                &cells_num as *const usize as *mut u8,
-                LEAF_NODE_NUM_CELLS_SIZE,
-            );
-            cells_num
-        }
+        unsafe { read_u32(page.add(LEAF_NODE_NUM_CELLS_OFFSET)) }
     }
 
     pub(crate) fn set_leaf_node_cells_num(page: *mut u8, num: usize) {
-        unsafe {
-            ptr::copy_nonoverlapping(
-                &num as *const usize as *mut u8,
-                page.add(LEAF_NODE_NUM_CELLS_OFFSET),
-                LEAF_NODE_NUM_CELLS_SIZE,
-            );
-        }
+        unsafe { write_u32(page.add(LEAF_NODE_NUM_CELLS_OFFSET), num) }
     }
 
     pub(crate) fn increment_leaf_node_cells_num(page: *mut u8) {
-        unsafe {
-            let page_ptr = page.add(LEAF_NODE_NUM_CELLS_OFFSET);
-            let mut cells_num: usize = 0;
-            ptr::copy_nonoverlapping(
-                page_ptr,
-                &mut cells_num as *mut usize as *mut u8,
-                LEAF_NODE_NUM_CELLS_SIZE,
-            );
-            cells_num += 1;
-            ptr::copy_nonoverlapping(
-                &mut cells_num as *mut usize as *mut u8,
-                page_ptr,
-                LEAF_NODE_NUM_CELLS_SIZE,
-            );
-        }
+        Self::set_leaf_node_cells_num(page, Self::get_leaf_node_num_cells(page) + 1);
     }
 
     pub(crate) fn get_leaf_node_cell_key(
@@ -260,7 +340,7 @@ impl BtreePager {
         }
     }
 
-    pub(crate) fn is_root_node(page: *mut u8) -> bool {
+    pub(crate) fn is_root_node(page: *const u8) -> bool {
         unsafe {
             let root = false;
             ptr::copy_nonoverlapping(
@@ -283,37 +363,23 @@ impl BtreePager {
         }
     }
 
-    pub(crate) fn get_internal_node_cell(page: *mut u8, cell_index: usize) -> *mut u8 {
-        unsafe { page.add(INTERNAL_NODE_BODY_OFFSET + cell_index * INTERNAL_NODE_CELL_SIZE) }
+    pub(crate) fn get_internal_node_cell(&self, page: *mut u8, cell_index: usize) -> *mut u8 {
+        unsafe { page.add(INTERNAL_NODE_BODY_OFFSET + cell_index * self.btree_leaf_node_body_layout.internal_node_cell_size) }
     }
 
     pub fn get_internal_node_num_keys(node: *const u8) -> usize {
-        unsafe {
-            let num: usize = 0;
-            ptr::copy_nonoverlapping(
-                node.add(INTERNAL_NODE_NUM_KEYS_OFFSET),
-                &num as *const usize as *mut u8,
-                INTERNAL_NODE_NUM_KEYS_SIZE,
-            );
-            num
-        }
+        unsafe { read_u32(node.add(INTERNAL_NODE_NUM_KEYS_OFFSET)) }
     }
 
     pub fn set_internal_node_num_keys(node: *mut u8, num: usize) {
-        unsafe {
-            ptr::copy_nonoverlapping(
-                &num as *const usize as *mut u8,
-                node.add(INTERNAL_NODE_NUM_KEYS_OFFSET),
-                INTERNAL_NODE_NUM_KEYS_SIZE,
-            );
-        }
+        unsafe { write_u32(node.add(INTERNAL_NODE_NUM_KEYS_OFFSET), num) }
     }
 
     /*
         set a child into cells.
         each page can have multiple child cells
     */
-    pub fn set_internal_node_child(node: *mut u8, child_index: usize, value: usize) {
+    pub fn set_internal_node_child(&self, node: *mut u8, child_index: usize, value: usize) {
         let num_keys = BtreePager::get_internal_node_num_keys(node);
         if child_index > num_keys {
             println!(
@@ -324,11 +390,11 @@ impl BtreePager {
         } else if child_index == num_keys {
             BtreePager::set_internal_node_right_child(node, value);
         } else {
-            BtreePager::set_internal_node_cell_child(node, child_index, value);
+            self.set_internal_node_cell_child(node, child_index, value);
         }
     }
 
-    pub fn get_internal_node_child(node: *const u8, child_index: usize) -> usize {
+    pub fn get_internal_node_child(&self, node: *const u8, child_index: usize) -> usize {
         let num_keys = BtreePager::get_internal_node_num_keys(node);
         if child_index > num_keys {
             println!(
@@ -344,7 +410,7 @@ impl BtreePager {
             }
             right_child
         } else {
-            let right_child = BtreePager::get_internal_node_cell_child(node, child_index);
+            let right_child = self.get_internal_node_cell_child(node, child_index);
             if right_child == INVALID_PAGE_NUM {
                 println!(
                     "Tried to access child {} of node, but was invalid page",
@@ -357,76 +423,42 @@ impl BtreePager {
     }
 
     pub fn get_internal_node_right_child(node: *const u8) -> usize {
-        unsafe {
-            let right_child_index: usize = 0;
-            ptr::copy_nonoverlapping(
-                node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET),
-                &right_child_index as *const usize as *mut u8,
-                INTERNAL_NODE_RIGHT_CHILD_SIZE,
-            );
-            right_child_index
-        }
+        unsafe { read_u32(node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET)) }
     }
 
     pub fn set_internal_node_right_child(node: *mut u8, cell_index: usize) {
-        unsafe {
-            ptr::copy_nonoverlapping(
-                &cell_index as *const usize as *mut u8,
-                node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET),
-                INTERNAL_NODE_RIGHT_CHILD_SIZE,
-            );
-        }
+        unsafe { write_u32(node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET), cell_index) }
     }
 
-    pub fn set_internal_node_cell_child(node: *mut u8, cell_index: usize, child_index: usize) {
-        unsafe {
-            ptr::copy_nonoverlapping(
-                &child_index as *const usize as *mut u8,
-                node.add(INTERNAL_NODE_BODY_OFFSET + cell_index * INTERNAL_NODE_CELL_SIZE),
-                INTERNAL_NODE_CHILD_SIZE,
-            );
-        }
+    pub fn set_internal_node_cell_child(&self, node: *mut u8, cell_index: usize, child_index: usize) {
+        write_u32(self.get_internal_node_cell(node, cell_index), child_index)
     }
 
-    pub fn get_internal_node_cell_child(node: *const u8, cell_index: usize) -> usize {
-        unsafe {
-            let child_index: usize = 0;
-            ptr::copy_nonoverlapping(
-                node.add(INTERNAL_NODE_BODY_OFFSET + cell_index * INTERNAL_NODE_CELL_SIZE),
-                &child_index as *const usize as *mut u8,
-                INTERNAL_NODE_CHILD_SIZE,
-            );
-            child_index
-        }
+    pub fn get_internal_node_cell_child(&self, node: *const u8, cell_index: usize) -> usize {
+        read_u32(self.get_internal_node_cell(node.cast_mut(), cell_index))
     }
 
     pub fn get_internal_node_cell_key(
+        &self,
         node: *const u8,
         cell_index: usize,
         key_type: &DataType,
     ) -> Value {
         unsafe {
-            let src = node.add(
-                INTERNAL_NODE_BODY_OFFSET
-                    + cell_index * INTERNAL_NODE_CELL_SIZE
-                    + INTERNAL_NODE_CHILD_SIZE,
-            );
+            let src = self.get_internal_node_cell(node.cast_mut(), cell_index).add(INTERNAL_NODE_CHILD_SIZE);
             Value::from_ptr(key_type, src)
         }
     }
 
     pub fn set_internal_node_cell_key(
+        &self,
         node: *mut u8,
         cell_index: usize,
         key_size: usize,
         key: &Value,
     ) {
         unsafe {
-            let dst = node.add(
-                INTERNAL_NODE_BODY_OFFSET
-                    + cell_index * INTERNAL_NODE_CELL_SIZE
-                    + INTERNAL_NODE_CHILD_SIZE,
-            );
+            let dst = self.get_internal_node_cell(node, cell_index).add(INTERNAL_NODE_CHILD_SIZE);
             Self::set_key(key_size, key, dst)
         }
     }
@@ -434,43 +466,38 @@ impl BtreePager {
     unsafe fn set_key(key_size: usize, key: &Value, dst: *mut u8) {
         match key {
             Value::TEXT(string) => {
+                // zero padded to the key size and cut like the row field, so equal strings have equal key bytes
+                let len = string.len().min(key_size);
                 let mut bytes = vec![0; key_size];
-                ptr::copy_nonoverlapping(string.as_ptr(), bytes.as_mut_ptr(), string.len());
+                ptr::copy_nonoverlapping(string.as_ptr(), bytes.as_mut_ptr(), len);
                 ptr::copy_nonoverlapping(bytes.as_ptr(), dst, key_size);
             }
             Value::INT(i) => {
-                ptr::copy_nonoverlapping(i as *const i32 as *const u8, dst, key_size);
+                ptr::copy_nonoverlapping(i.to_le_bytes().as_ptr(), dst, key_size);
             }
             Value::FLOAT(f) => {
-                ptr::copy_nonoverlapping(f as *const f32 as *const u8, dst, key_size);
+                ptr::copy_nonoverlapping(f.to_le_bytes().as_ptr(), dst, key_size);
             }
             Value::BOOL(b) => {
                 ptr::copy_nonoverlapping(b as *const bool as *const u8, dst, key_size);
             }
+            Value::BLOB(b) => {
+                let len = b.len().min(key_size - BLOB_LENGTH_SIZE);
+                let mut bytes = vec![0; key_size];
+                write_uint(bytes.as_mut_ptr(), len, BLOB_LENGTH_SIZE);
+                ptr::copy_nonoverlapping(b.as_ptr(), bytes.as_mut_ptr().add(BLOB_LENGTH_SIZE), len);
+                ptr::copy_nonoverlapping(bytes.as_ptr(), dst, key_size);
+            }
             _ => {}
         }
     }
 
     pub(crate) fn set_leaf_node_next_leaf(node: *mut u8, next_leaf: usize) {
-        unsafe {
-            ptr::copy_nonoverlapping(
-                &next_leaf as *const usize as *mut u8,
-                node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET),
-                INTERNAL_NODE_RIGHT_CHILD_SIZE,
-            );
-        }
+        unsafe { write_u32(node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET), next_leaf) }
     }
 
     pub(crate) fn get_leaf_node_next_leaf(node: *const u8) -> usize {
-        unsafe {
-            let next_leaf: usize = 0;
-            ptr::copy_nonoverlapping(
-                node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET),
-                &next_leaf as *const usize as *mut u8,
-                INTERNAL_NODE_RIGHT_CHILD_SIZE,
-            );
-            next_leaf
-        }
+        unsafe { read_u32(node.add(INTERNAL_NODE_RIGHT_CHILD_OFFSET)) }
     }
 
     pub fn get_node_biggest_key(&mut self, node: *const u8, key_type: &DataType) -> Value {
@@ -478,7 +505,7 @@ impl BtreePager {
             NodeType::Internal => {
                 let right_child = self
                     .abstract_pager
-                    .get_or_create_page(BtreePager::get_internal_node_right_child(node));
+                    .get_page(BtreePager::get_internal_node_right_child(node));
                 self.get_node_biggest_key(right_child, key_type)
             }
             NodeType::Leaf => self.get_leaf_node_cell_key(
@@ -489,12 +516,8 @@ impl BtreePager {
         }
     }
 
-    pub(crate) fn flush_page_to_disk(&mut self, page_num: usize) -> bool {
-        self.abstract_pager.flush_page_to_disk(page_num)
-    }
-
-    pub(crate) fn mark_page_as_updated(&mut self, page_index: usize) {
-        self.updated[page_index] = true;
+    pub(crate) fn flush_dirty_pages(&mut self) -> usize {
+        self.abstract_pager.flush_dirty_pages()
     }
 
     pub(crate) fn initialize_leaf_node(node: *mut u8) {
@@ -517,25 +540,11 @@ impl BtreePager {
     }
 
     pub fn set_parent(node: *mut u8, parent_index: usize) {
-        unsafe {
-            ptr::copy_nonoverlapping(
-                &parent_index as *const usize as *mut u8,
-                node.add(PARENT_POINTER_OFFSET),
-                PARENT_POINTER_SIZE,
-            );
-        }
+        unsafe { write_u32(node.add(PARENT_POINTER_OFFSET), parent_index) }
     }
 
     pub fn get_parent(node: *const u8) -> usize {
-        unsafe {
-            let parent_index: usize = 0;
-            ptr::copy_nonoverlapping(
-                node.add(PARENT_POINTER_OFFSET),
-                &parent_index as *const usize as *mut u8,
-                PARENT_POINTER_SIZE,
-            );
-            parent_index
-        }
+        unsafe { read_u32(node.add(PARENT_POINTER_OFFSET)) }
     }
 }
 
@@ -544,27 +553,13 @@ pub struct SequentialPager {
 }
 
 impl SequentialPager {
-    pub(crate) fn open(file: File) -> SequentialPager {
-        let size = file.metadata().unwrap().len() as usize;
-        if (size - SEQUENTIAL_NODE_HEADER_SIZE) % PAGE_SIZE != 0 {
-            panic!("Db file is not a whole number of pages. Corrupt file.");
-        }
-        let total_pages = size / PAGE_SIZE;
-        SequentialPager {
-            abstract_pager: AbstractPager::new(total_pages, file, SEQUENTIAL_NODE_HEADER_SIZE),
-        }
+    pub(crate) fn open(file: File, format: DbFormat) -> Result<SequentialPager, String> {
+        let abstract_pager = AbstractPager::open(file, SEQUENTIAL_NODE_HEADER_SIZE, format)?;
+        Ok(SequentialPager { abstract_pager })
     }
 
     pub fn get_num_cells(page: *const u8) -> usize {
-        unsafe {
-            let cells_num: usize = 0;
-            ptr::copy_nonoverlapping(
-                page,
-                &cells_num as *const usize as *mut u8,
-                SEQUENTIAL_CELLS_NUM_SIZE,
-            );
-            cells_num
-        }
+        read_u32(page)
     }
 
     pub(crate) fn get_or_create_page(&mut self, page_index: usize) -> *mut u8 {
@@ -579,6 +574,10 @@ impl SequentialPager {
         self.abstract_pager.total_pages
     }
 
+    pub(crate) fn can_allocate(&self, pages: usize) -> bool {
+        self.abstract_pager.can_allocate(pages)
+    }
+
     pub(crate) fn get_row_value(
         &self,
         page: *const u8,
@@ -597,25 +596,313 @@ impl SequentialPager {
         unsafe { page.add(SEQUENTIAL_NODE_BODY_OFFSET + cell_index * row_size) }
     }
 
-    pub fn flush_page_to_disk(&mut self, page_index: usize) -> bool {
-        self.abstract_pager.flush_page_to_disk(page_index)
+    /*
+        Offset of a cell of a page of variable-length rows. Cells follow each other from the start
+        of the body, the offset is found by walking the lengths of the cells before it.
+        `cell_index` == number of cells gives the offset where the next cell is appended.
+     */
+    pub(crate) fn variable_cell_offset(page: *const u8, cell_index: usize) -> usize {
+        let mut offset = SEQUENTIAL_NODE_BODY_OFFSET;
+        for _ in 0..cell_index {
+            offset += VARIABLE_ROW_LENGTH_SIZE + read_uint(unsafe { page.add(offset) }, VARIABLE_ROW_LENGTH_SIZE);
+        }
+        offset
+    }
+
+    /*
+        The encoded row of a cell of variable-length rows, without its length
+     */
+    pub(crate) fn get_variable_row_value(&self, page: *const u8, cell_index: usize) -> &[u8] {
+        let offset = Self::variable_cell_offset(page, cell_index);
+        unsafe {
+            let length = read_uint(page.add(offset), VARIABLE_ROW_LENGTH_SIZE);
+            std::slice::from_raw_parts(page.add(offset + VARIABLE_ROW_LENGTH_SIZE), length)
+        }
+    }
+
+    /*
+        Appends an encoded row to a page of variable-length rows, false when it does not fit
+     */
+    pub(crate) fn append_variable_row(&mut self, page_index: usize, row: &[u8]) -> bool {
+        let checksum_offset = self.abstract_pager.checksum_offset();
+        let page = self.get_or_create_page(page_index);
+        let offset = Self::variable_cell_offset(page, Self::get_num_cells(page));
+        if offset + VARIABLE_ROW_LENGTH_SIZE + row.len() > checksum_offset {
+            return false;
+        }
+        unsafe {
+            write_uint(page.add(offset), row.len(), VARIABLE_ROW_LENGTH_SIZE);
+            ptr::copy_nonoverlapping(row.as_ptr(), page.add(offset + VARIABLE_ROW_LENGTH_SIZE), row.len());
+        }
+        self.increment_cells_num(page_index);
+        true
+    }
+
+    pub fn flush_dirty_pages(&mut self) -> usize {
+        self.abstract_pager.flush_dirty_pages()
     }
 
     pub fn increment_cells_num(&mut self, page_index: usize) {
         let page_ptr = self.get_or_create_page(page_index);
-        let mut cells_num: usize = 0;
-        unsafe {
-            ptr::copy_nonoverlapping(
-                page_ptr,
-                &mut cells_num as *mut usize as *mut u8,
-                SEQUENTIAL_CELLS_NUM_SIZE,
-            );
-            cells_num += 1;
-            ptr::copy_nonoverlapping(
-                &mut cells_num as *mut usize as *mut u8,
-                page_ptr,
-                SEQUENTIAL_CELLS_NUM_SIZE,
-            );
+        write_u32(page_ptr, Self::get_num_cells(page_ptr) + 1);
+    }
+}
+
+/*
+    Pages of the `.ovf` file of a table, the TEXT values too long to be stored in a cell.
+    The file has no header, values are only appended.
+ */
+pub struct OverflowPager {
+    abstract_pager: AbstractPager,
+    // bytes of values a page holds
+    body_size: usize,
+}
+
+impl OverflowPager {
+    pub(crate) fn open(file: File, format: DbFormat) -> Result<OverflowPager, String> {
+        let abstract_pager = AbstractPager::open(file, 0, format)?;
+        let body_size = abstract_pager.checksum_offset() - OVERFLOW_PAGE_BODY_OFFSET;
+        Ok(OverflowPager { abstract_pager, body_size })
+    }
+
+    fn get_used(page: *const u8) -> usize {
+        read_u32(page)
+    }
+
+    /*
+        Where the next value starts: the last page when it has room left, a new page otherwise
+     */
+    fn end(&self) -> (usize, usize) {
+        let total_pages = self.abstract_pager.total_pages;
+        if total_pages == 0 {
+            return (0, 0);
+        }
+        let used = Self::get_used(self.abstract_pager.get_page(total_pages - 1));
+        if used >= self.body_size {
+            (total_pages, 0)
+        } else {
+            (total_pages - 1, used)
+        }
+    }
+
+    /*
+        Whether `length` more bytes of values keep the file within TABLE_MAX_PAGES pages
+     */
+    pub(crate) fn can_allocate(&self, length: usize) -> bool {
+        if length == 0 {
+            return true;
+        }
+        let (page_index, offset) = self.end();
+        let last_page = page_index + (offset + length).saturating_sub(1) / self.body_size;
+        last_page < TABLE_MAX_PAGES
+    }
+
+    /*
+        Appends a value, returns its first page and its offset in that page
+     */
+    pub(crate) fn append(&mut self, value: &[u8]) -> (usize, usize) {
+        let start = self.end();
+        let (mut page_index, mut offset) = start;
+        let mut written = 0;
+        while written < value.len() {
+            let length = (self.body_size - offset).min(value.len() - written);
+            let page = self.abstract_pager.get_or_create_page(page_index);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    value[written..].as_ptr(),
+                    page.add(OVERFLOW_PAGE_BODY_OFFSET + offset),
+                    length,
+                );
+            }
+            write_u32(page, offset + length);
+            written += length;
+            page_index += 1;
+            offset = 0;
         }
+        start
+    }
+
+    /*
+        The `length` bytes of the value starting at `offset` of page `page_index`.
+        A position past the end of the file, which only a corrupt cell holds, reads as fewer bytes.
+     */
+    pub(crate) fn read(&self, page_index: usize, offset: usize, length: usize) -> Vec<u8> {
+        let mut value = Vec::with_capacity(length);
+        let (mut page_index, mut offset) = (page_index, offset);
+        while value.len() < length && page_index < self.abstract_pager.total_pages && offset < self.body_size {
+            let page = self.abstract_pager.get_page(page_index);
+            let read = (self.body_size - offset).min(length - value.len());
+            unsafe {
+                value.extend_from_slice(std::slice::from_raw_parts(page.add(OVERFLOW_PAGE_BODY_OFFSET + offset), read));
+            }
+            page_index += 1;
+            offset = 0;
+        }
+        value
+    }
+
+    pub fn flush_dirty_pages(&mut self) -> usize {
+        self.abstract_pager.flush_dirty_pages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::build_path;
+    use crate::sql_engine::sql_structs::{DataType, Value};
+    use crate::utils::test_utils::TestDb;
+
+    fn open_pager(path: &PathBuf, format: DbFormat) -> Result<SequentialPager, String> {
+        let file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+        SequentialPager::open(file, format)
+    }
+
+    fn empty_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, [0; SEQUENTIAL_NODE_HEADER_SIZE]).unwrap();
+        path
+    }
+
+    #[test]
+    fn flush_writes_only_dirty_pages() {
+        let path = empty_file("pagers_flush_dirty.seq");
+        let mut pager = open_pager(&path, DbFormat::current(DEFAULT_PAGE_SIZE)).unwrap();
+
+        (0..4).for_each(|page_index| pager.increment_cells_num(page_index));
+        // the new pages are contiguous, they take a single write
+        assert_eq!(pager.flush_dirty_pages(), 1);
+        assert_eq!(pager.flush_dirty_pages(), 0);
+
+        (0..4).for_each(|page_index| {
+            pager.get_page(page_index);
+        });
+        assert_eq!(pager.flush_dirty_pages(), 0);
+
+        pager.increment_cells_num(0);
+        pager.increment_cells_num(2);
+        assert_eq!(pager.flush_dirty_pages(), 2);
+
+        let pager = open_pager(&path, DbFormat::current(DEFAULT_PAGE_SIZE)).unwrap();
+        let cells: Vec<usize> = (0..4).map(|page_index| SequentialPager::get_num_cells(pager.get_page(page_index))).collect();
+        assert_eq!(cells, vec![2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn corrupt_page_fails_the_open() {
+        let path = empty_file("pagers_corrupt.seq");
+        let format = DbFormat::current(DEFAULT_PAGE_SIZE);
+        let mut pager = open_pager(&path, format).unwrap();
+        (0..3).for_each(|page_index| pager.increment_cells_num(page_index));
+        pager.flush_dirty_pages();
+        drop(pager);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[SEQUENTIAL_NODE_HEADER_SIZE + DEFAULT_PAGE_SIZE + 10] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        let error = open_pager(&path, format).err().unwrap();
+        assert!(error.contains("Page 1 failed its checksum verification"), "{}", error);
+    }
+
+    #[test]
+    fn legacy_pages_have_no_checksum() {
+        let path = std::env::temp_dir().join("pagers_legacy.seq");
+        let mut bytes = vec![0; SEQUENTIAL_NODE_HEADER_SIZE + DEFAULT_PAGE_SIZE];
+        // one cell in the page, its last bytes hold rows rather than a checksum
+        bytes[SEQUENTIAL_NODE_HEADER_SIZE] = 1;
+        bytes[SEQUENTIAL_NODE_HEADER_SIZE + DEFAULT_PAGE_SIZE - 1] = 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        assert!(open_pager(&path, DbFormat::current(DEFAULT_PAGE_SIZE)).is_err());
+        let legacy = DbFormat { page_size: DEFAULT_PAGE_SIZE, version: LEGACY_FORMAT_VERSION };
+        let mut pager = open_pager(&path, legacy).unwrap();
+        assert_eq!(SequentialPager::get_num_cells(pager.get_page(0)), 1);
+
+        pager.increment_cells_num(0);
+        pager.flush_dirty_pages();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes[SEQUENTIAL_NODE_HEADER_SIZE], 2);
+        assert_eq!(bytes[SEQUENTIAL_NODE_HEADER_SIZE + DEFAULT_PAGE_SIZE - 1], 0xff);
+    }
+
+    #[test]
+    fn integers_are_stored_little_endian() {
+        let mut key = [0u8; 4];
+        unsafe { BtreePager::set_key(4, &Value::INT(0x01020304), key.as_mut_ptr()) };
+        assert_eq!(key, [4, 3, 2, 1]);
+        assert_eq!(Value::from_ptr(&DataType::INTEGER, key.as_ptr()), Value::INT(0x01020304));
+        unsafe { BtreePager::set_key(4, &Value::FLOAT(1.5), key.as_mut_ptr()) };
+        assert_eq!(key, 1.5f32.to_le_bytes());
+
+        let mut db = TestDb::open(&["pagers_little_endian"]);
+        db.run("create table pagers_little_endian (id int primary key, price float);").unwrap();
+        db.run("insert into pagers_little_endian (id, price) values (16909060, -2.0);").unwrap();
+        db.reopen();
+
+        let bytes = std::fs::read(build_path!(DATA_FOLDER, "pagers_little_endian", "pagers_little_endian.idx")).unwrap();
+        let page = &bytes[BTREE_METADATA_SIZE..];
+        assert_eq!(page[LEAF_NODE_NUM_CELLS_OFFSET..LEAF_NODE_NUM_CELLS_OFFSET + 4], [1, 0, 0, 0]);
+        // the key, then the row with the key field and the price
+        let cell = &page[LEAF_NODE_BODY_OFFSET..LEAF_NODE_BODY_OFFSET + 12];
+        assert_eq!(cell[..8], [4, 3, 2, 1, 4, 3, 2, 1]);
+        assert_eq!(cell[8..], (-2.0f32).to_le_bytes());
+    }
+
+    fn open_btree_pager(path: &PathBuf) -> BtreePager {
+        let file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+        BtreePager::open(INTEGER_SIZE, 2 * INTEGER_SIZE, DbFormat::current(DEFAULT_PAGE_SIZE), DEFAULT_FILL_FACTOR, file).unwrap()
+    }
+
+    #[test]
+    fn child_pointers_round_trip_through_disk() {
+        let path = std::env::temp_dir().join("pagers_children.idx");
+        std::fs::write(&path, [0; BTREE_METADATA_SIZE]).unwrap();
+        let mut pager = open_btree_pager(&path);
+        let node = pager.get_or_create_page(0);
+        BtreePager::initialize_internal_node(node);
+        BtreePager::set_internal_node_num_keys(node, 1);
+        pager.set_internal_node_child(node, 0, 3);
+        // past the 2 bytes of a u16, still within the 4 stored bytes
+        pager.set_internal_node_child(node, 1, 70_000);
+        BtreePager::set_parent(node, 65_537);
+        pager.flush_dirty_pages();
+        drop(pager);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let page = &bytes[BTREE_METADATA_SIZE..];
+        let right_child = &page[INTERNAL_NODE_RIGHT_CHILD_OFFSET..INTERNAL_NODE_RIGHT_CHILD_OFFSET + INTERNAL_NODE_RIGHT_CHILD_SIZE];
+        assert_eq!(right_child, 70_000u32.to_le_bytes());
+
+        let pager = open_btree_pager(&path);
+        let node = pager.get_page(0);
+        assert_eq!(pager.get_internal_node_child(node, 0), 3);
+        assert_eq!(pager.get_internal_node_child(node, 1), 70_000);
+        assert_eq!(BtreePager::get_parent(node), 65_537);
+    }
+
+    #[test]
+    fn cell_count_round_trips_through_disk() {
+        let path = std::env::temp_dir().join("pagers_cell_count.idx");
+        std::fs::write(&path, [0; BTREE_METADATA_SIZE]).unwrap();
+        let mut pager = open_btree_pager(&path);
+        for (page_index, cells) in [(0, 0), (1, 1), (2, 300), (3, 70_000)] {
+            let node = pager.get_or_create_page(page_index);
+            BtreePager::initialize_leaf_node(node);
+            BtreePager::set_leaf_node_cells_num(node, cells);
+        }
+        BtreePager::increment_leaf_node_cells_num(pager.get_or_create_page(1));
+        pager.flush_dirty_pages();
+        drop(pager);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let offset = BTREE_METADATA_SIZE + 3 * DEFAULT_PAGE_SIZE + LEAF_NODE_NUM_CELLS_OFFSET;
+        assert_eq!(bytes[offset..offset + LEAF_NODE_NUM_CELLS_SIZE], 70_000u32.to_le_bytes());
+
+        let pager = open_btree_pager(&path);
+        let cells: Vec<usize> = (0..4).map(|page_index| BtreePager::get_leaf_node_num_cells(pager.get_page(page_index))).collect();
+        assert_eq!(cells, vec![0, 2, 300, 70_000]);
     }
 }
@@ -1,35 +1,28 @@
 use std::any::Any;
-use std::cmp::max;
-use std::collections::HashSet;
 use std::fs::{File, OpenOptions};
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::ptr::null_mut;
 use std::rc::Rc;
 
-use crate::sql_engine::sql_structs::{Condition, ConditionCluster, ConditionExpr, DataType, LogicalOperator, Operator, Value};
+use crate::sql_engine::sql_structs::{Condition, ConditionExpr, DataType, Operator, Value};
 use crate::storage_engine::common::{RowBytes, RowToInsert, TableStructureMetadata};
 use crate::storage_engine::config::*;
 use crate::storage_engine::cursor::{ReadCursor, WriteReadCursor};
 use crate::storage_engine::enums::NodeType;
-use crate::storage_engine::pagers::{BtreePager, SequentialPager};
-use crate::utils::utils::{copy, copy_nonoverlapping, ToU8, u8_array_to_string};
+use crate::storage_engine::pagers::{BtreePager, OverflowPager, SequentialPager};
+use crate::utils::utils::{copy, copy_nonoverlapping, read_u32, read_uint, ToU8, u8_array_to_string};
 
 pub trait Table {
     fn begin(&mut self) -> WriteReadCursor;
     fn insert(&mut self, row: &RowToInsert) -> Result<(), String>;
-    fn find_by_condition_clusters(
-        &self,
-        condition_clusters: &Vec<ConditionCluster>,
-    ) -> Vec<RowBytes>;
-    fn find_by_condition_cluster(
-        &self,
-        condition_cluster: &ConditionCluster,
-    ) -> Vec<RowBytes>;
+    fn find_by_condition(&self, condition: &Condition) -> Vec<RowBytes>;
     fn end(&mut self) -> WriteReadCursor;
     fn is_btree(&self) -> bool;
     fn get_all(&self) -> Vec<RowBytes>;
+    fn for_each_row(&self, f: &mut dyn FnMut(&RowBytes));
+    fn row_count(&self) -> usize;
     fn get_row_size(&self) -> usize;
     fn get_num_cells(&self, page_index: usize) -> usize;
     fn get_next_page_index(&self, page_index: usize) -> usize;
@@ -40,8 +33,93 @@ pub trait Table {
     fn as_any(&self) -> &dyn Any;
 }
 
+/*
+    The overflow pages of a table with TEXT fields too long for a cell, None for the other tables
+ */
+fn open_overflow(
+    path: &Path,
+    table_metadata: &TableStructureMetadata,
+    format: DbFormat,
+) -> Result<Option<OverflowPager>, String> {
+    if !table_metadata.has_overflow() {
+        return Ok(None);
+    }
+    let overflow_path = path.with_file_name(table_metadata.table_name.to_owned() + OVERFLOW_EXTENSION);
+    match OpenOptions::new().read(true).write(true).open(overflow_path) {
+        Ok(file) => Ok(Some(OverflowPager::open(file, format)?)),
+        Err(_) => Err(format!("Can not open overflow file of table {}!", table_metadata.table_name)),
+    }
+}
+
+/*
+    The row as it is stored in a cell: the value of an overflow TEXT field is appended to the
+    overflow pages and the cell keeps its first page, its offset in that page and its length.
+    A row copied from a cell is stored as is.
+ */
+fn store_row(
+    row: &RowToInsert,
+    table_metadata: &TableStructureMetadata,
+    overflow: &mut Option<OverflowPager>,
+) -> Result<RowBytes, String> {
+    let (row, overflow) = match overflow {
+        Some(overflow) if !row.stored => (&row.raw_data, overflow),
+        _ => return Ok(row.raw_data.clone()),
+    };
+    // TEXT values are padded with zeros up to their declared size, only their bytes are stored
+    let text_length = |bytes: &[u8]| bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+    let overflow_length: usize = table_metadata
+        .fields
+        .iter()
+        .filter(|f| f.data_def.is_overflow())
+        .map(|f| text_length(&row[f.offset..f.offset + f.size]))
+        .sum();
+    if !overflow.can_allocate(overflow_length) {
+        return Err(BtreeTable::table_full_error(&table_metadata.table_name));
+    }
+
+    let mut data = Vec::with_capacity(table_metadata.stored_row_size());
+    for field in table_metadata.fields.iter() {
+        let bytes = &row[field.offset..field.offset + field.size];
+        if !field.data_def.is_overflow() {
+            data.extend_from_slice(bytes);
+            continue;
+        }
+        let length = text_length(bytes);
+        let (page_index, offset) = overflow.append(&bytes[..length]);
+        data.extend_from_slice(&(page_index as u32).to_le_bytes());
+        data.extend_from_slice(&(offset as u32).to_le_bytes());
+        data.extend_from_slice(&(length as u32).to_le_bytes());
+    }
+    Ok(RowBytes { data })
+}
+
+/*
+    The row of a cell, with the values of its overflow TEXT fields read back
+ */
+fn load_row(cell: *const u8, table_metadata: &TableStructureMetadata, overflow: &Option<OverflowPager>) -> RowBytes {
+    let overflow = match overflow {
+        Some(overflow) => overflow,
+        None => return RowBytes::deserialize_row(cell, table_metadata.row_size),
+    };
+    let mut data = vec![0; table_metadata.row_size];
+    let mut position = 0;
+    for field in table_metadata.fields.iter() {
+        let stored = unsafe { cell.add(position) };
+        if field.data_def.is_overflow() {
+            let page_index = read_u32(stored);
+            let offset = read_u32(unsafe { stored.add(OVERFLOW_POINTER_PAGE_SIZE) });
+            let length = read_u32(unsafe { stored.add(OVERFLOW_POINTER_PAGE_SIZE + OVERFLOW_POINTER_OFFSET_SIZE) });
+            let value = overflow.read(page_index, offset, length.min(field.size));
+            data[field.offset..field.offset + value.len()].copy_from_slice(&value);
+        } else {
+            copy_nonoverlapping(stored, data[field.offset..].as_mut_ptr(), field.size);
+        }
+        position += field.data_def.stored_size();
+    }
+    RowBytes { data }
+}
+
 pub struct BtreeMeta {
-    data_type: DataType,
     is_primary: bool,
     key_size: usize,
     key_field_name: String,
@@ -50,12 +128,14 @@ pub struct BtreeMeta {
 pub struct BtreeTable {
     pub root_page_index: usize,
     pub pager: BtreePager,
+    overflow: Option<OverflowPager>,
     pub is_primary: bool,
     pub key_type: DataType,
     pub key_size: usize,
     pub key_offset_in_row: usize,
     pub key_field_name: String,
     pub row_size: usize,
+    pub fill_factor: usize,
     table_metadata: Rc<TableStructureMetadata>,
 }
 
@@ -76,91 +156,42 @@ impl Table for BtreeTable {
 
         let (_, key_value) = key.unwrap();
 
-        let cursor = self.table_find_by_key(key_value, Operator::EQUALS(false));
+        let cursor = self.table_find_by_key(key_value, Operator::EQUALS(false))?;
         let page_index = cursor.page_index;
         let cell_index = cursor.cell_index;
 
-        let page = self.pager.get_or_create_page(page_index);
+        let page = self.pager.get_page(page_index);
         let num_cells = BtreePager::get_leaf_node_num_cells(page);
         if num_cells >= self.pager.get_body_layout().leaf_node_max_cells {
-            self.split_and_insert(page_index, cell_index, &row.raw_data);
+            let pages_needed = self.pages_needed_by_split(page_index);
+            if !self.pager.can_allocate(pages_needed) {
+                return Err(Self::table_full_error(&self.table_metadata.table_name));
+            }
+        }
+        // the cursor stops on the cell holding the key when it is already stored
+        if cell_index < num_cells && self.pager.get_leaf_node_cell_key(page, cell_index, &self.key_type) == **key_value {
+            return Err(format!(
+                "Duplicate value {} for primary key `{}`.",
+                key_value.to_sql_literal(),
+                self.key_field_name
+            ));
+        }
+        let stored = store_row(row, &self.table_metadata, &mut self.overflow)?;
+        if num_cells >= self.pager.get_body_layout().leaf_node_max_cells {
+            self.split_and_insert(page_index, cell_index, &stored);
         } else {
-            self.move_and_insert(page_index, cell_index, &row.raw_data);
+            self.move_and_insert(page_index, cell_index, &stored);
         }
         Ok(())
     }
 
-    fn find_by_condition_clusters(
-        &self,
-        condition_clusters: &Vec<ConditionCluster>,
-    ) -> Vec<RowBytes> {
+    fn find_by_condition(&self, condition: &Condition) -> Vec<RowBytes> {
         let mut result = vec![];
-        let mut last_op: Option<LogicalOperator> = None;
-        for cluster in condition_clusters {
-            let cluster_result = self.find_by_condition_cluster(cluster);
-            if last_op.is_none() || last_op.clone().unwrap().combine(cluster.logical_operator) == LogicalOperator::OR {
-                result.extend(cluster_result);
-                if last_op.is_none() {
-                    last_op = Some(cluster.logical_operator);
-                } else {
-                    last_op = Some(last_op.unwrap().combine(cluster.logical_operator));
-                }
-            } else {
-                let set: HashSet<RowBytes> = HashSet::from_iter(result.into_iter());
-                result = vec![];
-                for r in cluster_result {
-                    if set.contains(&r) {
-                        result.push(r);
-                    }
-                }
-            }
-        }
-        result
-    }
-
-    fn find_by_condition_cluster(&self, cluster: &ConditionCluster) -> Vec<RowBytes> {
-        let mut result = Vec::new();
-        let mut last_op: Option<LogicalOperator> = None;
-        unsafe {
-            let mut and_exprs = vec![];
-            let mut or_clusters = vec![];
-            for condition in cluster.iter() {
-                if condition.is_expr() && condition.unwrap_as_expr().unwrap().logical_operator == LogicalOperator::AND{
-                    and_exprs.push(condition.unwrap_as_expr().unwrap());
-                } else {
-                    or_clusters.push(condition)
-                }
-            }
-
-            if !and_exprs.is_empty() {
-                result = self.find_by_condition_exprs(and_exprs);
-            }
-
-            for oc in or_clusters {
-                if oc.is_expr() {
-                    result.extend(self.find_by_condition_exprs(vec![oc.unwrap_as_expr().unwrap()]));
-                } else {
-                    let cluster = oc.unwrap_as_cluster().unwrap();
-                    let cluster_result = self.find_by_condition_cluster(cluster);
-                    if last_op.is_none() || last_op.clone().unwrap().combine(cluster.logical_operator) == LogicalOperator::OR {
-                        result.extend(cluster_result);
-                        if last_op.is_none() {
-                            last_op = Some(cluster.logical_operator);
-                        } else {
-                            last_op = Some(last_op.unwrap().combine(cluster.logical_operator))
-                        }
-                    } else {
-                        let set: HashSet<RowBytes> = HashSet::from_iter(result.into_iter());
-                        result = vec![];
-                        for r in cluster_result {
-                            if set.contains(&r) {
-                                result.push(r);
-                            }
-                        }
-                    }
-                }
+        self.for_each_row(&mut |row| {
+            if condition.matches(row, &self.table_metadata) {
+                result.push(row.clone());
             }
-        }
+        });
         result
     }
 
@@ -173,14 +204,32 @@ impl Table for BtreeTable {
     }
 
     fn get_all(&self) -> Vec<RowBytes> {
-        let row_size = self.table_metadata.row_size;
-        let mut cursor = ReadCursor::at(self, 0, 0);
         let mut result = Vec::new();
+        self.for_each_row(&mut |row| result.push(row.clone()));
+        result
+    }
+
+    fn for_each_row(&self, f: &mut dyn FnMut(&RowBytes)) {
+        // the root is only a leaf until the first split, rows start at the leftmost leaf
+        let first = self.find_smallest_or_biggest_key(false);
+        let (page_index, cell_index) = (first.page_index, first.cell_index);
+        let mut cursor = ReadCursor::at(self, page_index, cell_index);
         while !cursor.is_end() {
-            result.push(RowBytes::deserialize_row(cursor.cursor_value(), row_size));
+            f(&load_row(cursor.cursor_value(), &self.table_metadata, &self.overflow));
             cursor.cursor_advance();
         }
-        result
+    }
+
+    /*
+        The cell counts of the leaf pages are summed without reading the rows
+     */
+    fn row_count(&self) -> usize {
+        let max_cells = self.pager.get_body_layout().leaf_node_max_cells;
+        (0..self.pager.get_unused_page_num())
+            .map(|page_index| self.pager.get_page(page_index))
+            .filter(|page| matches!(BtreePager::get_node_type(*page), NodeType::Leaf))
+            .map(|page| BtreePager::get_leaf_node_num_cells(page).min(max_cells))
+            .sum()
     }
 
     fn get_row_size(&self) -> usize {
@@ -208,10 +257,9 @@ impl Table for BtreeTable {
     }
 
     fn flush_to_disk(&mut self) {
-        for x in 0..TABLE_MAX_PAGES {
-            if !self.pager.flush_page_to_disk(x) {
-                break;
-            }
+        self.pager.flush_dirty_pages();
+        if let Some(overflow) = self.overflow.as_mut() {
+            overflow.flush_dirty_pages();
         }
     }
 
@@ -229,6 +277,7 @@ impl BtreeTable {
     pub(crate) fn new(
         path: &PathBuf,
         table_metadata: Rc<TableStructureMetadata>,
+        format: DbFormat,
     ) -> Result<BtreeTable, String> {
         match OpenOptions::new()
             .read(true)
@@ -237,9 +286,10 @@ impl BtreeTable {
         {
             Ok(mut file) => {
                 let meta = Self::load_metadata(&mut file, &table_metadata.table_name)?;
-                let pager = BtreePager::open(meta.key_size, table_metadata.row_size, file);
+                let pager = BtreePager::open(meta.key_size, table_metadata.stored_row_size(), format, table_metadata.fill_factor, file)?;
                 let mut pager = pager;
-                if pager.get_pager_total_size() == 0 {
+                // the file only holds the index metadata, the root leaf is not created yet
+                if pager.get_pager_total_size() == BTREE_METADATA_SIZE {
                     let first_page = pager.get_or_create_page(0);
                     BtreePager::initialize_leaf_node(first_page);
                     BtreePager::set_root_node(first_page, true);
@@ -247,14 +297,20 @@ impl BtreeTable {
                 Ok(BtreeTable {
                     root_page_index: 0,
                     pager,
+                    overflow: open_overflow(path, &table_metadata, format)?,
                     is_primary: meta.is_primary,
-                    key_type: meta.data_type,
-                    key_size: meta.key_size,
-                    key_offset_in_row: table_metadata
+                    // the index file only stores the type bits, the declared size comes from the table metadata
+                    key_type: table_metadata
                         .get_field_metadata(&meta.key_field_name)?
-                        .offset,
+                        .data_def
+                        .data_type,
+                    key_size: meta.key_size,
+                    // the key is read from the row stored in a cell
+                    key_offset_in_row: table_metadata.stored_offset(&meta.key_field_name)?,
                     key_field_name: meta.key_field_name,
-                    row_size: table_metadata.row_size,
+                    // the size of a row in a cell
+                    row_size: table_metadata.stored_row_size(),
+                    fill_factor: table_metadata.fill_factor,
                     table_metadata,
                 })
             }
@@ -265,45 +321,140 @@ impl BtreeTable {
         }
     }
 
-    unsafe fn find_by_condition_exprs(&self, mut exprs: Vec<&ConditionExpr>) -> Vec<RowBytes> {
-        exprs.sort_by(|e1, e2| (e2.field == self.key_field_name).to_u8().cmp(&(e1.field == self.key_field_name).to_u8()));
-        let max_field_size = exprs.iter().map(|e| self.table_metadata.get_field_metadata(&e.field).unwrap().size).max().unwrap();
-        let mut buf = Vec::<u8>::with_capacity(max_field_size);
+    /*
+        Rows matching every expression in key order, at most `limit` of them
+     */
+    pub(crate) unsafe fn find_by_condition_exprs(
+        &self,
+        mut exprs: Vec<&ConditionExpr>,
+        limit: Option<usize>,
+    ) -> Result<Vec<RowBytes>, String> {
+        for expr in exprs.iter().filter(|e| e.is_on_field(&self.key_field_name)) {
+            self.check_key_type(expr.value().unwrap())?;
+        }
+
+        if let Some(Value::ARRAY(keys)) = exprs
+            .iter()
+            .find(|e| e.is_on_field(&self.key_field_name) && e.operator == Operator::IN(false))
+            .and_then(|e| e.value())
+        {
+            return self.find_by_keys(keys, &exprs, limit);
+        }
+
+        exprs.sort_by_key(|e| std::cmp::Reverse(self.can_seek(e).to_u8()));
         let mut result = vec![];
 
+        // keys are sorted, once this condition fails no later row can match
+        let stop_expr = exprs.iter().find(|e| self.stops_scan(e)).copied();
+
         let mut cursor;
 
-        if exprs.first().unwrap().field == self.key_field_name {
-            println!("Index scan for field `{}`", self.key_field_name);
+        if self.can_seek(exprs.first().unwrap()) {
             let first_expr = exprs.first().unwrap();
-            cursor = self.table_find_by_key(&first_expr.value, first_expr.operator);
+            cursor = self.table_find_by_key(first_expr.value().unwrap(), first_expr.operator)?;
         } else {
             cursor = self.find_smallest_or_biggest_key(false);
         }
 
-        while !cursor.is_end() {
-            let row_ptr = cursor.cursor_value();
-            let mut matched = true;
-            for expr in exprs.iter() {
-                matched &= self.read_compare_value(row_ptr, &mut buf, expr);
-                if !matched {
+        while !cursor.is_end() && limit.is_none_or(|limit| result.len() < limit) {
+            if let Some(stop_expr) = stop_expr {
+                let page = self.pager.get_page(cursor.page_index);
+                let key = self.pager.get_leaf_node_cell_key(page, cursor.cell_index, &self.key_type);
+                if !stop_expr.operator.operate(&key, stop_expr.value().unwrap()) {
                     break;
                 }
             }
 
-            if matched {
-                result.push(RowBytes::deserialize_row(row_ptr, self.row_size));
+            let row = load_row(cursor.cursor_value(), &self.table_metadata, &self.overflow);
+            if exprs.iter().all(|expr| expr.compare_expression(&row, &self.table_metadata)) {
+                result.push(row);
             }
             cursor.cursor_advance();
         }
 
+        Ok(result)
+    }
 
-        result
+    /*
+        `key IN [a, b, c]`: one lookup per listed key instead of a scan, rows come in key order
+     */
+    unsafe fn find_by_keys(
+        &self,
+        keys: &[Value],
+        exprs: &[&ConditionExpr],
+        limit: Option<usize>,
+    ) -> Result<Vec<RowBytes>, String> {
+        let mut keys: Vec<&Value> = keys.iter().collect();
+        keys.sort_by(|k1, k2| k1.partial_cmp(k2).unwrap());
+        keys.dedup();
+
+        let mut result = vec![];
+        for key in keys {
+            if limit.is_some_and(|limit| result.len() >= limit) {
+                break;
+            }
+            if let Some(row) = self.get_by_key(key) {
+                if exprs.iter().all(|expr| expr.compare_expression(&row, &self.table_metadata)) {
+                    result.push(row);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /*
+        Keys matching `condition` in ascending order, read from the leaf cells without the rows.
+        At most `limit` keys are read.
+     */
+    pub fn scan_keys(&self, condition: Option<&ConditionExpr>, limit: Option<usize>) -> Result<Vec<Value>, String> {
+        if let Some(c) = condition {
+            self.check_key_type(c.value().unwrap())?;
+            if c.operator == Operator::IN(false) && matches!(c.value(), Some(Value::ARRAY(keys)) if keys.is_empty()) {
+                return Ok(vec![]);
+            }
+        }
+        let mut cursor = match condition {
+            Some(c) if self.can_seek(c) => self.table_find_by_key(c.value().unwrap(), c.operator)?,
+            _ => self.find_smallest_or_biggest_key(false),
+        };
+
+        let mut keys = vec![];
+        while !cursor.is_end() && limit.is_none_or(|limit| keys.len() < limit) {
+            let page = self.pager.get_page(cursor.page_index);
+            let key = self.pager.get_leaf_node_cell_key(page, cursor.cell_index, &self.key_type);
+            match condition {
+                Some(c) if !c.operator.operate(&key, c.value().unwrap()) => {
+                    if self.stops_scan(c) {
+                        break;
+                    }
+                }
+                _ => keys.push(key),
+            }
+            cursor.cursor_advance();
+        }
+        Ok(keys)
+    }
+
+    /*
+        Only `=`, `>` and `>=` give a key to start the scan from,
+        `<` and `<=` start from the smallest key, the others are checked row by row
+     */
+    fn can_seek(&self, expr: &ConditionExpr) -> bool {
+        expr.is_on_field(&self.key_field_name)
+            && matches!(expr.operator, Operator::EQUALS(false) | Operator::GT | Operator::GTE)
+    }
+
+    /*
+        A key condition that, once failed in key order, fails for every following key
+     */
+    fn stops_scan(&self, expr: &ConditionExpr) -> bool {
+        expr.is_on_field(&self.key_field_name)
+            && matches!(expr.operator, Operator::EQUALS(false) | Operator::LT | Operator::LTE)
     }
 
     fn load_metadata(file: &mut File, table_name: &str) -> Result<BtreeMeta, String> {
         let mut metadata: [u8; BTREE_METADATA_SIZE] = [0; BTREE_METADATA_SIZE];
-        match file.read(&mut metadata) {
+        match file.read_exact(&mut metadata) {
             Ok(_) => {}
             Err(_) => {
                 return Err(format!(
@@ -313,7 +464,6 @@ impl BtreeTable {
             }
         };
 
-        let data_type_mask: u8 = 0b0000_0000;
         let primary_mask: u8 = 0b0000_0001;
         let field_type_primary: u8 = 0;
 
@@ -322,18 +472,11 @@ impl BtreeTable {
             &field_type_primary as *const u8 as *mut u8,
             INDEXED_FIELD_TYPE_PRIMARY,
         );
-        let data_type_bit_code = (field_type_primary >> 1) | data_type_mask;
         let is_primary = (field_type_primary & primary_mask) == 1;
 
-        let data_type = DataType::from_bit_code(data_type_bit_code)?;
-        let key_size: usize = 0;
+        let key_size = unsafe { read_uint(metadata.as_ptr().add(INDEXED_FIELD_SIZE_OFFSET), INDEXED_FIELD_SIZE) };
         let mut key_name: [u8; INDEXED_FIELD_NAME_SIZE] = [0; INDEXED_FIELD_NAME_SIZE];
         unsafe {
-            copy_nonoverlapping(
-                metadata.as_ptr().add(INDEXED_FIELD_SIZE_OFFSET),
-                &key_size as *const usize as *mut u8,
-                INDEXED_FIELD_SIZE,
-            );
             copy_nonoverlapping(
                 metadata.as_ptr().add(INDEXED_FIELD_NAME_SIZE_OFFSET),
                 key_name.as_mut_ptr(),
@@ -342,13 +485,51 @@ impl BtreeTable {
         }
         let key_field_name = u8_array_to_string(&key_name);
         Ok(BtreeMeta {
-            data_type,
             is_primary,
             key_size,
             key_field_name,
         })
     }
 
+    /*
+        Rows of every leaf page in page order, read without following the tree,
+        so they are found even when parent or sibling pointers are wrong.
+        Rows are returned as stored in their cells, long TEXT values as their overflow pointers.
+     */
+    pub(crate) fn leaf_rows(&self) -> Vec<RowBytes> {
+        let max_cells = self.pager.get_body_layout().leaf_node_max_cells;
+        let mut rows = vec![];
+        for page_index in 0..self.pager.get_unused_page_num() {
+            let page = self.pager.get_page(page_index) as *mut u8;
+            if !matches!(BtreePager::get_node_type(page), NodeType::Leaf) {
+                continue;
+            }
+            for cell_index in 0..BtreePager::get_leaf_node_num_cells(page).min(max_cells) {
+                let row_ptr = self.pager.get_leaf_node_value(page, cell_index);
+                rows.push(RowBytes::deserialize_row(row_ptr, self.table_metadata.stored_row_size()));
+            }
+        }
+        rows
+    }
+
+    /*
+        Worst case of a leaf split: the new leaf, a new node for every ancestor that splits too
+        and the new left child when the split reaches the root.
+     */
+    fn pages_needed_by_split(&mut self, leaf_page_index: usize) -> usize {
+        let mut pages = 2;
+        let mut page = self.pager.get_page(leaf_page_index);
+        while !BtreePager::is_root_node(page) {
+            page = self.pager.get_page(BtreePager::get_parent(page));
+            pages += 1;
+        }
+        pages
+    }
+
+    pub(crate) fn table_full_error(table_name: &str) -> String {
+        format!("Table `{}` is full, a table can not use more than {} pages.", table_name, TABLE_MAX_PAGES)
+    }
+
     fn split_and_insert(&mut self, page_index: usize, cell_index: usize, row: &RowBytes) {
         /*
           Create a new node and move half the cells over.
@@ -370,8 +551,8 @@ impl BtreeTable {
         BtreePager::set_leaf_node_next_leaf(old_node, new_page_index);
 
         /*
-          All existing keys plus new key should be divided
-          evenly between old (left) and new (right) nodes.
+          All existing keys plus new key are divided between old (left)
+          and new (right) nodes following the fill factor.
           Starting from the right, move each key to correct position.
         */
         for i in (0..=self.pager.get_body_layout().leaf_node_max_cells).rev() {
@@ -383,7 +564,11 @@ impl BtreeTable {
                 destination_node = old_node;
             }
             // index_within_node will always decrement until it arrives to 0, then destination_node will be switched to old_node
-            let index_within_node = i % self.pager.get_body_layout().leaf_node_left_split_count;
+            let index_within_node = if i >= self.pager.get_body_layout().leaf_node_left_split_count {
+                i - self.pager.get_body_layout().leaf_node_left_split_count
+            } else {
+                i
+            };
             let cell_pointer = self
                 .pager
                 .leaf_node_cell(destination_node, index_within_node);
@@ -437,7 +622,7 @@ impl BtreeTable {
             let old_key_cell_index = self.internal_node_find_child(parent_page, &old_biggest_key);
             // old_node is split and contains left halves rows (lower halves)
             // so it's necessary to replace old_biggest_key to new_biggest_key
-            BtreePager::set_internal_node_cell_key(
+            self.pager.set_internal_node_cell_key(
                 parent_page,
                 old_key_cell_index,
                 self.key_size,
@@ -451,6 +636,10 @@ impl BtreeTable {
         let page = self.pager.get_or_create_page(page_index);
         let num_cells = BtreePager::get_leaf_node_num_cells(page);
         if cell_index < num_cells {
+            /*
+                shift the cells `cell_index..num_cells` one cell to the right, exactly `num_cells - cell_index` cells.
+                The last one lands at `num_cells`, which is free because the caller splits full leaves.
+             */
             copy(
                 self.pager.leaf_node_cell(page, cell_index),
                 self.pager.leaf_node_cell(page, cell_index + 1),
@@ -461,18 +650,183 @@ impl BtreeTable {
         self.pager
             .set_leaf_node_cell_key(page, cell_index, self.key_size, &key);
         BtreePager::increment_leaf_node_cells_num(page);
-        self.pager.mark_page_as_updated(page_index);
         row.serialize_row(self.pager.get_leaf_node_value(page, cell_index));
     }
 
-    pub(crate) fn table_find_by_key(&self, key: &Value, operator: Operator) -> WriteReadCursor {
+    /*
+        A key of another type can not be compared to the stored keys, INT and FLOAT keys accept both kinds of numbers.
+        The values of an IN list are checked one by one.
+     */
+    fn check_key_type(&self, key: &Value) -> Result<(), String> {
+        let valid = match (&self.key_type, key) {
+            (_, Value::ARRAY(values)) => return values.iter().try_for_each(|v| self.check_key_type(v)),
+            (DataType::INTEGER | DataType::FLOAT, Value::INT(_) | Value::FLOAT(_)) => true,
+            (DataType::TEXT(_), Value::TEXT(_)) => true,
+            (DataType::BOOLEAN, Value::BOOL(_)) => true,
+            (DataType::BLOB(_), Value::BLOB(_)) => true,
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "Key `{}` is of type {}, it can not be compared to `{}`.",
+                self.key_field_name,
+                self.key_type.to_sql(),
+                key.to_sql_literal()
+            ))
+        }
+    }
+
+    pub(crate) fn table_find_by_key(&self, key: &Value, operator: Operator) -> Result<WriteReadCursor<'_>, String> {
+        self.check_key_type(key)?;
         unsafe {
             let s_ptr: &mut Self = std::mem::transmute(self as *const Self);
 
             let node_type = (*s_ptr).pager.get_node_type_by_index(self.root_page_index);
             match node_type {
-                NodeType::Internal => (*s_ptr).internal_node_find(self.root_page_index, &key, operator),
-                NodeType::Leaf => (*s_ptr).leaf_node_find(self.root_page_index, &key, operator),
+                NodeType::Internal => Ok((*s_ptr).internal_node_find(self.root_page_index, key, operator)),
+                NodeType::Leaf => Ok((*s_ptr).leaf_node_find(self.root_page_index, key, operator)),
+            }
+        }
+    }
+
+    /*
+        Point lookup: the row stored under exactly this key, if any
+     */
+    pub fn get_by_key(&self, key: &Value) -> Option<RowBytes> {
+        let cursor = self.table_find_by_key(key, Operator::EQUALS(false)).ok()?;
+        let page_index = cursor.page_index;
+        let cell_index = cursor.cell_index;
+
+        let page = self.pager.get_page(page_index);
+        if cell_index >= BtreePager::get_leaf_node_num_cells(page) {
+            return None;
+        }
+
+        // an INT key equals the FLOAT of the same number
+        let found_key = self.pager.get_leaf_node_cell_key(page, cell_index, &self.key_type);
+        if !Operator::EQUALS(false).operate(&found_key, key) {
+            return None;
+        }
+        Some(load_row(self.get_row_value(page_index, cell_index), &self.table_metadata, &self.overflow))
+    }
+
+    /*
+        Walk the whole tree checking its invariants, the first violation is returned as the error
+     */
+    pub fn verify(&self) -> Result<(), String> {
+        let mut leaves = vec![];
+        self.verify_node(self.root_page_index, None, &mut leaves)?;
+
+        // following the sibling chain from the leftmost leaf must visit every leaf once, in key order
+        let mut chain = vec![leaves[0]];
+        loop {
+            let next_leaf = BtreePager::get_leaf_node_next_leaf(self.pager.get_page(*chain.last().unwrap()));
+            if next_leaf == 0 {
+                break;
+            }
+            if chain.len() == leaves.len() {
+                return Err(String::from("Leaf sibling chain is longer than the number of leaves."));
+            }
+            chain.push(next_leaf);
+        }
+        if chain != leaves {
+            return Err(format!(
+                "Leaf sibling chain {:?} does not match the leaves {:?} in key order.",
+                chain, leaves
+            ));
+        }
+        Ok(())
+    }
+
+    /*
+        Returns the biggest key of the subtree, None when it holds no key
+     */
+    fn verify_node(
+        &self,
+        page_index: usize,
+        parent_index: Option<usize>,
+        leaves: &mut Vec<usize>,
+    ) -> Result<Option<Value>, String> {
+        let node = self.pager.get_page(page_index);
+        let is_root = BtreePager::is_root_node(node);
+        match parent_index {
+            None if !is_root => return Err(format!("Page {} is the root but not flagged as root.", page_index)),
+            Some(_) if is_root => return Err(format!("Page {} is flagged as root but has a parent.", page_index)),
+            Some(parent) if BtreePager::get_parent(node) != parent => {
+                return Err(format!(
+                    "Page {} points to parent {} instead of {}.",
+                    page_index,
+                    BtreePager::get_parent(node),
+                    parent
+                ));
+            }
+            _ => {}
+        }
+
+        match BtreePager::get_node_type(node) {
+            NodeType::Leaf => {
+                leaves.push(page_index);
+                let layout = self.pager.get_body_layout();
+                let num_cells = BtreePager::get_leaf_node_num_cells(node);
+                if num_cells > layout.leaf_node_max_cells {
+                    return Err(format!(
+                        "Leaf {} holds {} cells, more than the maximum of {}.",
+                        page_index, num_cells, layout.leaf_node_max_cells
+                    ));
+                }
+                // a split leaves the smaller part in one of the two leaves
+                let min_cells = layout.leaf_node_left_split_count.min(layout.leaf_node_right_split_count);
+                if !is_root && num_cells < min_cells {
+                    return Err(format!(
+                        "Leaf {} holds {} cells, less than the minimum of {}.",
+                        page_index, num_cells, min_cells
+                    ));
+                }
+
+                let mut biggest: Option<Value> = None;
+                for cell_index in 0..num_cells {
+                    let key = self.pager.get_leaf_node_cell_key(node, cell_index, &self.key_type);
+                    if biggest.as_ref().is_some_and(|previous| *previous > key) {
+                        return Err(format!("Keys of leaf {} are not sorted.", page_index));
+                    }
+                    biggest = Some(key);
+                }
+                Ok(biggest)
+            }
+            NodeType::Internal => {
+                let num_keys = BtreePager::get_internal_node_num_keys(node);
+                let mut previous_key: Option<Value> = None;
+                for key_index in 0..num_keys {
+                    let key = self.pager.get_internal_node_cell_key(node, key_index, &self.key_type);
+                    if previous_key.as_ref().is_some_and(|previous| *previous > key) {
+                        return Err(format!("Keys of internal node {} are not sorted.", page_index));
+                    }
+
+                    let child_index = self.pager.get_internal_node_child(node, key_index);
+                    let child_biggest = self.verify_node(child_index, Some(page_index), leaves)?;
+                    if child_biggest.as_ref() != Some(&key) {
+                        return Err(format!(
+                            "Key {} of internal node {} does not match the biggest key {} of child {}.",
+                            key.to_string(),
+                            page_index,
+                            child_biggest.map(|k| k.to_string()).unwrap_or(String::from("(empty)")),
+                            child_index
+                        ));
+                    }
+                    previous_key = Some(key);
+                }
+
+                let right_child = BtreePager::get_internal_node_right_child(node);
+                let right_biggest = self.verify_node(right_child, Some(page_index), leaves)?;
+                match (&previous_key, &right_biggest) {
+                    (Some(key), Some(biggest)) if biggest <= key => Err(format!(
+                        "Right child {} of internal node {} holds keys not bigger than its last key.",
+                        right_child, page_index
+                    )),
+                    _ => Ok(right_biggest),
+                }
             }
         }
     }
@@ -492,8 +846,12 @@ impl BtreeTable {
         }
     }
 
+    /*
+        Keys are compared as decoded Values, never as raw bytes: the little-endian
+        two's complement bytes of a negative INT would sort after the positive ones.
+     */
     fn leaf_node_find(&mut self, page_index: usize, key: &Value, operator: Operator) -> WriteReadCursor {
-        let node = self.pager.get_or_create_page(page_index);
+        let node = self.pager.get_page(page_index);
         let cells_num = BtreePager::get_leaf_node_num_cells(node);
 
         let mut min_index = 0;
@@ -524,7 +882,7 @@ impl BtreeTable {
         let mut cell_index = 0;
         if biggest {
             cell_index =
-                BtreePager::get_leaf_node_num_cells(self.pager.get_or_create_page(page_index))
+                BtreePager::get_leaf_node_num_cells(self.pager.get_page(page_index))
         }
         WriteReadCursor::at(self, page_index, cell_index)
     }
@@ -539,7 +897,7 @@ impl BtreeTable {
         let mut max_index = num_keys;
         while max_index != min_index {
             let index = (min_index + max_index) / 2;
-            let key_at_index = BtreePager::get_internal_node_cell_key(node, index, &self.key_type);
+            let key_at_index = self.pager.get_internal_node_cell_key(node, index, &self.key_type);
             if *key <= key_at_index {
                 max_index = index;
             } else {
@@ -557,7 +915,7 @@ impl BtreeTable {
         /*
           Return the index of the child which contains the smallest key
         */
-        let node = self.pager.get_or_create_page(page_index);
+        let node = self.pager.get_page(page_index);
 
         let mut key_index: usize = 0;
 
@@ -565,8 +923,8 @@ impl BtreeTable {
             key_index = BtreePager::get_internal_node_num_keys(node) - 1;
         }
 
-        let child_index = BtreePager::get_internal_node_child(node, key_index);
-        let child = self.pager.get_or_create_page(child_index);
+        let child_index = self.pager.get_internal_node_child(node, key_index);
+        let child = self.pager.get_page(child_index);
 
         match BtreePager::get_node_type(child) {
             NodeType::Leaf => self.leaf_node_find_smallest_or_biggest(child_index, biggest),
@@ -575,10 +933,10 @@ impl BtreeTable {
     }
 
     fn internal_node_find(&mut self, page_index: usize, key: &Value, operator: Operator) -> WriteReadCursor {
-        let node = self.pager.get_or_create_page(page_index);
+        let node = self.pager.get_page(page_index);
         let cell_index = self.internal_node_find_child(node, key);
-        let child_index = BtreePager::get_internal_node_child(node, cell_index);
-        let child = self.pager.get_or_create_page(child_index);
+        let child_index = self.pager.get_internal_node_child(node, cell_index);
+        let child = self.pager.get_page(child_index);
         match BtreePager::get_node_type(child) {
             NodeType::Leaf => self.leaf_node_find(child_index, key, operator),
             NodeType::Internal => self.internal_node_find(child_index, key, operator),
@@ -605,7 +963,7 @@ impl BtreeTable {
 
         /* Left child has data copied from old root */
         unsafe {
-            ptr::copy_nonoverlapping(root, left_child, PAGE_SIZE);
+            ptr::copy_nonoverlapping(root, left_child, self.pager.get_page_size());
             BtreePager::set_root_node(left_child, false)
         };
 
@@ -615,7 +973,7 @@ impl BtreeTable {
             for i in 0..num_keys {
                 child = self
                     .pager
-                    .get_or_create_page(BtreePager::get_internal_node_child(left_child, i));
+                    .get_or_create_page(self.pager.get_internal_node_child(left_child, i));
                 BtreePager::set_parent(child, left_child_page_num);
             }
             child = self
@@ -630,9 +988,9 @@ impl BtreeTable {
 
         BtreePager::set_internal_node_num_keys(root, 1);
         // first child index = left child index
-        BtreePager::set_internal_node_child(root, 0, left_child_page_num);
+        self.pager.set_internal_node_child(root, 0, left_child_page_num);
         let left_child_biggest_key = self.pager.get_node_biggest_key(left_child, &self.key_type);
-        BtreePager::set_internal_node_cell_key(root, 0, self.key_size, &left_child_biggest_key);
+        self.pager.set_internal_node_cell_key(root, 0, self.key_size, &left_child_biggest_key);
         BtreePager::set_internal_node_right_child(root, right_child_page_index);
 
         BtreePager::set_parent(left_child, self.root_page_index);
@@ -676,7 +1034,7 @@ impl BtreeTable {
             to the new root's left child, new_page_num will already point to
             the new root's right child
              */
-            old_page_index = BtreePager::get_internal_node_child(parent.cast_const(), 0);
+            old_page_index = self.pager.get_internal_node_child(parent.cast_const(), 0);
             old_node = self.pager.get_or_create_page(old_page_index);
         } else {
             parent = self
@@ -700,8 +1058,8 @@ impl BtreeTable {
         /*
         For each key until you get to the middle key, move the key and the child to the new node
         */
-        for i in (INTERNAL_NODE_MAX_KEYS / 2 + 1..INTERNAL_NODE_MAX_KEYS - 1).rev() {
-            cur_page_num = BtreePager::get_internal_node_child(old_node, i);
+        for i in (INTERNAL_NODE_MAX_KEYS / 2 + 1..INTERNAL_NODE_MAX_KEYS).rev() {
+            cur_page_num = self.pager.get_internal_node_child(old_node, i);
             cur = self.pager.get_or_create_page(cur_page_num);
 
             self.internal_node_insert(new_page_index, cur_page_num);
@@ -716,7 +1074,7 @@ impl BtreeTable {
         */
         BtreePager::set_internal_node_right_child(
             old_node,
-            BtreePager::get_internal_node_child(old_node, old_num_keys - 1),
+            self.pager.get_internal_node_child(old_node, old_num_keys - 1),
         );
 
         old_num_keys -= 1;
@@ -738,12 +1096,8 @@ impl BtreeTable {
         BtreePager::set_parent(child, destination_page_index);
 
         let old_key_cell_index = self.internal_node_find_child(parent, &old_max);
-        BtreePager::set_internal_node_cell_key(
-            parent,
-            old_key_cell_index,
-            self.key_size,
-            &self.pager.get_node_biggest_key(old_node, &self.key_type),
-        );
+        let old_node_biggest_key = self.pager.get_node_biggest_key(old_node, &self.key_type);
+        self.pager.set_internal_node_cell_key(parent, old_key_cell_index, self.key_size, &old_node_biggest_key);
 
         if !splitting_root {
             self.internal_node_insert(BtreePager::get_parent(old_node), new_page_index);
@@ -796,8 +1150,8 @@ impl BtreeTable {
         let biggest_key = self.pager.get_node_biggest_key(right_child, &self.key_type);
         if child_max_key > biggest_key {
             /* Replace right child */
-            BtreePager::set_internal_node_child(parent, original_num_keys, right_child_page_index);
-            BtreePager::set_internal_node_cell_key(
+            self.pager.set_internal_node_child(parent, original_num_keys, right_child_page_index);
+            self.pager.set_internal_node_cell_key(
                 parent,
                 original_num_keys,
                 self.key_size,
@@ -807,12 +1161,12 @@ impl BtreeTable {
         } else {
             /* Make room for the new cell */
             copy(
-                BtreePager::get_internal_node_cell(parent, cell_index),
-                BtreePager::get_internal_node_cell(parent, cell_index + 1),
-                INTERNAL_NODE_CELL_SIZE * (original_num_keys - cell_index),
+                self.pager.get_internal_node_cell(parent, cell_index),
+                self.pager.get_internal_node_cell(parent, cell_index + 1),
+                self.pager.get_body_layout().internal_node_cell_size * (original_num_keys - cell_index),
             );
-            BtreePager::set_internal_node_child(parent, cell_index, child_index);
-            BtreePager::set_internal_node_cell_key(
+            self.pager.set_internal_node_child(parent, cell_index, child_index);
+            self.pager.set_internal_node_cell_key(
                 parent,
                 cell_index,
                 self.key_size,
@@ -820,37 +1174,15 @@ impl BtreeTable {
             );
         }
     }
-
-    pub unsafe fn read_compare_value(
-        &self,
-        row_ptr: *const u8,
-        buf: &mut Vec<u8>,
-        condition_expr: &ConditionExpr,
-    ) -> bool {
-        let field_meta = self
-            .table_metadata
-            .get_field_metadata(&condition_expr.field)
-            .unwrap();
-
-        copy_nonoverlapping(
-            row_ptr.add(field_meta.offset),
-            buf.as_mut_ptr(),
-            field_meta.size,
-        );
-        buf.set_len(field_meta.size);
-        let value = Value::from_ptr(&field_meta.data_def.data_type, buf.as_ptr());
-        buf.clear();
-
-        condition_expr
-            .operator
-            .operate(&value, &condition_expr.value)
-    }
 }
 
 pub struct SequentialTable {
     pub root_page_index: usize,
     pub cells_num_by_page: usize,
     pub pager: Box<SequentialPager>,
+    // `WITH (ROW_FORMAT = VARIABLE)`, rows are stored encoded with their real length
+    pub variable_rows: bool,
+    overflow: Option<OverflowPager>,
     table_metadata: Rc<TableStructureMetadata>,
 }
 
@@ -858,18 +1190,27 @@ impl SequentialTable {
     pub(crate) fn new(
         path: &PathBuf,
         table_metadata: Rc<TableStructureMetadata>,
+        format: DbFormat,
     ) -> Result<SequentialTable, String> {
         match OpenOptions::new()
             .read(true)
             .write(true)
             .open(path) {
-            Ok(file) => {
-                let pager = SequentialPager::open(file);
+            Ok(mut file) => {
+                let mut header = [0u8; SEQUENTIAL_NODE_HEADER_SIZE];
+                if file.read_exact(&mut header).is_err() {
+                    return Err(format!(
+                        "Can not load the header of table {}",
+                        table_metadata.table_name
+                    ));
+                }
+                let pager = SequentialPager::open(file, format)?;
                 Ok(SequentialTable {
                     root_page_index: 0,
-                    cells_num_by_page: (PAGE_SIZE - SEQUENTIAL_NODE_HEADER_SIZE)
-                        / table_metadata.row_size,
+                    cells_num_by_page: sequential_cells_num_by_page(format, table_metadata.stored_row_size())?,
                     pager: Box::new(pager),
+                    variable_rows: read_u32(header.as_ptr()) == VARIABLE_ROW_FORMAT,
+                    overflow: open_overflow(path, &table_metadata, format)?,
                     table_metadata,
                 })
             }
@@ -884,54 +1225,71 @@ impl SequentialTable {
         &mut self,
         page_index: usize,
         cell_index: usize,
-        row: &RowToInsert,
+        row: &RowBytes,
     ) {
         let ptr = self.get_row_value_mut(page_index, cell_index);
-        copy_nonoverlapping(row.raw_data.as_ptr(), ptr, self.table_metadata.row_size);
+        copy_nonoverlapping(row.as_ptr(), ptr, self.table_metadata.stored_row_size());
         self.pager.increment_cells_num(page_index);
     }
 
-    pub unsafe fn read_compare_value(
-        &self,
-        row_ptr: *const u8,
-        buf: &mut Vec<u8>,
-        cluster: &ConditionCluster,
-    ) -> bool {
-        let mut matched: Option<bool> = None;
-        for condition in &cluster.conditions {
-            let logical_op: LogicalOperator;
-            let compare_result = match condition {
-                Condition::Cluster(c) => {
-                    logical_op = c.logical_operator;
-                    self.read_compare_value(row_ptr, buf, c)
+    /*
+        Variable-length encoding of a row: a TEXT field keeps only its bytes before the zero padding,
+        prefixed with their length, a BLOB keeps its length and its real bytes, other fields are copied as is.
+     */
+    fn encode_row(&self, row: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(self.table_metadata.row_size);
+        for field in self.table_metadata.fields.iter() {
+            let bytes = &row[field.offset..field.offset + field.size];
+            match field.data_def.data_type {
+                DataType::TEXT(_) => {
+                    let length = bytes.iter().position(|c| *c == 0).unwrap_or(bytes.len());
+                    encoded.extend_from_slice(&(length as u16).to_le_bytes());
+                    encoded.extend_from_slice(&bytes[..length]);
                 }
-                Condition::Expr(expr) => {
-                    let field_meta = self
-                        .table_metadata
-                        .get_field_metadata(&expr.field)
-                        .unwrap();
-
-                    copy_nonoverlapping(
-                        row_ptr.add(field_meta.offset),
-                        buf.as_mut_ptr(),
-                        field_meta.size,
-                    );
-                    buf.set_len(field_meta.size);
-                    let value = Value::from_ptr(&field_meta.data_def.data_type, buf.as_ptr());
-                    buf.clear();
-                    logical_op = expr.logical_operator;
-                    expr.operator.operate(&value, &expr.value)
+                DataType::BLOB(_) => {
+                    let length = read_uint(bytes.as_ptr(), BLOB_LENGTH_SIZE);
+                    encoded.extend_from_slice(&bytes[..BLOB_LENGTH_SIZE + length]);
                 }
-            };
-
-            if matched.is_none() {
-                matched = Some(compare_result);
-            } else {
-                matched = Some(logical_op.operate(matched.unwrap(), compare_result));
+                _ => encoded.extend_from_slice(bytes),
             }
         }
+        encoded
+    }
+
+    /*
+        The fixed-width row of an encoded row, TEXT and BLOB fields padded with zeros
+     */
+    fn decode_row(&self, encoded: &[u8]) -> RowBytes {
+        let mut data = vec![0; self.table_metadata.row_size];
+        let mut position = 0;
+        for field in self.table_metadata.fields.iter() {
+            let length = match field.data_def.data_type {
+                DataType::TEXT(_) => {
+                    let length = read_uint(encoded[position..].as_ptr(), VARIABLE_TEXT_LENGTH_SIZE);
+                    position += VARIABLE_TEXT_LENGTH_SIZE;
+                    length
+                }
+                DataType::BLOB(_) => BLOB_LENGTH_SIZE + read_uint(encoded[position..].as_ptr(), BLOB_LENGTH_SIZE),
+                _ => field.size,
+            };
+            data[field.offset..field.offset + length].copy_from_slice(&encoded[position..position + length]);
+            position += length;
+        }
+        RowBytes { data }
+    }
 
-        matched.unwrap()
+    fn insert_variable(&mut self, row: &RowToInsert) -> Result<(), String> {
+        let encoded = self.encode_row(&row.raw_data);
+        let last_page = self.pager.get_total_page().saturating_sub(1);
+        if self.pager.append_variable_row(last_page, &encoded) {
+            return Ok(());
+        }
+        if !self.pager.can_allocate(1) {
+            return Err(BtreeTable::table_full_error(&self.table_metadata.table_name));
+        }
+        // `create table` checks that the longest encoded row fits in an empty page
+        self.pager.append_variable_row(last_page + 1, &encoded);
+        Ok(())
     }
 }
 
@@ -942,72 +1300,33 @@ impl Table for SequentialTable {
     }
 
     fn insert(&mut self, row: &RowToInsert) -> Result<(), String> {
-        let mut write_to_page = self.pager.get_total_page();
+        if self.variable_rows {
+            return self.insert_variable(row);
+        }
+        // rows are appended to the last page, the first insert creates page 0
+        let mut write_to_page = self.pager.get_total_page().saturating_sub(1);
         let mut write_to_cell_index = self.get_num_cells(write_to_page);
 
         if write_to_cell_index >= self.cells_num_by_page {
+            if !self.pager.can_allocate(1) {
+                return Err(BtreeTable::table_full_error(&self.table_metadata.table_name));
+            }
             write_to_page += 1;
             write_to_cell_index = 0;
         }
 
-        self.insert_to_end(write_to_page, write_to_cell_index, row);
+        let stored = store_row(row, &self.table_metadata, &mut self.overflow)?;
+        self.insert_to_end(write_to_page, write_to_cell_index, &stored);
         Ok(())
     }
 
-    fn find_by_condition_clusters(
-        &self,
-        condition_clusters: &Vec<ConditionCluster>,
-    ) -> Vec<RowBytes> {
+    fn find_by_condition(&self, condition: &Condition) -> Vec<RowBytes> {
         let mut result = vec![];
-        let mut last_op: Option<LogicalOperator> = None;
-        for cluster in condition_clusters {
-            let cluster_result = self.find_by_condition_cluster(cluster);
-            if last_op.is_none() || last_op.clone().unwrap().combine(cluster.logical_operator) == LogicalOperator::OR {
-                result.extend(cluster_result);
-                if last_op.is_none() {
-                    last_op = Some(cluster.logical_operator);
-                } else {
-                    last_op = Some(last_op.unwrap().combine(cluster.logical_operator));
-                }
-            } else {
-                let set: HashSet<RowBytes> = HashSet::from_iter(result.into_iter());
-                result = vec![];
-                for r in cluster_result {
-                    if set.contains(&r) {
-                        result.push(r);
-                    }
-                }
-            }
-        }
-        result
-    }
-
-    fn find_by_condition_cluster(&self, cluster: &ConditionCluster) -> Vec<RowBytes> {
-        let row_size = self.table_metadata.row_size;
-        let mut cursor = ReadCursor::at(self, 0, 0);
-        let mut result = Vec::new();
-        let mut global_max_field_size: usize = 0;
-
-        for condition in &cluster.conditions {
-            global_max_field_size = max(global_max_field_size, condition.get_field_max_size(&self.table_metadata));
-        }
-
-        let mut field_buf = Vec::<u8>::with_capacity(global_max_field_size);
-
-        unsafe {
-            while !cursor.is_end() {
-                let row_ptr = cursor.cursor_value();
-                let mut matched = true;
-                matched &= self.read_compare_value(row_ptr, &mut field_buf, cluster);
-
-                if matched {
-                    result.push(RowBytes::deserialize_row(row_ptr, row_size));
-                }
-
-                cursor.cursor_advance();
+        self.for_each_row(&mut |row| {
+            if condition.matches(row, &self.table_metadata) {
+                result.push(row.clone());
             }
-        }
-
+        });
         result
     }
 
@@ -1022,18 +1341,40 @@ impl Table for SequentialTable {
     }
 
     fn get_all(&self) -> Vec<RowBytes> {
-        let row_size = self.table_metadata.row_size;
-        let mut cursor = ReadCursor::at(self, 0, 0);
         let mut result = Vec::new();
+        self.for_each_row(&mut |row| result.push(row.clone()));
+        result
+    }
+
+    fn for_each_row(&self, f: &mut dyn FnMut(&RowBytes)) {
+        if self.variable_rows {
+            for page_index in 0..self.pager.get_total_page() {
+                let page = self.pager.get_page(page_index);
+                for cell_index in 0..SequentialPager::get_num_cells(page) {
+                    f(&self.decode_row(self.pager.get_variable_row_value(page, cell_index)));
+                }
+            }
+            return;
+        }
+        let mut cursor = ReadCursor::at(self, 0, 0);
         while !cursor.is_end() {
-            result.push(RowBytes::deserialize_row(cursor.cursor_value(), row_size));
+            f(&load_row(cursor.cursor_value(), &self.table_metadata, &self.overflow));
             cursor.cursor_advance();
         }
-        result
+    }
+
+    /*
+        Every page stores its number of cells, a partially filled last page is counted like the others.
+        The rows are not read.
+     */
+    fn row_count(&self) -> usize {
+        (0..self.pager.get_total_page())
+            .map(|page_index| SequentialPager::get_num_cells(self.pager.get_page(page_index)))
+            .sum()
     }
 
     fn get_row_size(&self) -> usize {
-        self.table_metadata.row_size
+        self.table_metadata.stored_row_size()
     }
 
     fn get_num_cells(&self, page_index: usize) -> usize {
@@ -1049,23 +1390,28 @@ impl Table for SequentialTable {
         }
     }
 
+    /*
+        With variable-length rows, the encoded row and not a fixed-width row, `for_each_row` decodes it
+     */
     fn get_row_value(&self, page_index: usize, cell_index: usize) -> *const u8 {
         let page = self.pager.get_page(page_index);
+        if self.variable_rows {
+            return self.pager.get_variable_row_value(page, cell_index).as_ptr();
+        }
         self.pager
-            .get_row_value(page, cell_index, self.table_metadata.row_size)
+            .get_row_value(page, cell_index, self.table_metadata.stored_row_size())
     }
 
     fn get_row_value_mut(&mut self, page_index: usize, cell_index: usize) -> *mut u8 {
         let page = self.pager.get_or_create_page(page_index);
         self.pager
-            .get_row_value_mut(page, cell_index, self.table_metadata.row_size)
+            .get_row_value_mut(page, cell_index, self.table_metadata.stored_row_size())
     }
 
     fn flush_to_disk(&mut self) {
-        for x in 0..TABLE_MAX_PAGES {
-            if !self.pager.flush_page_to_disk(x) {
-                break;
-            }
+        self.pager.flush_dirty_pages();
+        if let Some(overflow) = self.overflow.as_mut() {
+            overflow.flush_dirty_pages();
         }
     }
 
@@ -1078,3 +1424,503 @@ impl Table for SequentialTable {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::build_path;
+    use crate::sql_engine::sql_structs::{DataType, Operator, Value};
+    use crate::storage_engine::config::DATA_FOLDER;
+    use crate::storage_engine::enums::NodeType;
+    use crate::storage_engine::pagers::BtreePager;
+    use crate::storage_engine::tables::{BtreeTable, SequentialTable};
+    use crate::utils::test_utils::TestDb;
+
+    fn btree<'a>(db: &'a mut TestDb, table: &str) -> &'a BtreeTable {
+        db.table_manager.get_tables(table).unwrap()[0].as_any().downcast_ref::<BtreeTable>().unwrap()
+    }
+
+    /*
+        pages fetched by a statement run right after the table is opened
+     */
+    fn pages_read_by(db: &mut TestDb, table: &str, sql: &str) -> (usize, Vec<Vec<Value>>) {
+        db.reopen();
+        let rows = db.query(sql);
+        (btree(db, table).pager.pages_read(), rows)
+    }
+
+    fn height(btree: &BtreeTable) -> usize {
+        let mut height = 1;
+        let mut node = btree.pager.get_page(btree.root_page_index);
+        while matches!(BtreePager::get_node_type(node), NodeType::Internal) {
+            node = btree.pager.get_page(btree.pager.get_internal_node_child(node, 0));
+            height += 1;
+        }
+        height
+    }
+
+    fn leaves(btree: &BtreeTable) -> usize {
+        (0..btree.pager.get_unused_page_num())
+            .filter(|page_index| matches!(BtreePager::get_node_type(btree.pager.get_page(*page_index)), NodeType::Leaf))
+            .count()
+    }
+
+    fn create_people(db: &mut TestDb, table: &str, rows: i32) {
+        db.run(&format!("create table {} (id int primary key, name text(200));", table)).unwrap();
+        for id in 0..rows {
+            // keys are not inserted in order, leaves split on both sides
+            let id = id * 7 % rows;
+            db.run(&format!("insert into {} (id, name) values ({}, 'row {}');", table, id, id)).unwrap();
+        }
+    }
+
+    #[test]
+    fn point_lookup_reads_one_path_of_the_tree() {
+        let mut db = TestDb::open(&["tables_lookup"]);
+        create_people(&mut db, "tables_lookup", 200);
+
+        let (lookup_reads, rows) = pages_read_by(&mut db, "tables_lookup", "select name from tables_lookup where id = 150;");
+        assert_eq!(rows, vec![vec![Value::TEXT(String::from("row 150"))]]);
+        let (scan_reads, rows) = pages_read_by(&mut db, "tables_lookup", "select id from tables_lookup where name = 'row 150';");
+        assert_eq!(rows, vec![vec![Value::INT(150)]]);
+
+        let btree = btree(&mut db, "tables_lookup");
+        let (height, leaves) = (height(btree), leaves(btree));
+        assert!(height > 2);
+        // the sibling leaf is read when the key is the last one of its leaf
+        assert!(lookup_reads <= height + 1, "{} pages read, the tree is {} high", lookup_reads, height);
+        assert!(scan_reads >= leaves, "{} pages read for {} leaves", scan_reads, leaves);
+    }
+
+    #[test]
+    fn in_list_looks_up_each_key() {
+        let mut db = TestDb::open(&["tables_in_list"]);
+        create_people(&mut db, "tables_in_list", 200);
+
+        let (reads, rows) = pages_read_by(&mut db, "tables_in_list", "select name from tables_in_list where id in [180, 3, 500, 3];");
+        assert_eq!(rows, vec![vec![Value::TEXT(String::from("row 3"))], vec![Value::TEXT(String::from("row 180"))]]);
+        let btree = btree(&mut db, "tables_in_list");
+        // one path from the root per distinct key, the leaves of both keys are far apart
+        assert!(reads <= 2 * height(btree), "{} pages read", reads);
+        assert!(reads < leaves(btree));
+    }
+
+    /*
+        the `name` field of the row stored under `key`
+     */
+    fn name_by_key(btree: &BtreeTable, key: Value) -> Option<Value> {
+        let meta = btree.table_metadata.get_field_metadata("name").unwrap();
+        btree
+            .get_by_key(&key)
+            .map(|row| row.read_key(&meta.data_def.data_type, meta.offset, meta.size))
+    }
+
+    #[test]
+    fn get_by_key_returns_only_the_exact_key() {
+        let mut db = TestDb::open(&["tables_by_int_key", "tables_by_text_key"]);
+        create_people(&mut db, "tables_by_int_key", 200);
+        db.run("create table tables_by_text_key (code text(16) primary key, name text(16));").unwrap();
+        for code in ["b", "ab", "abc", "B"] {
+            db.run(&format!("insert into tables_by_text_key (code, name) values ('{}', 'name of {}');", code, code)).unwrap();
+        }
+        db.reopen();
+
+        let people = btree(&mut db, "tables_by_int_key");
+        assert!(height(people) > 2);
+        for id in [0, 57, 199] {
+            assert_eq!(name_by_key(people, Value::INT(id)), Some(Value::TEXT(format!("row {}", id))));
+        }
+        for id in [-1, 200, 1000] {
+            assert_eq!(name_by_key(people, Value::INT(id)), None);
+        }
+
+        // keys are shorter than the declared size, the padding of the stored key is not compared
+        let codes = btree(&mut db, "tables_by_text_key");
+        for code in ["ab", "B", "abc"] {
+            assert_eq!(name_by_key(codes, Value::TEXT(code.to_string())), Some(Value::TEXT(format!("name of {}", code))));
+        }
+        for code in ["a", "abcd", "c", "A"] {
+            assert_eq!(name_by_key(codes, Value::TEXT(code.to_string())), None);
+        }
+    }
+
+    #[test]
+    fn split_keeps_the_fill_factor_of_the_table() {
+        for fill_factor in [10, 50, 90] {
+            let table = format!("tables_fill_{}", fill_factor);
+            let mut db = TestDb::open(&[table.as_str()]);
+            db.run(&format!("create table {} (id int primary key, name text(200)) with (fillfactor = {});", table, fill_factor)).unwrap();
+            // the fill factor is read back from the table metadata
+            db.reopen();
+            let layout = btree(&mut db, &table).pager.get_body_layout();
+            let (max_cells, left_count, right_count) =
+                (layout.leaf_node_max_cells, layout.leaf_node_left_split_count, layout.leaf_node_right_split_count);
+            assert_eq!(left_count, (max_cells + 1) * fill_factor / 100);
+            for id in 0..=max_cells {
+                db.run(&format!("insert into {} (id, name) values ({}, 'row {}');", table, id, id)).unwrap();
+            }
+            db.reopen();
+
+            let btree = btree(&mut db, &table);
+            assert_eq!(btree.fill_factor, fill_factor);
+            let root = btree.pager.get_page(btree.root_page_index);
+            let left = btree.pager.get_page(btree.pager.get_internal_node_child(root, 0));
+            let right = btree.pager.get_page(btree.pager.get_internal_node_child(root, 1));
+            assert_eq!(BtreePager::get_leaf_node_num_cells(left), left_count);
+            assert_eq!(BtreePager::get_leaf_node_num_cells(right), right_count);
+        }
+    }
+
+    /*
+        inserts rows of about a quarter page until the table is full, returns the number of inserted rows
+     */
+    fn fill(db: &mut TestDb, table: &str) -> i32 {
+        let mut id = 0;
+        loop {
+            match db.run(&format!("insert into {} (id, name) values ({}, 'row {}');", table, id, id)) {
+                Ok(_) => id += 1,
+                Err(e) => {
+                    assert!(e.contains("is full"), "{}", e);
+                    return id;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn full_table_is_a_recoverable_error() {
+        let mut db = TestDb::open(&["tables_full_btree", "tables_full_seq"]);
+        db.run("create table tables_full_btree (id int primary key, name text(1000));").unwrap();
+        db.run("create table tables_full_seq (id int, name text(1000));").unwrap();
+
+        for table in ["tables_full_btree", "tables_full_seq"] {
+            let inserted = fill(&mut db, table);
+            assert!(inserted > 0);
+            assert_eq!(db.query(&format!("select count(*) from {};", table)), vec![vec![Value::INT(inserted)]]);
+            db.reopen();
+            assert_eq!(
+                db.query(&format!("select name from {} where id = {};", table, inserted - 1)),
+                vec![vec![Value::TEXT(format!("row {}", inserted - 1))]]
+            );
+        }
+        db.table_manager.verify("tables_full_btree").unwrap();
+    }
+
+    #[test]
+    fn long_text_is_stored_in_overflow_pages() {
+        let mut db = TestDb::open(&["tables_overflow_btree", "tables_overflow_seq"]);
+        db.run("create table tables_overflow_btree (id int primary key, note text(12000), tag text(8));").unwrap();
+        db.run("create table tables_overflow_seq (id int, note text(12000));").unwrap();
+        let notes: Vec<String> = (1..=3).map(|i| format!("{}", i).repeat(i * 3500)).collect();
+        for (i, note) in notes.iter().enumerate() {
+            db.run(&format!("insert into tables_overflow_btree values ({}, '{}', 't{}');", i, note, i)).unwrap();
+            db.run(&format!("insert into tables_overflow_seq values ({}, '{}');", i, note)).unwrap();
+        }
+
+        // a row takes its pointer in the cell, several rows fit in a leaf page
+        assert_eq!(leaves(btree(&mut db, "tables_overflow_btree")), 1);
+        for _ in 0..2 {
+            for table in ["tables_overflow_btree", "tables_overflow_seq"] {
+                let rows = db.query(&format!("select note from {};", table));
+                let expected: Vec<Vec<Value>> = notes.iter().map(|n| vec![Value::TEXT(n.clone())]).collect();
+                assert_eq!(rows, expected);
+                assert_eq!(
+                    db.query(&format!("select id from {} where note = '{}';", table, notes[1])),
+                    vec![vec![Value::INT(1)]]
+                );
+            }
+            assert_eq!(db.query("select tag from tables_overflow_btree where id = 2;"), vec![vec![Value::TEXT(String::from("t2"))]]);
+            db.reopen();
+        }
+    }
+
+    /*
+        runs `verify` with one node of the tree changed in memory by `corrupt`, then restored by `restore`
+     */
+    fn verify_corrupted(db: &mut TestDb, table: &str, corrupt: &dyn Fn(&BtreeTable), restore: &dyn Fn(&BtreeTable)) -> String {
+        let btree = btree(db, table);
+        corrupt(btree);
+        let error = btree.verify().unwrap_err();
+        restore(btree);
+        error
+    }
+
+    #[test]
+    fn verify_reports_a_corrupted_tree() {
+        let mut db = TestDb::open(&["tables_verify"]);
+        create_people(&mut db, "tables_verify", 200);
+        btree(&mut db, "tables_verify").verify().unwrap();
+        let first_leaf = btree(&mut db, "tables_verify").find_smallest_or_biggest_key(false).page_index;
+        let set_first_key = |btree: &BtreeTable, key: i32| {
+            let page = btree.pager.get_page(first_leaf) as *mut u8;
+            btree.pager.set_leaf_node_cell_key(page, 0, btree.key_size, &Value::INT(key));
+        };
+
+        let error = verify_corrupted(&mut db, "tables_verify", &|b| set_first_key(b, 1000), &|b| set_first_key(b, 0));
+        assert_eq!(error, format!("Keys of leaf {} are not sorted.", first_leaf));
+
+        let second_leaf = BtreePager::get_leaf_node_next_leaf(btree(&mut db, "tables_verify").pager.get_page(first_leaf));
+        let error = verify_corrupted(
+            &mut db,
+            "tables_verify",
+            &|b| BtreePager::set_leaf_node_next_leaf(b.pager.get_page(first_leaf) as *mut u8, 0),
+            &|b| BtreePager::set_leaf_node_next_leaf(b.pager.get_page(first_leaf) as *mut u8, second_leaf),
+        );
+        assert!(error.starts_with("Leaf sibling chain"), "{}", error);
+
+        let parent = BtreePager::get_parent(btree(&mut db, "tables_verify").pager.get_page(first_leaf));
+        let error = verify_corrupted(
+            &mut db,
+            "tables_verify",
+            &|b| BtreePager::set_parent(b.pager.get_page(first_leaf) as *mut u8, second_leaf),
+            &|b| BtreePager::set_parent(b.pager.get_page(first_leaf) as *mut u8, parent),
+        );
+        assert_eq!(error, format!("Page {} points to parent {} instead of {}.", first_leaf, second_leaf, parent));
+
+        btree(&mut db, "tables_verify").verify().unwrap();
+    }
+
+    #[test]
+    fn key_of_another_type_is_an_error() {
+        let mut db = TestDb::open(&["tables_key_type_int", "tables_key_type_text"]);
+        db.run("create table tables_key_type_int (id int primary key, name text(8));").unwrap();
+        db.run("create table tables_key_type_text (code text(8) primary key, name text(8));").unwrap();
+        db.run("insert into tables_key_type_int (id, name) values (1, 'a');").unwrap();
+        db.run("insert into tables_key_type_text (code, name) values ('1', 'a');").unwrap();
+
+        assert_eq!(
+            db.run("select * from tables_key_type_int where id = 'abc';").err().unwrap(),
+            "`id` is of type INT, it can not be compared to `'abc'` of type TEXT."
+        );
+        assert_eq!(
+            db.run("select * from tables_key_type_int where id > true;").err().unwrap(),
+            "`id` is of type INT, it can not be compared to `true` of type BOOL."
+        );
+        assert_eq!(
+            db.run("select * from tables_key_type_text where code = 1;").err().unwrap(),
+            "`code` is of type TEXT, it can not be compared to `1` of type INT."
+        );
+        // the B-tree checks the key it is given too
+        assert_eq!(
+            btree(&mut db, "tables_key_type_text").table_find_by_key(&Value::INT(1), Operator::EQUALS(false)).err().unwrap(),
+            "Key `code` is of type TEXT(8), it can not be compared to `1`."
+        );
+        // INT and FLOAT are both numbers
+        assert_eq!(db.query("select name from tables_key_type_int where id = 1.0;"), vec![vec![Value::TEXT(String::from("a"))]]);
+    }
+
+    #[test]
+    fn duplicate_text_key_is_rejected() {
+        let mut db = TestDb::open(&["tables_duplicate_text"]);
+        db.run("create table tables_duplicate_text (code text(6) primary key, name text(200));").unwrap();
+        for id in 0..100 {
+            db.run(&format!("insert into tables_duplicate_text (code, name) values ('k{}', 'row {}');", id * 7 % 100, id)).unwrap();
+        }
+        // prefixes of a stored key are other keys, their padding does not make them equal
+        db.run("insert into tables_duplicate_text (code, name) values ('k', 'prefix');").unwrap();
+        db.run("insert into tables_duplicate_text (code, name) values ('k1000', 'longer');").unwrap();
+        db.reopen();
+        assert!(leaves(btree(&mut db, "tables_duplicate_text")) > 1);
+
+        for code in ["k", "k0", "k1", "k42", "k99", "k1000"] {
+            assert_eq!(
+                db.run(&format!("insert into tables_duplicate_text (code, name) values ('{}', 'again');", code)).err().unwrap(),
+                format!("Duplicate value '{}' for primary key `code`.", code)
+            );
+        }
+        assert_eq!(db.query("select count(*) from tables_duplicate_text;"), vec![vec![Value::INT(102)]]);
+        assert_eq!(db.query("select name from tables_duplicate_text where code = 'k';"), vec![vec![Value::TEXT(String::from("prefix"))]]);
+        btree(&mut db, "tables_duplicate_text").verify().unwrap();
+    }
+
+    #[test]
+    fn limit_stops_the_scan() {
+        let mut db = TestDb::open(&["tables_limit"]);
+        create_people(&mut db, "tables_limit", 200);
+
+        let (limited_reads, rows) = pages_read_by(&mut db, "tables_limit", "select id from tables_limit where id > 10 limit 3;");
+        assert_eq!(rows, vec![vec![Value::INT(11)], vec![Value::INT(12)], vec![Value::INT(13)]]);
+        let (full_reads, rows) = pages_read_by(&mut db, "tables_limit", "select id from tables_limit where id > 10;");
+        assert_eq!(rows.len(), 189);
+
+        let btree = btree(&mut db, "tables_limit");
+        // the seek reads one path, the three rows are in one or two leaves
+        assert!(limited_reads <= height(btree) + 1, "{} pages read", limited_reads);
+        assert!(full_reads >= leaves(btree), "{} pages read for {} leaves", full_reads, leaves(btree));
+    }
+
+    #[test]
+    fn variable_rows_take_their_real_length() {
+        let mut db = TestDb::open(&["tables_variable_rows", "tables_fixed_rows"]);
+        db.run("create table tables_variable_rows (id int, name text(255), b blob(16)) with (row_format = variable);").unwrap();
+        db.run("create table tables_fixed_rows (id int, name text(255), b blob(16));").unwrap();
+        let mut expected = vec![];
+        for id in 0..250 {
+            // empty, short and full TEXT values, each length once
+            let name = "x".repeat(id as usize * 37 % 256);
+            for table in ["tables_variable_rows", "tables_fixed_rows"] {
+                db.run(&format!("insert into {} (id, name, b) values ({}, '{}', x'0a0b');", table, id, name)).unwrap();
+            }
+            expected.push(vec![Value::INT(id), Value::TEXT(name), Value::BLOB(vec![0x0a, 0x0b])]);
+        }
+        db.reopen();
+
+        assert_eq!(db.query("select * from tables_variable_rows;"), expected);
+        assert_eq!(db.query("select count(*) from tables_variable_rows;"), vec![vec![Value::INT(250)]]);
+        assert_eq!(db.query("select id from tables_variable_rows where name = 'xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx';"), vec![vec![Value::INT(1)]]);
+
+        let size = |table: &str| std::fs::metadata(build_path!(DATA_FOLDER, table, table.to_owned() + ".seq")).unwrap().len();
+        assert!(size("tables_variable_rows") * 3 / 2 < size("tables_fixed_rows"));
+    }
+
+    #[test]
+    fn row_count_adds_the_cells_of_every_page() {
+        let mut db = TestDb::open(&["tables_row_count"]);
+        db.run("create table tables_row_count (id int, name text(200));").unwrap();
+        let count = |db: &mut TestDb| db.table_manager.get_tables("tables_row_count").unwrap()[0].row_count();
+        assert_eq!(count(&mut db), 0);
+
+        // about 20 rows fill a page, the last page is partially filled
+        for id in 0..90 {
+            db.run(&format!("insert into tables_row_count (id, name) values ({}, 'row {}');", id, id)).unwrap();
+        }
+        let table = db.table_manager.get_tables("tables_row_count").unwrap()[0].as_any().downcast_ref::<SequentialTable>().unwrap();
+        assert!(table.pager.get_total_page() > 3);
+        assert_ne!(90 % table.cells_num_by_page, 0);
+        assert_eq!(count(&mut db), 90);
+
+        db.reopen();
+        assert_eq!(count(&mut db), 90);
+        assert_eq!(db.query("select count(*) from tables_row_count;"), vec![vec![Value::INT(90)]]);
+    }
+
+    #[test]
+    fn insert_in_the_middle_of_a_leaf_keeps_every_cell() {
+        let mut db = TestDb::open(&["tables_middle_insert"]);
+        db.run("create table tables_middle_insert (id int primary key, name text(8));").unwrap();
+        for id in [10, 20, 30, 40, 50] {
+            db.run(&format!("insert into tables_middle_insert (id, name) values ({}, 'n{}');", id, id)).unwrap();
+        }
+        // before the first cell, between two cells and before the last one
+        for id in [5, 25, 45] {
+            db.run(&format!("insert into tables_middle_insert (id, name) values ({}, 'n{}');", id, id)).unwrap();
+        }
+        db.reopen();
+
+        let people = btree(&mut db, "tables_middle_insert");
+        assert_eq!(leaves(people), 1);
+        people.verify().unwrap();
+        let ids = [5, 10, 20, 25, 30, 40, 45, 50];
+        let expected: Vec<Vec<Value>> = ids.iter().map(|id| vec![Value::INT(*id), Value::TEXT(format!("n{}", id))]).collect();
+        assert_eq!(db.query("select id, name from tables_middle_insert;"), expected);
+    }
+
+    #[test]
+    fn text_key_keeps_its_declared_size() {
+        let mut db = TestDb::open(&["tables_text_key"]);
+        db.run("create table tables_text_key (code text(10) primary key, b blob(6));").unwrap();
+        db.run("insert into tables_text_key (code, b) values ('abcdefghij', x'010203040506');").unwrap();
+        db.reopen();
+
+        let btree = btree(&mut db, "tables_text_key");
+        assert_eq!(btree.key_size, 10);
+        assert_eq!(btree.key_type, DataType::TEXT(10));
+        let metadata = db.table_manager.get_table_metadata("tables_text_key").unwrap();
+        assert_eq!(metadata.get_field_metadata("b").unwrap().data_def.data_type, DataType::BLOB(6));
+        assert_eq!(
+            db.query("select code, b from tables_text_key where code = 'abcdefghij';"),
+            vec![vec![Value::TEXT(String::from("abcdefghij")), Value::BLOB(vec![1, 2, 3, 4, 5, 6])]]
+        );
+    }
+
+    #[test]
+    fn key_scan_stops_once_the_key_can_not_match() {
+        let mut db = TestDb::open(&["tables_stop"]);
+        create_people(&mut db, "tables_stop", 200);
+
+        let (reads, rows) = pages_read_by(&mut db, "tables_stop", "select id from tables_stop where id < 3;");
+        assert_eq!(rows, vec![vec![Value::INT(0)], vec![Value::INT(1)], vec![Value::INT(2)]]);
+        let (equal_reads, rows) = pages_read_by(&mut db, "tables_stop", "select id from tables_stop where id = 150 and name = 'row 150';");
+        assert_eq!(rows, vec![vec![Value::INT(150)]]);
+        let (scan_reads, rows) = pages_read_by(&mut db, "tables_stop", "select id from tables_stop where id >= 0 and name = 'row 3';");
+        assert_eq!(rows, vec![vec![Value::INT(3)]]);
+        assert_eq!(
+            db.query("select id from tables_stop where id <= 2;"),
+            vec![vec![Value::INT(0)], vec![Value::INT(1)], vec![Value::INT(2)]]
+        );
+
+        let btree = btree(&mut db, "tables_stop");
+        let (height, leaves) = (height(btree), leaves(btree));
+        assert!(reads <= height + 1, "{} pages read, the tree is {} high", reads, height);
+        assert!(equal_reads <= height + 1, "{} pages read, the tree is {} high", equal_reads, height);
+        assert!(scan_reads >= leaves, "{} pages read for {} leaves", scan_reads, leaves);
+    }
+
+    #[test]
+    fn boolean_key_finds_both_values() {
+        let mut db = TestDb::open(&["tables_bool_key"]);
+        db.run("create table tables_bool_key (flag bool primary key, name text(8), active bool);").unwrap();
+        db.run("insert into tables_bool_key (flag, name, active) values (true, 'yes', false);").unwrap();
+        db.run("insert into tables_bool_key (flag, name, active) values (false, 'no', true);").unwrap();
+        assert!(db.run("insert into tables_bool_key (flag, name, active) values (true, 'again', true);").is_err());
+        db.reopen();
+
+        let btree = btree(&mut db, "tables_bool_key");
+        assert_eq!(btree.key_size, 1);
+        assert_eq!(name_by_key(btree, Value::BOOL(true)), Some(Value::TEXT(String::from("yes"))));
+        assert_eq!(name_by_key(btree, Value::BOOL(false)), Some(Value::TEXT(String::from("no"))));
+
+        let text = |s: &str| vec![Value::TEXT(s.to_string())];
+        assert_eq!(db.query("select name from tables_bool_key where flag = true;"), vec![text("yes")]);
+        assert_eq!(db.query("select name from tables_bool_key where flag = false;"), vec![text("no")]);
+        assert_eq!(db.query("select name from tables_bool_key where active = true;"), vec![text("no")]);
+        assert_eq!(db.query("select name from tables_bool_key where active = false;"), vec![text("yes")]);
+        // false sorts before true
+        assert_eq!(db.query("select name from tables_bool_key;"), vec![text("no"), text("yes")]);
+    }
+
+    #[test]
+    fn negative_keys_sort_before_positive_ones() {
+        let mut db = TestDb::open(&["tables_negative_keys"]);
+        db.run("create table tables_negative_keys (id int primary key, name text(16));").unwrap();
+        // distinct keys from -299 to 299 in a shuffled order
+        let mut keys: Vec<i32> = (-150..150).map(|i| i * 7919 % 300).collect();
+        keys.extend([i32::MIN, i32::MAX]);
+        for key in &keys {
+            db.run(&format!("insert into tables_negative_keys (id, name) values ({}, 'row {}');", key, key)).unwrap();
+        }
+        db.reopen();
+
+        keys.sort();
+        let ids: Vec<Value> = keys.iter().map(|key| Value::INT(*key)).collect();
+        assert_eq!(db.query("select id from tables_negative_keys;").concat(), ids);
+        let negatives: Vec<Value> = keys.iter().filter(|key| **key < 0).map(|key| Value::INT(*key)).collect();
+        assert_eq!(db.query("select id from tables_negative_keys where id < 0;").concat(), negatives);
+
+        let btree = btree(&mut db, "tables_negative_keys");
+        assert!(height(btree) > 1);
+        for key in [i32::MIN, keys[1], keys[keys.len() / 2], i32::MAX] {
+            assert_eq!(name_by_key(btree, Value::INT(key)), Some(Value::TEXT(format!("row {}", key))));
+        }
+    }
+
+    #[test]
+    fn unknown_field_fails_before_the_scan() {
+        let mut db = TestDb::open(&["tables_unknown_field"]);
+        create_people(&mut db, "tables_unknown_field", 200);
+
+        for (sql, field) in [
+            ("select id, nope from tables_unknown_field;", "nope"),
+            ("select id from tables_unknown_field where id > 5 order by missing;", "missing"),
+            ("select distinct on (other) id from tables_unknown_field;", "other"),
+        ] {
+            db.reopen();
+            let error = db.run(sql).err().unwrap();
+            assert!(error.contains(&format!("`{}`", field)), "{}", error);
+            assert_eq!(btree(&mut db, "tables_unknown_field").pager.pages_read(), 0, "{}", sql);
+        }
+        let (reads, _) = pages_read_by(&mut db, "tables_unknown_field", "select id from tables_unknown_field;");
+        assert!(reads > 0);
+    }
+}
@@ -1 +1,3 @@
-pub(crate) mod utils;
\ No newline at end of file
+pub(crate) mod utils;
+#[cfg(test)]
+pub(crate) mod test_utils;
@@ -0,0 +1,70 @@
+use std::sync::{Mutex, MutexGuard};
+
+use crate::sql_engine::sql_parser::SqlParser;
+use crate::sql_engine::sql_structs::{SqlStmt, Value};
+use crate::storage_engine::common::{SelectResult, TableManager};
+
+/*
+    Every test works in the same data folder: a test holds the lock while it runs
+    and only uses tables of its own, dropped when it starts.
+ */
+static DATA_FOLDER_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) struct TestDb {
+    pub(crate) table_manager: TableManager,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestDb {
+    pub(crate) fn open(tables: &[&str]) -> TestDb {
+        let guard = DATA_FOLDER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut db = TestDb { table_manager: TableManager::new(None).unwrap(), _guard: guard };
+        for table in tables {
+            db.run(&format!("drop table if exists {};", table)).unwrap();
+        }
+        db
+    }
+
+    /*
+        the SELECT statements return their result, the other statements None
+     */
+    pub(crate) fn run(&mut self, sql: &str) -> Result<Option<SelectResult>, String> {
+        let table_manager = &mut self.table_manager;
+        match SqlParser::parse_sql(sql.to_string())? {
+            SqlStmt::SELECT(mut select) => return select.execute(table_manager).map(Some),
+            SqlStmt::INSERT(mut insert) => insert.execute(table_manager)?,
+            SqlStmt::CREATE(mut create) => {
+                create.execute(table_manager)?;
+            }
+            SqlStmt::ALTER(alter) => alter.execute(table_manager)?,
+            SqlStmt::DROP(drop) => {
+                drop.execute(table_manager)?;
+            }
+            SqlStmt::REINDEX(reindex) => {
+                reindex.execute(table_manager)?;
+            }
+            SqlStmt::PRAGMA(pragma) => {
+                pragma.execute(table_manager)?;
+            }
+        }
+        Ok(None)
+    }
+
+    /*
+        the rows of a SELECT statement
+     */
+    pub(crate) fn query(&mut self, sql: &str) -> Vec<Vec<Value>> {
+        let result = self.run(sql).unwrap().unwrap();
+        (0..result.len())
+            .map(|row| (0..result.fields.len()).map(|column| (*result.get(row, column)).clone()).collect())
+            .collect()
+    }
+
+    /*
+        writes the cached pages and opens the data folder again, as a new session would
+     */
+    pub(crate) fn reopen(&mut self) {
+        self.table_manager.flush_to_disk();
+        self.table_manager = TableManager::new(None).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use std::{fs, ptr};
+use std::io::{BufRead, Write};
 use std::ffi::OsString;
 use std::path::PathBuf;
 
@@ -62,6 +63,70 @@ pub(crate) fn copy<T>(src: *const T, dst: *mut T, count: usize) {
     }
 }
 
+/*
+    Numbers are stored little-endian whatever the host, so a data folder can be read on any machine.
+    Page indices and counters are stored as 4 bytes on disk, they go through u32
+    so the same bytes are read and written whatever the size of usize.
+ */
+pub(crate) fn read_u32(src: *const u8) -> usize {
+    unsafe { u32::from_le_bytes(ptr::read_unaligned(src as *const [u8; 4])) as usize }
+}
+
+pub(crate) fn write_u32(dst: *mut u8, value: usize) {
+    unsafe {
+        ptr::copy_nonoverlapping((value as u32).to_le_bytes().as_ptr(), dst, 4);
+    }
+}
+
+/*
+    Unsigned little-endian number of `size` bytes, e.g. the 2 bytes length of a blob
+ */
+pub(crate) fn read_uint(src: *const u8, size: usize) -> usize {
+    (0..size).rev().fold(0, |value, i| value << 8 | unsafe { *src.add(i) } as usize)
+}
+
+pub(crate) fn write_uint(dst: *mut u8, value: usize, size: usize) {
+    for i in 0..size {
+        unsafe { *dst.add(i) = (value >> (8 * i)) as u8 }
+    }
+}
+
+/*
+    CRC-32 (IEEE 802.3), bitwise to avoid a lookup table
+ */
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/*
+    `s` as a quoted JSON string, quotes, backslashes and control characters escaped
+ */
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
 pub(crate) fn u8_array_to_string(array: &[u8]) -> String {
     let end = array.iter().position(|c| *c == 0).unwrap_or(array.len());
     String::from_utf8_lossy(&array[..end]).to_string()
@@ -89,3 +154,91 @@ pub(crate) fn list_files_of_folder(
         }
     }
 }
+
+/*
+    reads lines from `input` until the statement is terminated by `;`,
+    writing `prompt` to `output` before the first line and `continuation` before the following ones.
+    A line ending with `\c` or an end of input in the middle of a statement cancels it, an empty string is returned.
+    None once the input is closed at the prompt.
+ */
+pub(crate) fn read_statement(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    prompt: &str,
+    continuation: &str,
+) -> Option<String> {
+    let mut statement = String::new();
+    write!(output, "{}", prompt).expect("write failed!");
+    loop {
+        output.flush().expect("flush failed!");
+        match input.read_line(&mut statement) {
+            Ok(0) | Err(_) if statement.trim().is_empty() => return None,
+            Ok(0) | Err(_) => {
+                writeln!(output).expect("write failed!");
+                return Some(String::new());
+            }
+            Ok(_) => {}
+        }
+        if statement.trim_end().ends_with("\\c") {
+            return Some(String::new());
+        }
+        if statement.trim().ends_with(";") {
+            break;
+        }
+        write!(output, "{}", continuation).expect("write failed!");
+    }
+    Some(statement.trim().to_string())
+}
+
+/*
+    Levenshtein distance, the number of single character edits turning `a` into `b`
+ */
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::utils::read_statement;
+
+    #[test]
+    fn statement_is_read_until_its_semicolon() {
+        let mut input = "select id,\n  name from t\nwhere id = 1;\nselect 2;\n".as_bytes();
+        let mut output = vec![];
+        assert_eq!(
+            read_statement(&mut input, &mut output, "sql>", "...>").unwrap(),
+            "select id,\n  name from t\nwhere id = 1;"
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "sql>...>...>");
+
+        // the next statement starts on the following line
+        let mut output = vec![];
+        assert_eq!(read_statement(&mut input, &mut output, "sql>", "...>").unwrap(), "select 2;");
+        assert_eq!(String::from_utf8(output).unwrap(), "sql>");
+    }
+
+    #[test]
+    fn end_of_input_cancels_the_pending_statement() {
+        let mut output = vec![];
+        // the statement is cut by the end of input, then the input is closed at the prompt
+        let mut input = "select id\nfrom t".as_bytes();
+        assert_eq!(read_statement(&mut input, &mut output, "sql>", ">"), Some(String::new()));
+        assert_eq!(read_statement(&mut input, &mut output, "sql>", ">"), None);
+        assert_eq!(read_statement(&mut "".as_bytes(), &mut output, "sql>", ">"), None);
+
+        // `\c` drops the lines read so far, the next statement is read from scratch
+        let mut input = "select id\nfrom t \\c\nselect 1;\n".as_bytes();
+        assert_eq!(read_statement(&mut input, &mut output, "sql>", ">"), Some(String::new()));
+        assert_eq!(read_statement(&mut input, &mut output, "sql>", ">").unwrap(), "select 1;");
+    }
+}